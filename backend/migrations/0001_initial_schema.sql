@@ -0,0 +1,93 @@
+-- Initial schema (the one of database/init.sql, which created the existing databases: every
+-- statement is skipped when its object already exists)
+
+-- Create crypto_symbol Table
+CREATE TABLE IF NOT EXISTS crypto_symbol (
+    id SERIAL PRIMARY KEY,
+    symbol VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    volume FLOAT NOT NULL,
+    last_updated TIMESTAMP NOT NULL,
+    available BOOLEAN DEFAULT TRUE,
+    UNIQUE (symbol)
+);
+
+-- Create crypto_interval ENUM type
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'crypto_interval') THEN
+        CREATE TYPE crypto_interval AS ENUM ('Int1m', 'Int5m', 'Int15m', 'Int30m', 'Int1h', 'Int2h', 'Int4h', 'Int6h', 'Int8h', 'Int12h', 'Int1d', 'Int3d', 'Int1w', 'Int1M');
+    END IF;
+END $$;
+
+-- Create crypto_list Table
+CREATE TABLE IF NOT EXISTS crypto_list (
+    id SERIAL PRIMARY KEY,
+    hidden BOOLEAN DEFAULT FALSE NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    interval crypto_interval NOT NULL,
+    limit_count INTEGER NOT NULL,
+    type VARCHAR(255) NOT NULL
+);
+
+-- Create junction table for many-to-many relationship between crypto_list and crypto_symbol
+CREATE TABLE IF NOT EXISTS crypto_list_x_crypto_symbol (
+    crypto_list_id INTEGER NOT NULL,
+    crypto_symbol_id INTEGER NOT NULL,
+    PRIMARY KEY (crypto_list_id, crypto_symbol_id),
+    FOREIGN KEY (crypto_list_id) REFERENCES crypto_list(id),
+    FOREIGN KEY (crypto_symbol_id) REFERENCES crypto_symbol(id)
+);
+
+-- Create mh_object Table without foreign key constraint
+CREATE TABLE IF NOT EXISTS mh_object (
+    id SERIAL PRIMARY KEY,
+    hidden BOOLEAN DEFAULT FALSE NOT NULL,
+    mh_algorithm_name TEXT NOT NULL,
+    mh_parameters TEXT NOT NULL,
+    other_parameters TEXT
+);
+
+-- Create result Table without foreign key constraint
+CREATE TABLE IF NOT EXISTS result (
+    id SERIAL PRIMARY KEY,
+    results TEXT NOT NULL,
+    other_parameters TEXT
+);
+
+-- Create state ENUM type
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'state_enum') THEN
+        CREATE TYPE state_enum AS ENUM ('CREATED', 'PENDING', 'RUNNING', 'CANCELLING', 'CANCELLED', 'COMPLETED', 'FAILED');
+    END IF;
+END $$;
+
+-- Create task Table
+CREATE TABLE IF NOT EXISTS task (
+    id SERIAL PRIMARY KEY,
+    state state_enum NOT NULL DEFAULT 'CREATED',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    other_parameters TEXT,
+    mh_object_id INTEGER,
+    crypto_list_id INTEGER,
+    indicator_combination_id INTEGER,
+    result_id INTEGER,
+    FOREIGN KEY (mh_object_id) REFERENCES mh_object(id),
+    FOREIGN KEY (crypto_list_id) REFERENCES crypto_list(id),
+    FOREIGN KEY (result_id) REFERENCES result(id)
+);
+
+-- Create indicator combination Table
+CREATE TABLE IF NOT EXISTS indicator_combination (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    hidden BOOLEAN DEFAULT FALSE NOT NULL
+);
+
+-- Create indicator in combination Table
+CREATE TABLE IF NOT EXISTS indicator_in_combination (
+    id SERIAL PRIMARY KEY,
+    indicator_combination_id INTEGER NOT NULL,
+    indicator_struct_name TEXT NOT NULL,
+    parameters TEXT NOT NULL,
+    FOREIGN KEY (indicator_combination_id) REFERENCES indicator_combination(id)
+);
@@ -0,0 +1,9 @@
+-- Create checkpoint Table (last saved state of the optimisation run of a task, so that an
+-- interrupted task can be resumed)
+CREATE TABLE IF NOT EXISTS checkpoint (
+    task_id INTEGER PRIMARY KEY,
+    iteration INTEGER NOT NULL,
+    state TEXT NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (task_id) REFERENCES task(id) ON DELETE CASCADE
+);
@@ -0,0 +1,21 @@
+-- Markets of the crypto symbols and lists (spot, USDⓈ-M futures, COIN-M futures), the existing
+-- ones being USDⓈ-M futures. A symbol is unique in its market
+
+-- Create market_type ENUM type
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_type WHERE typname = 'market_type') THEN
+        CREATE TYPE market_type AS ENUM ('Spot', 'UsdM', 'CoinM');
+    END IF;
+END $$;
+
+ALTER TABLE crypto_symbol ADD COLUMN IF NOT EXISTS market market_type NOT NULL DEFAULT 'UsdM';
+ALTER TABLE crypto_list ADD COLUMN IF NOT EXISTS market market_type NOT NULL DEFAULT 'UsdM';
+
+-- Unique symbol in each market (instead of unique symbol, in the first databases). The lists have
+-- no unique key, their market column is all they need
+ALTER TABLE crypto_symbol DROP CONSTRAINT IF EXISTS crypto_symbol_symbol_key;
+DO $$ BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'crypto_symbol_symbol_market_key') THEN
+        ALTER TABLE crypto_symbol ADD CONSTRAINT crypto_symbol_symbol_market_key UNIQUE (symbol, market);
+    END IF;
+END $$;
@@ -0,0 +1,129 @@
+-- Move the klines of the per-symbol tables (klines_<prefix><symbol>_<interval>, prefix: spot_,
+-- coinm_ or none for USDⓈ-M futures) and their indicator columns (i_...) into the kline and
+-- indicator_value tables, then drop the per-symbol tables
+
+-- Create kline Table (klines of every market, symbol and interval, one partition per interval)
+-- open_time and close_time are in milliseconds
+CREATE TABLE IF NOT EXISTS kline (
+    market market_type NOT NULL,
+    symbol VARCHAR(255) NOT NULL,
+    interval crypto_interval NOT NULL,
+    open_time BIGINT NOT NULL,
+    open FLOAT NOT NULL,
+    high FLOAT NOT NULL,
+    low FLOAT NOT NULL,
+    close FLOAT NOT NULL,
+    volume FLOAT NOT NULL,
+    close_time BIGINT NOT NULL,
+    quote_asset_volume FLOAT NOT NULL,
+    number_of_trades BIGINT NOT NULL,
+    taker_buy_base_asset_volume FLOAT NOT NULL,
+    taker_buy_quote_asset_volume FLOAT NOT NULL,
+    PRIMARY KEY (market, symbol, interval, open_time)
+) PARTITION BY LIST (interval);
+
+CREATE TABLE IF NOT EXISTS kline_1m PARTITION OF kline FOR VALUES IN ('Int1m');
+CREATE TABLE IF NOT EXISTS kline_5m PARTITION OF kline FOR VALUES IN ('Int5m');
+CREATE TABLE IF NOT EXISTS kline_15m PARTITION OF kline FOR VALUES IN ('Int15m');
+CREATE TABLE IF NOT EXISTS kline_30m PARTITION OF kline FOR VALUES IN ('Int30m');
+CREATE TABLE IF NOT EXISTS kline_1h PARTITION OF kline FOR VALUES IN ('Int1h');
+CREATE TABLE IF NOT EXISTS kline_2h PARTITION OF kline FOR VALUES IN ('Int2h');
+CREATE TABLE IF NOT EXISTS kline_4h PARTITION OF kline FOR VALUES IN ('Int4h');
+CREATE TABLE IF NOT EXISTS kline_6h PARTITION OF kline FOR VALUES IN ('Int6h');
+CREATE TABLE IF NOT EXISTS kline_8h PARTITION OF kline FOR VALUES IN ('Int8h');
+CREATE TABLE IF NOT EXISTS kline_12h PARTITION OF kline FOR VALUES IN ('Int12h');
+CREATE TABLE IF NOT EXISTS kline_1d PARTITION OF kline FOR VALUES IN ('Int1d');
+CREATE TABLE IF NOT EXISTS kline_3d PARTITION OF kline FOR VALUES IN ('Int3d');
+CREATE TABLE IF NOT EXISTS kline_1w PARTITION OF kline FOR VALUES IN ('Int1w');
+CREATE TABLE IF NOT EXISTS kline_1mo PARTITION OF kline FOR VALUES IN ('Int1M');
+
+-- Create indicator_value Table (values of the indicator columns, ex: i_ema_20, for each kline)
+CREATE TABLE IF NOT EXISTS indicator_value (
+    market market_type NOT NULL,
+    symbol VARCHAR(255) NOT NULL,
+    interval crypto_interval NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    open_time BIGINT NOT NULL,
+    value FLOAT NOT NULL,
+    PRIMARY KEY (market, symbol, interval, name, open_time),
+    FOREIGN KEY (market, symbol, interval, open_time)
+        REFERENCES kline(market, symbol, interval, open_time) ON DELETE CASCADE
+);
+
+DO $$
+DECLARE
+    old_table RECORD;
+    column_record RECORD;
+    rest TEXT;
+    market_value market_type;
+    symbol_value TEXT;
+    interval_value crypto_interval;
+    average_gap DOUBLE PRECISION;
+BEGIN
+    FOR old_table IN
+        SELECT table_name FROM information_schema.tables
+        WHERE table_schema = 'public' AND table_name LIKE 'klines\_%'
+    LOOP
+        rest := substring(old_table.table_name FROM 8);
+        IF rest LIKE 'spot\_%' THEN
+            market_value := 'Spot';
+            rest := substring(rest FROM 6);
+        ELSIF rest LIKE 'coinm\_%' THEN
+            market_value := 'CoinM';
+            rest := substring(rest FROM 7);
+        ELSE
+            market_value := 'UsdM';
+        END IF;
+
+        -- The interval is after the last underscore (COIN-M symbols contain one, ex: btcusd_perp)
+        symbol_value := upper(regexp_replace(rest, '_[^_]*$', ''));
+        SELECT e.value INTO interval_value
+        FROM unnest(enum_range(NULL::crypto_interval)) AS e(value)
+        WHERE lower(e.value::TEXT) = substring(rest FROM '_([^_]*)$')
+        ORDER BY e.value
+        LIMIT 1;
+        IF interval_value IS NULL THEN
+            RAISE NOTICE 'Skipping %: unknown interval', old_table.table_name;
+            CONTINUE;
+        END IF;
+
+        -- Int1m and Int1M had the same table name, told apart by the time between the klines
+        IF interval_value = 'Int1m' THEN
+            EXECUTE format(
+                'SELECT (MAX(open_time) - MIN(open_time))::FLOAT / NULLIF(COUNT(*) - 1, 0) FROM %I',
+                old_table.table_name
+            ) INTO average_gap;
+            IF average_gap >= 28 * 86400000::BIGINT THEN
+                interval_value := 'Int1M';
+            END IF;
+        END IF;
+
+        RAISE NOTICE 'Moving % (% % %)', old_table.table_name, market_value, symbol_value, interval_value;
+        EXECUTE format(
+            'INSERT INTO kline (market, symbol, interval, open_time, open, high, low, close, volume,
+                close_time, quote_asset_volume, number_of_trades, taker_buy_base_asset_volume,
+                taker_buy_quote_asset_volume)
+            SELECT $1, $2, $3, open_time, open, high, low, close, volume, close_time,
+                quote_asset_volume, number_of_trades, taker_buy_base_asset_volume,
+                taker_buy_quote_asset_volume
+            FROM %I
+            ON CONFLICT DO NOTHING',
+            old_table.table_name
+        ) USING market_value, symbol_value, interval_value;
+
+        FOR column_record IN
+            SELECT column_name FROM information_schema.columns
+            WHERE table_schema = 'public' AND table_name = old_table.table_name
+            AND column_name LIKE 'i\_%'
+        LOOP
+            EXECUTE format(
+                'INSERT INTO indicator_value (market, symbol, interval, name, open_time, value)
+                SELECT $1, $2, $3, $4, open_time, %I FROM %I WHERE %I IS NOT NULL
+                ON CONFLICT DO NOTHING',
+                column_record.column_name, old_table.table_name, column_record.column_name
+            ) USING market_value, symbol_value, interval_value, column_record.column_name;
+        END LOOP;
+
+        EXECUTE format('DROP TABLE %I', old_table.table_name);
+    END LOOP;
+END $$;
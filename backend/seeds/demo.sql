@@ -0,0 +1,38 @@
+-- Demo data, inserted at startup into an empty database when DATABASE_SEED=true
+-- (the ids are the ones of a new database)
+
+-- Insert data into crypto_symbol Table
+INSERT INTO crypto_symbol (symbol, name, volume, last_updated)
+VALUES ('BTC', 'Bitcoin', 1.0, '2021-01-01 00:00:00'),
+       ('ETH', 'Ethereum', 0.5, '2021-01-01 00:00:00'),
+       ('XRP', 'Ripple', 0.3, '2021-01-01 00:00:00');
+
+-- Insert data into crypto_list Table
+INSERT INTO crypto_list (name, type, interval, limit_count)
+VALUES ('Top 2', 'Top', 'Int1h', 20);
+
+-- Insert data into crypto_list_x_crypto_symbol Table
+INSERT INTO crypto_list_x_crypto_symbol (crypto_list_id, crypto_symbol_id)
+VALUES (1, 1),
+       (1, 2);
+
+-- Insert data into mh_object Table
+INSERT INTO mh_object (mh_algorithm_name, mh_parameters, other_parameters)
+VALUES ('mh_1', '{"param1": "value1"}', '{"other_param1": "other_value1"}'),
+       ('mh_2', '{"param2": "value2"}', '{"other_param2": "other_value2"}');
+
+-- Insert data into result Table
+INSERT INTO result (results, other_parameters)
+VALUES ('{"result1": "result_value1"}', '{"other_result1": "other_result_value1"}'),
+       ('{"result2": "result_value2"}', '{"other_result2": "other_result_value2"}');
+
+-- Insert data into task Table
+INSERT INTO task (state, other_parameters, mh_object_id, crypto_list_id, result_id)
+VALUES ('PENDING', '{"task_param1": "task_value1"}', 1, 1, NULL),
+       ('COMPLETED', '{"task_param2": "task_value2"}', 2, 1, 2);
+
+-- Update task state to 'COMPLETED' and set result_id to 1
+UPDATE task
+SET state = 'FAILED',
+    result_id = 1
+WHERE id = 1;
@@ -1,16 +1,16 @@
-use super::super::klines::utils;
+use sqlx::PgPool;
+
+use super::super::klines::utils::KlineSeries;
 use crate::objects::indicators::IndicatorTrait;
 use crate::objects::{indicators::Indicator, klines::KlineCollection};
 
 pub async fn compute_indicator(
+    pool: &PgPool,
     indicator: &mut Indicator,
     kline_collection: &KlineCollection,
 ) -> Result<(), sqlx::Error> {
     // We assume klines are already present in the database
-    let table_name = utils::get_table_name_collection(&kline_collection);
-
-    // Check if the table exists
-    let pool = crate::utils::db::get_new_pool().await;
+    let series = KlineSeries::from_collection(kline_collection);
 
     // Calculate indicator values
     indicator.calculate(kline_collection);
@@ -18,38 +18,37 @@ pub async fn compute_indicator(
     //
     // println!("Indicator values: {:?}", indicator);
 
-    // Insert the indicator values into the database
+    // Insert the indicator values into the database, one batch per column
+    // (the values that cannot be computed are not stored)
     let values: Vec<&Vec<Option<f64>>> = indicator.get_values();
-    let values_elements_len = values[0].len();
     let columns = indicator.column_names();
-    for i in 0..values_elements_len {
-        let mut columns_str = String::new();
-        for j in 0..values.len() {
-            columns_str.push_str(&columns[j]);
-            columns_str.push_str(" = ");
-            columns_str.push_str(&values[j][i].unwrap().to_string());
-            if j < values.len() - 1 {
-                columns_str.push_str(", ");
-            }
-        }
+    for (column, column_values) in columns.iter().zip(values) {
+        let (open_times, column_values): (Vec<i64>, Vec<f64>) = column_values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| {
+                let kline = kline_collection.get(i as i32)?;
+                value.map(|value| (kline.open_time.timestamp_millis(), value))
+            })
+            .unzip();
 
-        sqlx::query(&format!(
+        sqlx::query(
             r#"
-            UPDATE {}
-            SET {}
-            WHERE open_time = {}
+            INSERT INTO indicator_value (market, symbol, interval, name, open_time, value)
+            SELECT $1, $2, $3, $4, open_time, value
+            FROM UNNEST($5::BIGINT[], $6::FLOAT8[]) AS t(open_time, value)
+            ON CONFLICT (market, symbol, interval, name, open_time)
+            DO UPDATE SET value = EXCLUDED.value
             "#,
-            table_name,
-            columns_str,
-            kline_collection
-                .get(i.try_into().unwrap())
-                .unwrap()
-                .open_time
-                .timestamp_millis()
-        ))
-        .execute(&pool)
-        .await
-        .unwrap();
+        )
+        .bind(&series.market)
+        .bind(&series.symbol)
+        .bind(&series.interval)
+        .bind(column)
+        .bind(&open_times)
+        .bind(&column_values)
+        .execute(pool)
+        .await?;
     }
 
     Ok(())
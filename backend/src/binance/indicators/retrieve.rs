@@ -1,60 +1,64 @@
-use sqlx::Row;
+use sqlx::{PgPool, Row};
 
-use super::super::klines;
+use super::super::klines::{self, utils::KlineSeries};
 use super::compute;
 use crate::objects::{
     indicators::{Indicator, IndicatorTrait},
     klines::KlineCollection,
 };
 
+// Values of the indicator columns for each kline of the collection (one column per indicator
+// column, NULL when the value is not stored)
 async fn query_present_rows(
-    pool: &sqlx::PgPool,
-    table_name: &str,
+    pool: &PgPool,
+    series: &KlineSeries,
     kline_collection: &KlineCollection,
     indicator: &Indicator,
 ) -> Vec<sqlx::postgres::PgRow> {
+    let columns = indicator.column_names();
+    let values = (0..columns.len())
+        .map(|i| format!("MAX(v.value) FILTER (WHERE v.name = ${})", i + 6))
+        .collect::<Vec<String>>()
+        .join(", ");
+
     // Query the database to retrieve the indicator columns
-    sqlx::query(&format!(
+    let sql = format!(
         r#"
         SELECT {}
-        FROM {}
-        WHERE open_time BETWEEN {} AND {}
-        ORDER BY open_time ASC
+        FROM kline k
+        LEFT JOIN indicator_value v
+            ON v.market = k.market AND v.symbol = k.symbol AND v.interval = k.interval
+            AND v.open_time = k.open_time
+        WHERE k.market = $1 AND k.symbol = $2 AND k.interval = $3
+        AND k.open_time BETWEEN $4 AND $5
+        GROUP BY k.open_time
+        ORDER BY k.open_time ASC
         "#,
-        indicator.column_names().join(", "),
-        table_name,
-        kline_collection.get_first_open_time().timestamp_millis(),
-        kline_collection.get_last_open_time().timestamp_millis()
-    ))
-    .fetch_all(pool)
-    .await
-    .unwrap()
+        values
+    );
+    let mut query = sqlx::query(&sql)
+        .bind(&series.market)
+        .bind(&series.symbol)
+        .bind(&series.interval)
+        .bind(kline_collection.get_first_open_time().timestamp_millis())
+        .bind(kline_collection.get_last_open_time().timestamp_millis());
+    for column in &columns {
+        query = query.bind(column);
+    }
+    query.fetch_all(pool).await.unwrap()
 }
 
 // Retrieve from the database the klines indicators for the given symbol, interval and limit
 // if the klines indicators are not present in the database, they are computed and stored
 pub async fn retrieve_indicator(
+    pool: &PgPool,
     indicator: &mut Indicator,
     kline_collection: &KlineCollection,
 ) -> Result<(), sqlx::Error> {
     // Retrieve the klines indicators from the database
     // We assume klines are already present in the database
-    let table_name: String = klines::utils::get_table_name_collection(kline_collection);
+    let series = KlineSeries::from_collection(kline_collection);
     let indicator_columns: Vec<String> = indicator.column_names();
-    let pool = crate::utils::db::get_new_pool().await;
-
-    // Check if the indicator columns exists
-    let columns_exists =
-        klines::utils::check_columns_exists(&pool, &table_name, &indicator_columns).await;
-
-    // If the indicator columns does not exist, create them
-    if !columns_exists {
-        println!("Creating indicator columns...");
-        klines::utils::create_indicator_columns(&pool, &table_name, &indicator_columns)
-            .await
-            .unwrap();
-    }
-
     // Query the database to retrieve the open_time of rows that are missing the indicator columns
     // and between the first and last open time
     // let first_open_time = kline_collection.get_first_open_time();
@@ -84,7 +88,7 @@ pub async fn retrieve_indicator(
     // println!("Missing rows: {}", missing_rows); //, result);
 
     // Query the database to retrieve the indicator columns
-    let rows = query_present_rows(&pool, &table_name, kline_collection, indicator).await;
+    let rows = query_present_rows(pool, &series, kline_collection, indicator).await;
     println!("Rows: {}, {}", rows.len(), kline_collection.get_length());
 
     if rows.len() as i32 != kline_collection.get_length() {
@@ -96,11 +100,7 @@ pub async fn retrieve_indicator(
 
     let missing_rows_count = rows
         .iter()
-        .filter(|row| {
-            indicator_columns
-                .iter()
-                .all(|column| row.get::<Option<f64>, _>(column.as_str()).is_none())
-        })
+        .filter(|row| (0..indicator_columns.len()).all(|i| row.get::<Option<f64>, _>(i).is_none()))
         .count();
 
     println!("Missing rows count: {}", missing_rows_count);
@@ -110,7 +110,7 @@ pub async fn retrieve_indicator(
     // Compute the indicator columns for the missing rows
     if missing_rows_count > 0 {
         println!("Computing indicator columns...");
-        compute::compute_indicator(indicator, kline_collection)
+        compute::compute_indicator(pool, indicator, kline_collection)
             .await
             .unwrap();
     }
@@ -118,7 +118,7 @@ pub async fn retrieve_indicator(
     // println!("Indicator: {:?}", indicator);
 
     // // Query the database to retrieve the indicator columns
-    // let rows = query_present_rows(&pool, &table_name, kline_collection, indicator).await;
+    // let rows = query_present_rows(&pool, &series, kline_collection, indicator).await;
 
     // if rows.len() as i32 != kline_collection.get_length() {
     //     println!("Error: missing rows in the indicator columns");
@@ -134,6 +134,7 @@ pub async fn retrieve_indicator(
 }
 
 pub async fn retrieve_extended_klines(
+    pool: &PgPool,
     kline_collection: &mut KlineCollection,
     indicator: &Indicator,
 ) -> Result<(), sqlx::Error> {
@@ -141,7 +142,7 @@ pub async fn retrieve_extended_klines(
     let n_before_needed = indicator.n_before_needed();
 
     // Retrieve the klines needed to compute the indicator (extended collection)
-    klines::retrieve::retrieve_klines_extend(kline_collection, n_before_needed)
+    klines::retrieve::retrieve_klines_extend(pool, kline_collection, n_before_needed)
         .await
         .unwrap();
 
@@ -149,6 +150,7 @@ pub async fn retrieve_extended_klines(
 }
 
 pub async fn retrieve_extended_klines_max(
+    pool: &PgPool,
     kline_collection: &mut KlineCollection,
     indicators: &Vec<Indicator>,
 ) -> Result<(), sqlx::Error> {
@@ -159,7 +161,7 @@ pub async fn retrieve_extended_klines_max(
         .unwrap();
 
     // Retrieve the klines needed to compute the indicator (extended collection)
-    retrieve_extended_klines(kline_collection, &max_indicator)
+    retrieve_extended_klines(pool, kline_collection, &max_indicator)
         .await
         .unwrap();
 
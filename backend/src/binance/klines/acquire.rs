@@ -1,15 +1,13 @@
-use chrono::TimeZone;
-use reqwest::Client;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use sqlx::PgPool;
-use std::time::Duration;
-use tokio::time::sleep;
 
-use super::utils;
-use crate::{objects::intervals::CryptoInterval, utils::loading};
+use super::progress::{KlinesProgress, KlinesProgressReporter};
+use super::utils::{self, KlineSeries};
+use crate::market_data::{KlinesRequest, MarketDataProvider, MarketDataProviderTrait};
+use crate::objects::{intervals::CryptoInterval, klines::Kline, objects::CryptoSymbol};
 
-const BINANCE_FUTURES_API_URL: &str = "https://fapi.binance.com";
-const KLINES_LIMIT: &str = "250";
-const REQUESTS_DELAY_MILLIS: u64 = 300;
+const KLINES_LIMIT: usize = 250;
 
 #[derive(PartialEq)]
 enum KlinesFetchType {
@@ -17,131 +15,310 @@ enum KlinesFetchType {
     Older,
 }
 
+/// Klines to acquire
+#[derive(Debug, Clone)]
+pub enum KlinesWindow {
+    Last(i64),                           // the most recent klines
+    Range(DateTime<Utc>, DateTime<Utc>), // the klines opened in [start, end)
+}
+
+// Klines of several symbols, acquired concurrently (at most `concurrency` symbols at a time)
+// The providers of a market share the same request weight budget, so the concurrent requests
+// slow down together instead of being rate limited
+pub async fn acquire_symbols_klines(
+    pool: &PgPool,
+    symbols: &[CryptoSymbol],
+    interval: &CryptoInterval,
+    window: &KlinesWindow,
+    force_fetch: bool,
+    concurrency: usize,
+    reporter: Option<&KlinesProgressReporter>,
+) -> Vec<(CryptoSymbol, Result<(), sqlx::Error>)> {
+    stream::iter(symbols)
+        .map(|symbol| async move {
+            let result = match (MarketDataProvider::from_env(&symbol.market), window) {
+                (Ok(provider), KlinesWindow::Last(limit)) => {
+                    acquire_klines(
+                        pool,
+                        &provider,
+                        symbol,
+                        interval,
+                        &Some(*limit),
+                        force_fetch,
+                        reporter,
+                    )
+                    .await
+                }
+                (Ok(provider), KlinesWindow::Range(start, end)) => {
+                    acquire_range(
+                        pool,
+                        &provider,
+                        symbol,
+                        interval,
+                        (*start, *end),
+                        force_fetch,
+                        reporter,
+                    )
+                    .await
+                }
+                (Err(e), _) => Err(sqlx::Error::Protocol(e)),
+            };
+            (symbol.clone(), result)
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await
+}
+
 pub async fn acquire_klines(
     pool: &PgPool,
-    symbol: &str,
+    provider: &MarketDataProvider,
+    symbol: &CryptoSymbol,
     interval: &CryptoInterval,
     limit: &Option<i64>,
-    table_exists: bool,
-    table_length: &mut i64,
     force_fetch: bool,
+    reporter: Option<&KlinesProgressReporter>,
 ) -> Result<(), sqlx::Error> {
-    let table_name = utils::get_table_name(symbol, &interval);
-    println!("Table name: {}", table_name);
-
-    // let table_exists = utils::check_table_exists(&pool, &table_name).await;
-    // let mut table_length = utils::get_table_length(&pool, &table_name).await;
+    let series = KlineSeries::new(&symbol.market, &symbol.symbol, interval);
+    println!("Klines: {:?}", series);
 
-    println!("Table length: {}", table_length);
+    let length = utils::get_length(pool, &series).await;
+    let mut progress = KlinesProgress::new(symbol, interval, length, *limit);
+    if let Some(reporter) = reporter {
+        reporter.report(&progress);
+    }
 
-    let client = Client::new();
+    println!("Klines length: {}", length);
 
-    if !table_exists || *table_length == 0 {
-        println!("Table doesn't exist, creating table and fetching historical data...");
-        if !table_exists {
-            let result = utils::create_klines_table(&pool, &table_name).await;
-            if result.is_err() {
-                return Err(result.err().unwrap());
-            }
-        }
+    if length == 0 {
+        println!("No klines stored, fetching historical data...");
         loop_fetch_klines(
             &pool,
-            &client,
-            &table_name,
-            symbol,
-            interval,
-            table_length,
-            limit,
+            provider,
+            &series,
+            &mut progress,
+            reporter,
             &KlinesFetchType::Older,
             false,
         )
-        .await;
+        .await
+        .map_err(sqlx::Error::Protocol)?;
     } else {
         println!(
-            "Table exists, fetching (recent ({})) and older data...",
+            "Klines stored, fetching (recent ({})) and older data...",
             force_fetch
         );
         if force_fetch {
             loop_fetch_klines(
                 &pool,
-                &client,
-                &table_name,
-                symbol,
-                interval,
-                table_length,
-                limit,
+                provider,
+                &series,
+                &mut progress,
+                reporter,
                 &KlinesFetchType::Recent,
                 force_fetch,
             )
-            .await;
+            .await
+            .map_err(sqlx::Error::Protocol)?;
         }
         loop_fetch_klines(
             &pool,
-            &client,
-            &table_name,
-            symbol,
-            interval,
-            table_length,
-            limit,
+            provider,
+            &series,
+            &mut progress,
+            reporter,
             &KlinesFetchType::Older,
             false,
         )
-        .await;
+        .await
+        .map_err(sqlx::Error::Protocol)?;
     }
 
-    // Check the integrity of the klines
-    check_klines_integrity(&pool, &table_name).await;
+    // Check the integrity of the klines, and fetch the missing ones
+    backfill_gaps(pool, provider, &series).await?;
+
+    progress.done = true;
+    if let Some(reporter) = reporter {
+        reporter.report(&progress);
+    }
 
     Ok(())
 }
 
-async fn loop_fetch_klines(
+// Klines opened in [start, end), only the missing ones are downloaded
+pub async fn acquire_range(
     pool: &PgPool,
-    client: &Client,
-    table_name: &str,
-    symbol: &str,
+    provider: &MarketDataProvider,
+    symbol: &CryptoSymbol,
     interval: &CryptoInterval,
-    table_length: &mut i64,
-    limit: &Option<i64>,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    force_fetch: bool,
+    reporter: Option<&KlinesProgressReporter>,
+) -> Result<(), sqlx::Error> {
+    let series = KlineSeries::new(&symbol.market, &symbol.symbol, interval);
+
+    // The kline currently open is not stored
+    let first_open_time = get_first_open_time(interval, start);
+    let end = end.min(interval.get_open_time(Utc::now()));
+    let range = (start.timestamp_millis(), end.timestamp_millis());
+    let expected = count_open_times(interval, (start, end));
+    let stored = utils::get_range_length(pool, &series, range).await?;
+    let mut progress = KlinesProgress::new(symbol, interval, stored, Some(expected));
+    if let Some(reporter) = reporter {
+        reporter.report(&progress);
+    }
+    println!(
+        "{}: {} / {} klines from {} to {}",
+        symbol.symbol, stored, expected, start, end
+    );
+
+    // Open times [first, last] of the missing klines
+    let mut missing: Vec<(i64, i64)> = Vec::new();
+    match get_open_time_bounds(pool, &series, range).await? {
+        _ if expected == 0 => {}
+        (Some(first), Some(last)) if !force_fetch => {
+            if first > first_open_time.timestamp_millis() {
+                missing.push((range.0, first - 1));
+            }
+            let after_last = interval
+                .get_next_open_time(DateTime::<Utc>::from_timestamp_millis(last).unwrap())
+                .timestamp_millis();
+            if after_last < range.1 {
+                missing.push((after_last, range.1 - 1));
+            }
+            // Months have different lengths, their klines are not evenly spaced
+            if !matches!(interval, CryptoInterval::Int1M) {
+                let interval_millis = interval.to_minutes() * 60_000;
+                for (previous_open_time, open_time) in
+                    get_gaps(pool, &series, interval_millis, range).await?
+                {
+                    missing.push((previous_open_time + interval_millis, open_time - 1));
+                }
+            }
+        }
+        _ => missing.push((range.0, range.1 - 1)),
+    }
+
+    for (first, last) in missing {
+        let klines = fetch_range(
+            provider,
+            &symbol.symbol,
+            interval,
+            (first as u64, last as u64),
+            KLINES_LIMIT,
+        )
+        .await
+        .map_err(sqlx::Error::Protocol)?;
+        for kline in &klines {
+            if insert_kline(pool, &series, kline).await.is_ok() {
+                progress.stored += 1;
+            }
+        }
+        if let Some(reporter) = reporter {
+            reporter.report(&progress);
+        }
+    }
+
+    progress.done = true;
+    if let Some(reporter) = reporter {
+        reporter.report(&progress);
+    }
+
+    Ok(())
+}
+
+// Open time of the first kline opened from the given time
+fn get_first_open_time(interval: &CryptoInterval, time: DateTime<Utc>) -> DateTime<Utc> {
+    let open_time = interval.get_open_time(time);
+    match open_time < time {
+        true => interval.get_next_open_time(open_time),
+        false => open_time,
+    }
+}
+
+// Number of klines of the interval opened in [start, end)
+fn count_open_times(
+    interval: &CryptoInterval,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+) -> i64 {
+    let mut open_time = get_first_open_time(interval, start);
+    let mut count = 0;
+    while open_time < end {
+        count += 1;
+        open_time = interval.get_next_open_time(open_time);
+    }
+    count
+}
+
+// First and last open times of the stored klines opened in [start, end)
+async fn get_open_time_bounds(
+    pool: &PgPool,
+    series: &KlineSeries,
+    (start, end): (i64, i64),
+) -> Result<(Option<i64>, Option<i64>), sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT MIN(open_time), MAX(open_time) FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3 AND open_time >= $4 AND open_time < $5
+        "#,
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await
+}
+
+async fn loop_fetch_klines(
+    pool: &PgPool,
+    provider: &MarketDataProvider,
+    series: &KlineSeries,
+    progress: &mut KlinesProgress,
+    reporter: Option<&KlinesProgressReporter>,
     fetch_type: &KlinesFetchType,
     force_fetch: bool,
-) {
-    let time_string: &str;
+) -> Result<(), String> {
     let mut time_param: u64;
     if *fetch_type == KlinesFetchType::Recent {
-        time_string = "startTime";
-        time_param = utils::get_max_open_time(pool, table_name).await;
+        time_param = utils::get_max_open_time(pool, series).await;
     } else {
-        time_string = "endTime";
-        time_param = match *table_length {
+        time_param = match progress.stored {
             0 => {
                 chrono::Utc::now().timestamp_millis() as u64
                     + chrono::Duration::days(100).num_milliseconds() as u64
             }
-            _ => utils::get_min_open_time(pool, table_name).await,
+            _ => utils::get_min_open_time(pool, series).await,
         };
     }
 
     loop {
         // If the limit is reached, stop fetching data
-        if let Some(limit) = limit {
-            loading::print_loading_progress(*table_length as i32, *limit as i32);
-            if !force_fetch && *table_length >= *limit {
+        if let Some(target) = progress.target {
+            if !force_fetch && progress.stored >= target {
                 println!("Limit reached");
                 break;
             }
         }
 
         // Parameters for the request
-        let params = [
-            ("symbol", symbol),
-            ("interval", &interval.to_binance_string()),
-            ("limit", KLINES_LIMIT),
-            (time_string, &time_param.to_string()),
-        ];
+        let request = KlinesRequest {
+            symbol: progress.symbol.clone(),
+            interval: progress.interval.clone(),
+            limit: KLINES_LIMIT,
+            start_time: match fetch_type {
+                KlinesFetchType::Recent => Some(time_param),
+                KlinesFetchType::Older => None,
+            },
+            end_time: match fetch_type {
+                KlinesFetchType::Recent => None,
+                KlinesFetchType::Older => Some(time_param),
+            },
+        };
 
         // Fetch klines
-        let klines = fetch_klines(client, &params).await;
+        let klines = provider.fetch_klines(&request).await?;
         if klines.len() <= 1 {
             println!("No more recent data to fetch");
             break;
@@ -152,151 +329,218 @@ async fn loop_fetch_klines(
             KlinesFetchType::Recent => klines.last(),
             KlinesFetchType::Older => klines.first(),
         }
-        .unwrap()[0]
-            .as_u64()
-            .expect("Failed to get open time");
+        .unwrap()
+        .open_time
+        .timestamp_millis() as u64;
 
         // Insert the klines
         for kline in klines {
-            let result = insert_kline(pool, table_name, &kline).await;
+            let result = insert_kline(pool, series, &kline).await;
             if result.is_ok() {
-                *table_length += 1;
+                progress.stored += 1;
             }
         }
+        if let Some(reporter) = reporter {
+            reporter.report(progress);
+        }
     }
-}
-
-async fn fetch_klines(client: &Client, params: &[(&str, &str)]) -> Vec<serde_json::Value> {
-    let end_time_human = chrono::Utc
-        .timestamp_millis_opt(params[3].1.parse().unwrap())
-        .single();
-    print!(
-        "Fetching klines... Time : {:?}",
-        end_time_human.unwrap().to_rfc2822()
-    );
 
-    let response = client
-        .get(&format!("{}/fapi/v1/klines", BINANCE_FUTURES_API_URL))
-        .query(params)
-        .send()
-        .await
-        .expect("Failed to get klines")
-        .text()
-        .await
-        .expect("Failed to convert response to string");
+    Ok(())
+}
 
-    let data: serde_json::Value = serde_json::from_str(&response).expect("Failed to parse klines");
-    sleep(Duration::from_millis(REQUESTS_DELAY_MILLIS)).await; // Add a delay between requests
-    data.as_array().expect("Failed to get klines").clone()
+// Klines with an open time in [start_time, end_time], fetched page by page
+pub async fn fetch_range(
+    provider: &MarketDataProvider,
+    symbol: &str,
+    interval: &CryptoInterval,
+    (start_time, end_time): (u64, u64),
+    page_size: usize,
+) -> Result<Vec<Kline>, String> {
+    let mut klines: Vec<Kline> = Vec::new();
+    let mut cursor = start_time;
+    while cursor <= end_time {
+        let page = provider
+            .fetch_klines(&KlinesRequest {
+                symbol: symbol.to_string(),
+                interval: interval.clone(),
+                limit: page_size,
+                start_time: Some(cursor),
+                end_time: Some(end_time),
+            })
+            .await?;
+        let last_open_time = match page.last() {
+            Some(kline) => kline.open_time.timestamp_millis() as u64,
+            None => break,
+        };
+        let complete = page.len() >= page_size;
+        klines.extend(page);
+        if !complete {
+            break;
+        }
+        cursor = last_open_time + 1;
+    }
+    Ok(klines)
 }
 
 async fn insert_kline(
     pool: &PgPool,
-    table_name: &str,
-    kline: &serde_json::Value,
+    series: &KlineSeries,
+    kline: &Kline,
 ) -> Result<(), sqlx::Error> {
-    let kline = kline.as_array().expect("Failed to get kline");
-    let open_time = kline[0].as_u64().expect("Failed to get open time");
-    let open = utils::json_value_to_f64(&kline[1]);
-    let high = utils::json_value_to_f64(&kline[2]);
-    let low = utils::json_value_to_f64(&kline[3]);
-    let close = utils::json_value_to_f64(&kline[4]);
-    let volume = utils::json_value_to_f64(&kline[5]);
-    let close_time = kline[6].as_u64().expect("Failed to get close time");
     // If close time is after now, skip the kline
-    if close_time > chrono::Utc::now().timestamp_millis() as u64 {
+    if kline.close_time > chrono::Utc::now() {
         return Err(sqlx::Error::ColumnNotFound(
             "Close time is after now".to_string(),
         ));
     }
-    let quote_asset_volume = utils::json_value_to_f64(&kline[7]);
-    let number_of_trades = kline[8].as_u64().expect("Failed to get number of trades");
-    let taker_buy_base_asset_volume = utils::json_value_to_f64(&kline[9]);
-    let taker_buy_quote_asset_volume = utils::json_value_to_f64(&kline[10]);
 
-    let result = sqlx::query(&format!(
-        r#"
-        INSERT INTO {} (open_time, open, high, low, close, volume, close_time, quote_asset_volume, number_of_trades, taker_buy_base_asset_volume, taker_buy_quote_asset_volume)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
-        "#,
-        table_name
-    ))
-    .bind(open_time as i64)
-    .bind(open)
-    .bind(high)
-    .bind(low)
-    .bind(close)
-    .bind(volume)
-    .bind(close_time as i64)
-    .bind(quote_asset_volume)
-    .bind(number_of_trades as i64)
-    .bind(taker_buy_base_asset_volume)
-    .bind(taker_buy_quote_asset_volume)
-    .execute(pool)
-    .await;
-
-    if let Err(e) = result {
-        // println!("Failed to insert kline: {}", e);
-        return Err(e);
+    utils::insert_kline(pool, series, kline).await
+}
+
+// Fetch the klines missing between two consecutive stored klines
+// (the gaps that remain are periods without trading, such as exchange maintenances)
+async fn backfill_gaps(
+    pool: &PgPool,
+    provider: &MarketDataProvider,
+    series: &KlineSeries,
+) -> Result<(), sqlx::Error> {
+    let interval = &series.interval;
+    // Months have different lengths, their klines are not evenly spaced
+    if matches!(interval, CryptoInterval::Int1M) {
+        return Ok(());
     }
+    let interval_millis = interval.to_minutes() * 60_000;
 
-    Ok(())
-}
+    let gaps = get_gaps(pool, series, interval_millis, (i64::MIN, i64::MAX)).await?;
+    print!("Klines gaps: {} / ", gaps.len());
+    if gaps.is_empty() {
+        println!("=> Klines integrity is OK");
+        return Ok(());
+    }
+    println!("=> Backfilling the missing klines");
 
-async fn check_klines_integrity(pool: &PgPool, table_name: &str) {
-    // Check the number of klines in the table
-    let result: i64 = sqlx::query_scalar(&format!(
-        r#"
-        SELECT COUNT(*) FROM {}
-        "#,
-        table_name
-    ))
-    .fetch_one(pool)
-    .await
-    .expect("Failed to get klines count");
+    for (previous_open_time, open_time) in gaps {
+        let missing = fetch_range(
+            provider,
+            &series.symbol,
+            interval,
+            (
+                (previous_open_time + interval_millis) as u64,
+                (open_time - 1) as u64,
+            ),
+            KLINES_LIMIT,
+        )
+        .await
+        .map_err(sqlx::Error::Protocol)?;
+
+        let mut inserted = 0;
+        for kline in missing {
+            if insert_kline(pool, series, &kline).await.is_ok() {
+                inserted += 1;
+            }
+        }
+        println!(
+            "Gap {} -> {}: {} kline(s) inserted",
+            previous_open_time, open_time, inserted
+        );
+    }
+
+    let remaining = get_gaps(pool, series, interval_millis, (i64::MIN, i64::MAX)).await?;
+    if remaining.is_empty() {
+        println!("=> Klines integrity is OK");
+    } else {
+        println!(
+            "=> {} gap(s) without klines on the exchange: {:?}",
+            remaining.len(),
+            remaining
+        );
+    }
 
-    print!("Klines count: {} / ", result);
+    Ok(())
+}
 
-    // Check if each difference between the open time of two consecutive klines is equal to the interval
-    // Get interval between two consecutive klines (first kline open time - second kline open time)
-    // We will then count the number of differences that are not equal to this interval
-    // Get the interval
-    let intervals: Vec<Option<i64>> = sqlx::query_scalar(&format!(
+// Consecutive open times (previous, next) further apart than the interval, opened in [start, end)
+async fn get_gaps(
+    pool: &PgPool,
+    series: &KlineSeries,
+    interval_millis: i64,
+    (start, end): (i64, i64),
+) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+    sqlx::query_as(
         r#"
-        SELECT open_time - LAG(open_time, 1) OVER (ORDER BY open_time) AS diff
-        FROM {}
-        LIMIT 2
+        SELECT previous_open_time, open_time FROM (
+            SELECT open_time, LAG(open_time, 1) OVER (ORDER BY open_time) AS previous_open_time
+            FROM kline
+            WHERE market = $1 AND symbol = $2 AND interval = $3 AND open_time >= $4 AND open_time < $5
+        ) AS diffs
+        WHERE open_time - previous_open_time > $6
+        ORDER BY open_time
         "#,
-        table_name
-    ))
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(start)
+    .bind(end)
+    .bind(interval_millis)
     .fetch_all(pool)
     .await
-    .expect("Failed to get interval");
+}
 
-    let interval: i64 = intervals.last().unwrap().expect("Failed to get interval");
+//// Tests
 
-    print!("Interval: {} / ", interval);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market_data::FileProvider;
+    use std::fs;
 
-    //
-    let result: i64 = sqlx::query_scalar(&format!(
-        r#"
-        SELECT COUNT(*) FROM (
-            SELECT open_time - LAG(open_time, 1) OVER (ORDER BY open_time) AS diff
-            FROM {}
-        ) AS diffs
-        WHERE diff != {}
-        "#,
-        table_name, interval
-    ))
-    .fetch_one(pool)
-    .await
-    .expect("Failed to get differences count");
+    #[test]
+    fn test_count_open_times() {
+        let time = |day: u32, hour: u32| {
+            chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, day, hour, 0, 0).unwrap()
+        };
+        assert_eq!(
+            count_open_times(&CryptoInterval::Int1h, (time(1, 0), time(2, 0))),
+            24
+        );
+        // The first kline opens at 4:00
+        assert_eq!(
+            count_open_times(&CryptoInterval::Int4h, (time(1, 1), time(1, 12))),
+            2
+        );
+        assert_eq!(
+            count_open_times(&CryptoInterval::Int1d, (time(2, 0), time(1, 0))),
+            0
+        );
+    }
 
-    print!("Differences count: {} / ", result);
+    #[tokio::test]
+    async fn test_fetch_range() {
+        let directory = std::env::temp_dir().join(format!("fetch_range_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let lines: String = (0..10)
+            .map(|i| format!("{},1,2,0.5,1.5,10\n", 1704067200000i64 + i * 3_600_000))
+            .collect();
+        fs::write(directory.join("BTCUSDT-1h.csv"), lines).unwrap();
+        let provider = MarketDataProvider::File(FileProvider::new(directory.to_str().unwrap()));
 
-    if result == 0 {
-        println!("=> Klines integrity is OK");
-    } else {
-        panic!("=> Klines integrity is NOT OK");
+        // Klines 3 to 7, two per page
+        let klines = fetch_range(
+            &provider,
+            "BTCUSDT",
+            &CryptoInterval::Int1h,
+            (1704067200000 + 3 * 3_600_000, 1704067200000 + 7 * 3_600_000),
+            2,
+        )
+        .await
+        .unwrap();
+        assert_eq!(klines.len(), 5);
+        assert_eq!(
+            klines[4].open_time.timestamp_millis(),
+            1704067200000 + 7 * 3_600_000
+        );
+
+        fs::remove_dir_all(&directory).unwrap();
     }
 }
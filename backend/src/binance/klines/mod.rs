@@ -1,3 +1,5 @@
-mod acquire;
+pub mod acquire;
+pub mod progress;
+pub mod resample;
 pub mod retrieve;
 pub mod utils;
@@ -0,0 +1,63 @@
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::objects::{intervals::CryptoInterval, markets::MarketType, objects::CryptoSymbol};
+
+/// Progress of the klines acquisition of a symbol, reported after each page of klines
+#[derive(Clone, Debug, Serialize)]
+pub struct KlinesProgress {
+    pub symbol: String,
+    pub market: MarketType,
+    pub interval: CryptoInterval,
+    pub stored: i64,         // klines stored
+    pub target: Option<i64>, // klines wanted (None: all the available ones)
+    pub done: bool,
+}
+
+impl KlinesProgress {
+    pub fn new(
+        symbol: &CryptoSymbol,
+        interval: &CryptoInterval,
+        stored: i64,
+        target: Option<i64>,
+    ) -> Self {
+        KlinesProgress {
+            symbol: symbol.symbol.clone(),
+            market: symbol.market.clone(),
+            interval: interval.clone(),
+            stored,
+            target,
+            done: false,
+        }
+    }
+
+    // Share of the target already stored (between 0 and 1)
+    pub fn ratio(&self) -> Option<f64> {
+        self.target
+            .filter(|&target| target > 0)
+            .map(|target| (self.stored as f64 / target as f64).min(1.0))
+    }
+}
+
+/// Receives the progress of the klines acquisitions (called from several symbols concurrently)
+#[derive(Clone)]
+pub struct KlinesProgressReporter {
+    callback: Arc<dyn Fn(&KlinesProgress) + Send + Sync>,
+}
+
+impl KlinesProgressReporter {
+    pub fn new(callback: Arc<dyn Fn(&KlinesProgress) + Send + Sync>) -> Self {
+        KlinesProgressReporter { callback }
+    }
+
+    pub fn report(&self, progress: &KlinesProgress) {
+        (self.callback)(progress);
+    }
+}
+
+impl fmt::Debug for KlinesProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KlinesProgressReporter").finish()
+    }
+}
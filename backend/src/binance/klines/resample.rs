@@ -0,0 +1,223 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::utils::{self, KlineSeries};
+use crate::objects::{intervals::CryptoInterval, klines::Kline, objects::CryptoSymbol};
+
+// Whether the klines of the interval `to` can be built exactly from the klines of the interval `from`
+pub fn can_resample(from: &CryptoInterval, to: &CryptoInterval) -> bool {
+    let (from_minutes, to_minutes) = (from.to_minutes(), to.to_minutes());
+    match (from, to) {
+        (CryptoInterval::Int1w | CryptoInterval::Int1M, _) => false,
+        // Weeks and months start at midnight
+        (_, CryptoInterval::Int1w | CryptoInterval::Int1M) => 1440 % from_minutes == 0,
+        _ => from_minutes < to_minutes && to_minutes % from_minutes == 0,
+    }
+}
+
+// Kline being built from the finer ones
+struct Bucket {
+    kline: Kline,
+    first_open_time: DateTime<Utc>, // open time of the first finer kline
+    last_open_time: DateTime<Utc>,  // open time of the last finer kline
+}
+
+/// Klines of the interval `to` built from klines of the finer interval `from` (sorted by open time)
+/// The first and last klines are dropped when the finer klines do not cover them entirely
+/// (the other ones may miss finer klines, like the klines of the exchange during a maintenance)
+pub fn resample_klines(
+    klines: &[Kline],
+    from: &CryptoInterval,
+    to: &CryptoInterval,
+) -> Result<Vec<Kline>, String> {
+    if !can_resample(from, to) {
+        return Err(format!(
+            "{} klines cannot be built from {} klines",
+            to.to_string(),
+            from.to_string()
+        ));
+    }
+
+    let mut buckets: Vec<Bucket> = Vec::new();
+    for kline in klines {
+        let open_time = to.get_open_time(kline.open_time);
+        match buckets.last_mut() {
+            Some(bucket) if bucket.kline.open_time == open_time => {
+                let resampled = &mut bucket.kline;
+                resampled.high = resampled.high.max(kline.high);
+                resampled.low = resampled.low.min(kline.low);
+                resampled.close = kline.close;
+                resampled.volume += kline.volume;
+                resampled.quote_asset_volume += kline.quote_asset_volume;
+                resampled.number_of_trades += kline.number_of_trades;
+                resampled.taker_buy_base_asset_volume += kline.taker_buy_base_asset_volume;
+                resampled.taker_buy_quote_asset_volume += kline.taker_buy_quote_asset_volume;
+                bucket.last_open_time = kline.open_time;
+            }
+            _ => buckets.push(Bucket {
+                kline: Kline {
+                    open_time,
+                    close_time: to.get_next_open_time(open_time)
+                        - chrono::Duration::milliseconds(1),
+                    ..kline.clone()
+                },
+                first_open_time: kline.open_time,
+                last_open_time: kline.open_time,
+            }),
+        }
+    }
+
+    // Incomplete first and last klines
+    if buckets
+        .first()
+        .is_some_and(|bucket| bucket.first_open_time != bucket.kline.open_time)
+    {
+        buckets.remove(0);
+    }
+    if buckets.last().is_some_and(|bucket| {
+        from.get_next_open_time(bucket.last_open_time)
+            != to.get_next_open_time(bucket.kline.open_time)
+    }) {
+        buckets.pop();
+    }
+
+    Ok(buckets.into_iter().map(|bucket| bucket.kline).collect())
+}
+
+// Build the klines of the interval `to` from the stored klines of the interval `from` opened
+// from the given time (default: from the last stored kline of `to`, the older ones are already built)
+pub async fn resample_table(
+    pool: &PgPool,
+    symbol: &CryptoSymbol,
+    from: &CryptoInterval,
+    to: &CryptoInterval,
+    start: Option<i64>,
+) -> Result<usize, sqlx::Error> {
+    let source = KlineSeries::new(&symbol.market, &symbol.symbol, from);
+    let target = KlineSeries::new(&symbol.market, &symbol.symbol, to);
+    if utils::get_length(pool, &source).await == 0 {
+        return Err(sqlx::Error::Protocol(format!(
+            "No {} klines to build the {} klines of {}",
+            from.to_string(),
+            to.to_string(),
+            symbol.symbol
+        )));
+    }
+
+    let start = match start {
+        Some(start) => start,
+        None => utils::get_max_open_time(pool, &target).await as i64,
+    };
+
+    let klines = utils::get_klines(pool, &source, (start, i64::MAX)).await?;
+    let resampled = resample_klines(&klines, from, to).map_err(sqlx::Error::Protocol)?;
+    for kline in &resampled {
+        utils::upsert_kline(pool, &target, kline).await?;
+    }
+    println!(
+        "{}: {} {} klines built from {} {} klines",
+        symbol.symbol,
+        resampled.len(),
+        to.to_string(),
+        klines.len(),
+        from.to_string()
+    );
+
+    Ok(resampled.len())
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn minute_kline(open_time: DateTime<Utc>, price: f64) -> Kline {
+        Kline {
+            open_time,
+            open: price,
+            high: price + 2.0,
+            low: price - 1.0,
+            close: price + 1.0,
+            volume: 10.0,
+            close_time: open_time + chrono::Duration::milliseconds(59_999),
+            quote_asset_volume: 100.0,
+            number_of_trades: 3,
+            taker_buy_base_asset_volume: 4.0,
+            taker_buy_quote_asset_volume: 40.0,
+        }
+    }
+
+    #[test]
+    fn test_resample_klines() {
+        // 12:03 to 12:16 : only 12:05 - 12:10 and 12:10 - 12:15 are complete
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 12, 3, 0).unwrap();
+        let klines: Vec<Kline> = (0..14)
+            .map(|i| minute_kline(start + chrono::Duration::minutes(i), i as f64))
+            .collect();
+
+        let resampled =
+            resample_klines(&klines, &CryptoInterval::Int1m, &CryptoInterval::Int5m).unwrap();
+        assert_eq!(resampled.len(), 2);
+        let kline = &resampled[0];
+        assert_eq!(
+            kline.open_time,
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 5, 0).unwrap()
+        );
+        assert_eq!(
+            kline.close_time,
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 9, 59).unwrap()
+                + chrono::Duration::milliseconds(999)
+        );
+        assert_eq!(kline.open, 2.0);
+        assert_eq!(kline.close, 7.0);
+        assert_eq!(kline.high, 8.0);
+        assert_eq!(kline.low, 1.0);
+        assert_eq!(kline.volume, 50.0);
+        assert_eq!(kline.quote_asset_volume, 500.0);
+        assert_eq!(kline.number_of_trades, 15);
+        assert_eq!(kline.taker_buy_base_asset_volume, 20.0);
+        assert_eq!(kline.taker_buy_quote_asset_volume, 200.0);
+
+        assert!(resample_klines(&klines, &CryptoInterval::Int5m, &CryptoInterval::Int1m).is_err());
+        assert!(!can_resample(
+            &CryptoInterval::Int3d,
+            &CryptoInterval::Int1w
+        ));
+        assert!(can_resample(&CryptoInterval::Int1d, &CryptoInterval::Int1M));
+    }
+
+    #[test]
+    fn test_open_times() {
+        let time = Utc.with_ymd_and_hms(2024, 2, 15, 13, 47, 12).unwrap();
+        // 2024-02-15 is a Thursday
+        assert_eq!(
+            CryptoInterval::Int1w.get_open_time(time),
+            Utc.with_ymd_and_hms(2024, 2, 12, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            CryptoInterval::Int1M.get_open_time(time),
+            Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            CryptoInterval::Int1M
+                .get_next_open_time(Utc.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()),
+            Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            CryptoInterval::Int4h.get_open_time(time),
+            Utc.with_ymd_and_hms(2024, 2, 15, 12, 0, 0).unwrap()
+        );
+
+        // A daily kline built from the hourly ones
+        let start = Utc.with_ymd_and_hms(2024, 2, 15, 0, 0, 0).unwrap();
+        let klines: Vec<Kline> = (0..24)
+            .map(|i| minute_kline(start + chrono::Duration::hours(i), i as f64))
+            .collect();
+        let resampled =
+            resample_klines(&klines, &CryptoInterval::Int1h, &CryptoInterval::Int1d).unwrap();
+        assert_eq!(resampled.len(), 1);
+        assert_eq!(resampled[0].close, 24.0);
+    }
+}
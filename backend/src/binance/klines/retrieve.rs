@@ -1,9 +1,10 @@
 use chrono::DateTime;
 use chrono::Utc;
-use sqlx::Row;
+use sqlx::PgPool;
 
 use super::acquire;
-use super::utils;
+use super::utils::{self, KlineSeries};
+use crate::market_data::MarketDataProvider;
 use crate::objects::{
     intervals::CryptoInterval,
     klines::{Kline, KlineCollection},
@@ -12,6 +13,7 @@ use crate::objects::{
 
 // Function to retrieve klines from database with the given parameters
 pub async fn retrieve_klines_simple(
+    pool: &PgPool,
     klines_collection: &mut KlineCollection,
     symbol: &CryptoSymbol,
     interval: &CryptoInterval,
@@ -20,6 +22,7 @@ pub async fn retrieve_klines_simple(
     force_fetch: bool,
 ) -> Result<(), sqlx::Error> {
     retrieve_klines(
+        pool,
         klines_collection,
         symbol,
         interval,
@@ -27,59 +30,41 @@ pub async fn retrieve_klines_simple(
         training_percentage,
         force_fetch,
         None,
-        None,
     )
     .await
 }
 
 // Function to retrieve klines from database
 pub async fn retrieve_klines(
+    pool: &PgPool,
     klines_collection: &mut KlineCollection,
     symbol: &CryptoSymbol,
     interval: &CryptoInterval,
     limit_minutes: i64,
     training_percentage: f64,
     force_fetch: bool,
-    only_before: Option<DateTime<Utc>>,
-    additional_klines: Option<i32>, // will be added to the 'past' klines vector
+    additional_klines: Option<i32>, // will be added to the 'past' klines vector, before the first one
 ) -> Result<(), sqlx::Error> {
     // Retrieve the klines from the database
-    let table_name = utils::get_table_name(&symbol.symbol, interval);
+    let series = KlineSeries::new(&symbol.market, &symbol.symbol, interval);
     let mut limit = limit_minutes / interval.to_minutes();
-    let mut only_before: Option<DateTime<Utc>> = only_before;
-
-    // Check if the table exists
-    let pool = crate::utils::db::get_new_pool().await;
-    let table_exists = utils::check_table_exists(&pool, &table_name).await;
-    let mut table_length = match table_exists {
-        true => utils::get_table_length(&pool, &table_name).await,
-        false => 0,
-    };
-    // let mut table_empty = true;
-    // if table_exists {
-    //     table_empty = utils::check_table_empty(pool, &table_name).await;
-    // }
+    let mut only_before: Option<DateTime<Utc>> = None;
+
+    // Number of stored klines
+    let length = utils::get_length(pool, &series).await;
 
     let skip_fetch = match additional_klines {
         Some(additional_klines) => {
             // Query the number of klines before the last open time
-            let result = sqlx::query(&format!(
-                r#"
-                SELECT COUNT(*)
-                FROM {}
-                WHERE open_time < {}
-                "#,
-                utils::get_table_name(
-                    &klines_collection.symbol.symbol,
-                    &klines_collection.interval
+            let klines_before_last_open_time = utils::get_range_length(
+                pool,
+                &KlineSeries::from_collection(klines_collection),
+                (
+                    i64::MIN,
+                    klines_collection.get_last_open_time().timestamp_millis(),
                 ),
-                klines_collection.get_last_open_time().timestamp_millis()
-            ))
-            .fetch_one(&pool)
-            .await
-            .unwrap();
-
-            let klines_before_last_open_time = result.get::<i64, _>(0);
+            )
+            .await?;
 
             println!(
                 "Klines before the last open time: {}",
@@ -92,22 +77,23 @@ pub async fn retrieve_klines(
         None => false,
     };
 
-    // If the table does not exist or is empty, fetch the klines from the Binance API
+    // If there are not enough klines stored, fetch the klines from the Binance API
     if skip_fetch {
         println!("Skipping fetch");
-    } else if !table_exists || table_length == 0 || table_length < limit || force_fetch {
+    } else if length == 0 || length < limit || force_fetch {
         // Acquire the klines from the Binance API
+        let provider =
+            MarketDataProvider::from_env(&symbol.market).map_err(sqlx::Error::Protocol)?;
         acquire::acquire_klines(
-            &pool,
-            &symbol.symbol,
+            pool,
+            &provider,
+            symbol,
             &interval,
             &Some(limit),
-            table_exists,
-            &mut table_length,
             force_fetch,
+            None,
         )
-        .await
-        .unwrap();
+        .await?;
     }
 
     if additional_klines != None {
@@ -120,29 +106,21 @@ pub async fn retrieve_klines(
         }
     }
 
-    let before_cond = match only_before {
-        Some(only_before) => format!("\n\rWHERE open_time < {}", only_before.timestamp_millis()),
-        None => "\n\r".to_string(),
-    };
-
     // Query the database
-    let result = sqlx::query(&format!(
-        r#"
-        SELECT open_time, open, high, low, close, volume, close_time, quote_asset_volume, number_of_trades, taker_buy_base_asset_volume, taker_buy_quote_asset_volume
-        FROM {}{}
-        ORDER BY open_time DESC
-        LIMIT {}
-        "#,
-        table_name, before_cond, limit
-    )).fetch_all(&pool).await.unwrap();
-
-    println!("Retrieved {} klines from the database", result.len());
-
-    // Parse the results and add them to the klines collection
-    let training_length = (result.len() as f64 * training_percentage).round() as usize;
-    let mut i = 0;
-    let mut past_klines = Vec::<Kline>::with_capacity(result.len());
-    for row in result.iter().rev() {
+    let klines = utils::get_last_klines(
+        pool,
+        &series,
+        only_before.map(|only_before| only_before.timestamp_millis()),
+        limit,
+    )
+    .await?;
+
+    println!("Retrieved {} klines from the database", klines.len());
+
+    // Add the klines to the klines collection
+    let training_length = (klines.len() as f64 * training_percentage).round() as usize;
+    let mut past_klines = Vec::<Kline>::with_capacity(klines.len());
+    for (i, kline) in klines.into_iter().enumerate() {
         if additional_klines != None {
             &mut past_klines
         } else if i < training_length {
@@ -150,22 +128,7 @@ pub async fn retrieve_klines(
         } else {
             &mut klines_collection.validation
         }
-        .push(Kline {
-            open_time: DateTime::<Utc>::from_timestamp_millis(row.get::<i64, _>("open_time"))
-                .unwrap(),
-            open: row.get("open"),
-            high: row.get("high"),
-            low: row.get("low"),
-            close: row.get("close"),
-            volume: row.get("volume"),
-            close_time: DateTime::<Utc>::from_timestamp_millis(row.get::<i64, _>("close_time"))
-                .unwrap(),
-            quote_asset_volume: row.get("quote_asset_volume"),
-            number_of_trades: row.get("number_of_trades"),
-            taker_buy_base_asset_volume: row.get("taker_buy_base_asset_volume"),
-            taker_buy_quote_asset_volume: row.get("taker_buy_quote_asset_volume"),
-        });
-        i += 1;
+        .push(kline);
     }
 
     // If additional klines are requested
@@ -180,7 +143,56 @@ pub async fn retrieve_klines(
     Ok(())
 }
 
+// Function to retrieve the klines opened in [start, end) from database
+// (the missing ones are downloaded first)
+pub async fn retrieve_klines_range(
+    pool: &PgPool,
+    klines_collection: &mut KlineCollection,
+    symbol: &CryptoSymbol,
+    interval: &CryptoInterval,
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    training_percentage: f64,
+    force_fetch: bool,
+) -> Result<(), sqlx::Error> {
+    if start >= end {
+        return Err(sqlx::Error::Protocol(format!(
+            "Invalid date range: {} - {}",
+            start, end
+        )));
+    }
+
+    let provider = MarketDataProvider::from_env(&symbol.market).map_err(sqlx::Error::Protocol)?;
+    acquire::acquire_range(
+        pool,
+        &provider,
+        symbol,
+        interval,
+        (start, end),
+        force_fetch,
+        None,
+    )
+    .await?;
+
+    let mut klines = utils::get_klines(
+        pool,
+        &KlineSeries::new(&symbol.market, &symbol.symbol, interval),
+        (start.timestamp_millis(), end.timestamp_millis()),
+    )
+    .await?;
+    println!("Retrieved {} klines from the database", klines.len());
+
+    let training_length = (klines.len() as f64 * training_percentage).round() as usize;
+    klines_collection.validation = klines.split_off(training_length);
+    klines_collection.training = klines;
+    klines_collection.past.clear();
+    klines_collection.symbol = symbol.clone();
+    klines_collection.interval = interval.clone();
+
+    Ok(())
+}
+
 pub async fn retrieve_klines_extend(
+    pool: &PgPool,
     klines_collection: &mut KlineCollection,
     additional_klines: i32,
 ) -> Result<(), sqlx::Error> {
@@ -201,15 +213,37 @@ pub async fn retrieve_klines_extend(
 
     println!("New limit minutes: {}", new_limit_minutes);
 
+    // The klines before the first one, downloaded if missing
+    // (the collection may be far from now, when built for a date range)
+    let first_open_time = klines_collection.get_first_open_time();
+    let provider = MarketDataProvider::from_env(&klines_collection.symbol.market)
+        .map_err(sqlx::Error::Protocol)?;
+    acquire::acquire_range(
+        pool,
+        &provider,
+        &klines_collection.symbol,
+        &klines_collection.interval,
+        (
+            first_open_time
+                - chrono::Duration::minutes(
+                    klines_collection.interval.to_minutes() * additional_klines as i64,
+                ),
+            first_open_time,
+        ),
+        false,
+        None,
+    )
+    .await?;
+
     // Retrieve the klines from the database
     retrieve_klines(
+        pool,
         klines_collection,
         &klines_collection.symbol.clone(),
         &klines_collection.interval.clone(),
         new_limit_minutes,
         klines_collection.training_percentage,
         false,
-        Some(klines_collection.get_last_open_time()),
         Some(additional_klines),
     )
     .await
@@ -1,169 +1,247 @@
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgRow, PgPool, Row};
 
-use crate::objects::{intervals::CryptoInterval, klines::KlineCollection};
+use crate::objects::{
+    intervals::CryptoInterval,
+    klines::{Kline, KlineCollection},
+    markets::MarketType,
+};
 
-pub fn get_table_name(symbol: &str, interval: &CryptoInterval) -> String {
-    format!("klines_{}_{}", symbol, interval.to_string()).to_lowercase()
-}
+const KLINE_COLUMNS: &str = "open_time, open, high, low, close, volume, close_time, quote_asset_volume, number_of_trades, taker_buy_base_asset_volume, taker_buy_quote_asset_volume";
 
-pub fn get_table_name_collection(klines_collection: &KlineCollection) -> String {
-    get_table_name(
-        &klines_collection.symbol.symbol,
-        &klines_collection.interval,
-    )
+/// Klines of a symbol at an interval, in the kline table (its key without the open time)
+/// Each market has its own klines, a symbol can be traded on several markets
+#[derive(Debug, Clone)]
+pub struct KlineSeries {
+    pub market: MarketType,
+    pub symbol: String,
+    pub interval: CryptoInterval,
 }
 
-pub async fn create_klines_table(pool: &PgPool, table_name: &str) -> Result<(), sqlx::Error> {
-    let result = sqlx::query(&format!(
-        r#"
-        CREATE TABLE {} (
-            open_time BIGINT PRIMARY KEY,
-            open FLOAT NOT NULL,
-            high FLOAT NOT NULL,
-            low FLOAT NOT NULL,
-            close FLOAT NOT NULL,
-            volume FLOAT NOT NULL,
-            close_time BIGINT NOT NULL,
-            quote_asset_volume FLOAT NOT NULL,
-            number_of_trades BIGINT NOT NULL,
-            taker_buy_base_asset_volume FLOAT NOT NULL,
-            taker_buy_quote_asset_volume FLOAT NOT NULL
-        )
-        "#,
-        table_name
-    ))
-    .execute(pool)
-    .await;
+impl KlineSeries {
+    pub fn new(market: &MarketType, symbol: &str, interval: &CryptoInterval) -> Self {
+        KlineSeries {
+            market: market.clone(),
+            symbol: symbol.to_string(),
+            interval: interval.clone(),
+        }
+    }
 
-    result.map(|_| ())
+    pub fn from_collection(klines_collection: &KlineCollection) -> Self {
+        KlineSeries::new(
+            &klines_collection.symbol.market,
+            &klines_collection.symbol.symbol,
+            &klines_collection.interval,
+        )
+    }
 }
 
-pub async fn check_table_exists(pool: &PgPool, table_name: &str) -> bool {
-    sqlx::query_scalar(&format!(
+pub async fn get_length(pool: &PgPool, series: &KlineSeries) -> i64 {
+    sqlx::query_scalar(
         r#"
-        SELECT EXISTS (
-            SELECT FROM information_schema.tables 
-            WHERE table_name = '{}'
-        )
+        SELECT COUNT(*) FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3
         "#,
-        table_name
-    ))
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
     .fetch_one(pool)
     .await
-    .expect("Failed to check if table exists")
+    .expect("Failed to get klines length")
 }
 
-pub async fn get_table_length(pool: &PgPool, table_name: &str) -> i64 {
-    sqlx::query_scalar(&format!(
+// Number of klines opened in [start, end) (open times in milliseconds)
+pub async fn get_range_length(
+    pool: &PgPool,
+    series: &KlineSeries,
+    (start, end): (i64, i64),
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
         r#"
-        SELECT COUNT(*) FROM {}
+        SELECT COUNT(*) FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3 AND open_time >= $4 AND open_time < $5
         "#,
-        table_name
-    ))
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(start)
+    .bind(end)
     .fetch_one(pool)
     .await
-    .expect("Failed to get table length")
 }
 
-pub async fn check_column_exists(pool: &PgPool, table_name: &str, column_name: &str) -> bool {
-    sqlx::query_scalar(&format!(
+pub async fn get_min_open_time(pool: &PgPool, series: &KlineSeries) -> u64 {
+    let result: Option<i64> = sqlx::query_scalar(
         r#"
-        SELECT EXISTS (
-            SELECT FROM information_schema.columns 
-            WHERE table_name = '{}' AND column_name = '{}'
-        )
+        SELECT MIN(open_time) FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3
         "#,
-        table_name, column_name
-    ))
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
     .fetch_one(pool)
     .await
-    .expect("Failed to check if column exists")
+    .expect("Failed to get min open time");
+
+    result.unwrap_or(0).unsigned_abs()
 }
 
-pub async fn check_columns_exists(
-    pool: &PgPool,
-    table_name: &str,
-    column_names: &Vec<String>,
-) -> bool {
-    let mut exists = true;
+pub async fn get_max_open_time(pool: &PgPool, series: &KlineSeries) -> u64 {
+    let result: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT MAX(open_time) FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3
+        "#,
+    )
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .fetch_one(pool)
+    .await
+    .expect("Failed to get max open time");
 
-    for column_name in column_names {
-        exists = check_column_exists(pool, table_name, column_name).await;
+    result.unwrap_or(0).unsigned_abs()
+}
 
-        if !exists {
-            break;
-        }
+fn parse_kline(row: &PgRow) -> Kline {
+    Kline {
+        open_time: DateTime::<Utc>::from_timestamp_millis(row.get::<i64, _>("open_time")).unwrap(),
+        open: row.get("open"),
+        high: row.get("high"),
+        low: row.get("low"),
+        close: row.get("close"),
+        volume: row.get("volume"),
+        close_time: DateTime::<Utc>::from_timestamp_millis(row.get::<i64, _>("close_time"))
+            .unwrap(),
+        quote_asset_volume: row.get("quote_asset_volume"),
+        number_of_trades: row.get("number_of_trades"),
+        taker_buy_base_asset_volume: row.get("taker_buy_base_asset_volume"),
+        taker_buy_quote_asset_volume: row.get("taker_buy_quote_asset_volume"),
     }
+}
+
+// Klines opened in [start, end) (open times in milliseconds), sorted by open time
+pub async fn get_klines(
+    pool: &PgPool,
+    series: &KlineSeries,
+    (start, end): (i64, i64),
+) -> Result<Vec<Kline>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
+        r#"
+        SELECT {}
+        FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3 AND open_time >= $4 AND open_time < $5
+        ORDER BY open_time
+        "#,
+        KLINE_COLUMNS
+    ))
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(start)
+    .bind(end)
+    .fetch_all(pool)
+    .await?;
 
-    exists
+    Ok(rows.iter().map(parse_kline).collect())
 }
 
-pub async fn create_indicator_column(
+// The `limit` last klines opened before the given time (open time in milliseconds),
+// sorted by open time
+pub async fn get_last_klines(
     pool: &PgPool,
-    table_name: &str,
-    column_name: &str,
-) -> Result<(), sqlx::Error> {
-    let result = sqlx::query(&format!(
+    series: &KlineSeries,
+    before: Option<i64>,
+    limit: i64,
+) -> Result<Vec<Kline>, sqlx::Error> {
+    let rows = sqlx::query(&format!(
         r#"
-        ALTER TABLE {} ADD COLUMN {} FLOAT
+        SELECT {}
+        FROM kline
+        WHERE market = $1 AND symbol = $2 AND interval = $3 AND open_time < $4
+        ORDER BY open_time DESC
+        LIMIT $5
         "#,
-        table_name, column_name
+        KLINE_COLUMNS
     ))
-    .execute(pool)
-    .await;
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(before.unwrap_or(i64::MAX))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
 
-    result.map(|_| ())
+    Ok(rows.iter().rev().map(parse_kline).collect())
 }
 
-pub async fn create_indicator_columns(
+// Insert a kline (fails if a kline with the same open time is already stored)
+pub async fn insert_kline(
     pool: &PgPool,
-    table_name: &str,
-    column_names: &Vec<String>,
+    series: &KlineSeries,
+    kline: &Kline,
 ) -> Result<(), sqlx::Error> {
-    for column_name in column_names {
-        create_indicator_column(pool, table_name, column_name)
-            .await
-            .unwrap();
-    }
-
-    Ok(())
+    write_kline(pool, series, kline, "").await
 }
 
-pub async fn get_min_open_time(pool: &PgPool, table_name: &str) -> u64 {
-    let result: i64 = sqlx::query_scalar(&format!(
+// Insert a kline, or replace the one with the same open time
+pub async fn upsert_kline(
+    pool: &PgPool,
+    series: &KlineSeries,
+    kline: &Kline,
+) -> Result<(), sqlx::Error> {
+    write_kline(
+        pool,
+        series,
+        kline,
         r#"
-        SELECT MIN(open_time) FROM {}
+        ON CONFLICT (market, symbol, interval, open_time) DO UPDATE SET
+            open = EXCLUDED.open, high = EXCLUDED.high, low = EXCLUDED.low, close = EXCLUDED.close,
+            volume = EXCLUDED.volume, close_time = EXCLUDED.close_time,
+            quote_asset_volume = EXCLUDED.quote_asset_volume,
+            number_of_trades = EXCLUDED.number_of_trades,
+            taker_buy_base_asset_volume = EXCLUDED.taker_buy_base_asset_volume,
+            taker_buy_quote_asset_volume = EXCLUDED.taker_buy_quote_asset_volume
         "#,
-        table_name
-    ))
-    .fetch_one(pool)
+    )
     .await
-    .expect("Failed to get min open time");
-
-    result.abs() as u64
 }
 
-pub async fn get_max_open_time(pool: &PgPool, table_name: &str) -> u64 {
-    let result: i64 = sqlx::query_scalar(&format!(
+async fn write_kline(
+    pool: &PgPool,
+    series: &KlineSeries,
+    kline: &Kline,
+    on_conflict: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(&format!(
         r#"
-        SELECT MAX(open_time) FROM {}
+        INSERT INTO kline (market, symbol, interval, {})
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        {}
         "#,
-        table_name
+        KLINE_COLUMNS, on_conflict
     ))
-    .fetch_one(pool)
-    .await
-    .expect("Failed to get max open time");
-
-    result.abs() as u64
-}
+    .bind(&series.market)
+    .bind(&series.symbol)
+    .bind(&series.interval)
+    .bind(kline.open_time.timestamp_millis())
+    .bind(kline.open)
+    .bind(kline.high)
+    .bind(kline.low)
+    .bind(kline.close)
+    .bind(kline.volume)
+    .bind(kline.close_time.timestamp_millis())
+    .bind(kline.quote_asset_volume)
+    .bind(kline.number_of_trades)
+    .bind(kline.taker_buy_base_asset_volume)
+    .bind(kline.taker_buy_quote_asset_volume)
+    .execute(pool)
+    .await?;
 
-// &serde_json::Value to f64
-pub fn json_value_to_f64(value: &serde_json::Value) -> f64 {
-    value
-        .as_str()
-        .expect("Failed to get value")
-        .parse::<f64>()
-        .unwrap()
+    Ok(())
 }
 
 // Check KlineCollection klines integrity
@@ -207,19 +285,3 @@ pub fn check_klines_collection_integrity(klines_collection: &KlineCollection) ->
 
     check_1 && check_2
 }
-
-// pub async fn check_table_empty(pool: &PgPool, table_name: &str) -> bool {
-//     let result: bool = sqlx::query_scalar(&format!(
-//         r#"
-//         SELECT EXISTS (
-//             SELECT FROM {}
-//         )
-//         "#,
-//         table_name
-//     ))
-//     .fetch_one(pool)
-//     .await
-//     .expect("Failed to check if table is empty");
-
-//     !result
-// }
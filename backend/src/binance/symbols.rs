@@ -3,40 +3,47 @@ use binance_spot_connector_rust::{
     hyper::BinanceHttpClient,
 };
 
-use crate::objects::objects::CryptoSymbolSimple;
+use crate::objects::{markets::MarketType, objects::CryptoSymbolSimple};
 
-const BINANCE_FUTURES_API_URL: &str = "https://fapi.binance.com";
-
-pub async fn get_symbols_actual_info(symbol_volumes_vec: &mut Vec<CryptoSymbolSimple>) {
+pub async fn get_symbols_actual_info(
+    market: &MarketType,
+    api_url: &str,
+    symbol_volumes_vec: &mut Vec<CryptoSymbolSimple>,
+) -> Result<(), String> {
     // Create a new BinanceHttpClient
-    let futures_client = BinanceHttpClient::with_url(BINANCE_FUTURES_API_URL);
+    let futures_client = BinanceHttpClient::with_url(api_url);
+    let api_path = market.to_binance_api_path();
 
-    // Create a new custom endpoint '/fapi/v1/exchangeInfo' to fetch the exchange info
-    let builder_exchange_info = RequestBuilder::new(Method::Get, "/fapi/v1/exchangeInfo");
+    // Create a new custom endpoint '.../exchangeInfo' to fetch the exchange info
+    let builder_exchange_info =
+        RequestBuilder::new(Method::Get, &format!("{}/exchangeInfo", api_path));
 
     let data = futures_client
         .send(builder_exchange_info)
         .await
-        .expect("Failed to get exchange info")
+        .map_err(|e| format!("Failed to get exchange info: {:?}", e))?
         .into_body_str()
         .await
-        .expect("Failed to convert response to string");
+        .map_err(|e| format!("Failed to convert response to string: {:?}", e))?;
 
     let data: serde_json::Value =
-        serde_json::from_str(&data).expect("Failed to parse exchange info");
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse exchange info: {}", e))?;
 
-    let symbols = data["symbols"].as_array().expect("Failed to get symbols");
+    let symbols = data["symbols"].as_array().ok_or("Failed to get symbols")?;
 
     let mut symbols_availability: Vec<(String, bool)> = Vec::new();
 
     for symbol in symbols {
-        let symbol_info = symbol.as_object().expect("Failed to get symbol");
+        let symbol_info = symbol.as_object().ok_or("Failed to get symbol")?;
         let symbol = symbol_info["symbol"]
             .as_str()
-            .expect("Failed to get symbol");
-        let status = symbol_info["status"]
-            .as_str()
-            .expect("Failed to get status");
+            .ok_or("Failed to get symbol")?;
+        // COIN-M futures name it 'contractStatus'
+        let status = match market {
+            MarketType::CoinM => &symbol_info["contractStatus"],
+            _ => &symbol_info["status"],
+        };
+        let status = status.as_str().ok_or("Failed to get status")?;
 
         let symbol = symbol.to_string();
         symbols_availability.push((symbol, status == "TRADING"));
@@ -46,23 +53,24 @@ pub async fn get_symbols_actual_info(symbol_volumes_vec: &mut Vec<CryptoSymbolSi
 
     //
 
-    // Create a new custom endpoint '/fapi/v1/ticker/24hr' to fetch the 24h ticker
-    let builder_ticker_24h = RequestBuilder::new(Method::Get, "/fapi/v1/ticker/24hr");
+    // Create a new custom endpoint '.../ticker/24hr' to fetch the 24h ticker
+    let builder_ticker_24h = RequestBuilder::new(Method::Get, &format!("{}/ticker/24hr", api_path));
 
     // Send the request to the endpoint
     let data = futures_client
         .send(builder_ticker_24h)
         .await
-        .expect("Failed to get 24h ticker")
+        .map_err(|e| format!("Failed to get 24h ticker: {:?}", e))?
         .into_body_str()
         .await
-        .expect("Failed to convert response to string");
+        .map_err(|e| format!("Failed to convert response to string: {:?}", e))?;
 
     // Parse the response
-    let data: serde_json::Value = serde_json::from_str(&data).expect("Failed to parse 24h ticker");
+    let data: serde_json::Value =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse 24h ticker: {}", e))?;
 
     // Get the data as an array
-    let data = data.as_array().expect("Failed to get 24h ticker");
+    let data = data.as_array().ok_or("Failed to get 24h ticker")?;
 
     // For each symbol in the data, get the symbol and calculate the volume
     // Add the symbol and its volume to the Vec
@@ -71,28 +79,34 @@ pub async fn get_symbols_actual_info(symbol_volumes_vec: &mut Vec<CryptoSymbolSi
         // println!("{:?}", symbol);
 
         // Get the symbol and volume
-        let symbol_info = symbol.as_object().expect("Failed to get symbol");
+        let symbol_info = symbol.as_object().ok_or("Failed to get symbol")?;
         let symbol = symbol_info["symbol"]
             .as_str()
-            .expect("Failed to get symbol");
-        let volume = symbol_info["volume"]
-            .as_str()
-            .expect("Failed to get volume");
+            .ok_or("Failed to get symbol")?;
+        // COIN-M futures volumes are in contracts, their volume in the base asset is 'baseVolume'
+        let volume = match market {
+            MarketType::CoinM => &symbol_info["baseVolume"],
+            _ => &symbol_info["volume"],
+        };
+        let volume = volume.as_str().ok_or("Failed to get volume")?;
 
         // Multiply by weightedAvgPrice to get the volume in USD
-        let volume = volume.parse::<f64>().expect("Failed to parse volume")
+        let volume = volume
+            .parse::<f64>()
+            .map_err(|_| format!("Failed to parse volume: {}", volume))?
             * symbol_info["weightedAvgPrice"]
                 .as_str()
-                .expect("Failed to get weighted average price")
+                .ok_or("Failed to get weighted average price")?
                 .parse::<f64>()
-                .expect("Failed to parse weighted average price") as f64;
+                .map_err(|_| "Failed to parse weighted average price".to_string())?;
 
         // Get the availability of the symbol
+        // (the spot ticker also lists symbols that are no longer in the exchange info)
         let available = symbols_availability
             .iter()
             .find(|(s, _)| s == symbol)
-            .expect("Failed to get availability")
-            .1;
+            .map(|(_, available)| *available)
+            .unwrap_or(false);
 
         // Add the symbol and its volume to the Vec
         symbol_volumes_vec.push(CryptoSymbolSimple {
@@ -101,6 +115,8 @@ pub async fn get_symbols_actual_info(symbol_volumes_vec: &mut Vec<CryptoSymbolSi
             available,
         });
     }
+
+    Ok(())
 }
 
 //
@@ -0,0 +1,62 @@
+use rocket::State;
+use sqlx::PgPool;
+
+// Not routes, but functions to be used by the tasks
+
+// Save (or replace) the checkpoint of a task
+pub async fn save_checkpoint(
+    pool: &State<PgPool>,
+    task_id: i32,
+    iteration: i32,
+    state: String,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO checkpoint (task_id, iteration, state)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (task_id) DO UPDATE
+        SET iteration = EXCLUDED.iteration, state = EXCLUDED.state, updated_at = CURRENT_TIMESTAMP
+        "#,
+        task_id,
+        iteration,
+        state
+    )
+    .execute(&**pool)
+    .await?;
+
+    Ok(())
+}
+
+// Get the last checkpoint of a task (if any)
+pub async fn get_checkpoint(
+    pool: &State<PgPool>,
+    task_id: i32,
+) -> Result<Option<String>, sqlx::Error> {
+    let rec = sqlx::query!(
+        r#"
+        SELECT state
+        FROM checkpoint
+        WHERE task_id = $1
+        "#,
+        task_id
+    )
+    .fetch_optional(&**pool)
+    .await?;
+
+    Ok(rec.map(|row| row.state))
+}
+
+// Delete the checkpoint of a task (once the task is over)
+pub async fn delete_checkpoint(pool: &State<PgPool>, task_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM checkpoint
+        WHERE task_id = $1
+        "#,
+        task_id
+    )
+    .execute(&**pool)
+    .await?;
+
+    Ok(())
+}
@@ -5,6 +5,7 @@ use sqlx::PgPool;
 
 use crate::objects::{
     intervals::CryptoInterval,
+    markets::MarketType,
     objects::{CreateCryptoList, CryptoListComplete},
 };
 
@@ -18,7 +19,7 @@ pub async fn get_crypto_lists(
         let id = id.parse::<i32>().unwrap();
         let recs = sqlx::query!(
             r#"
-            SELECT id, hidden, name, interval::TEXT as interval, limit_count, type
+            SELECT id, hidden, name, interval::TEXT as interval, limit_count, type, market as "market: MarketType"
             FROM crypto_list
             WHERE id = $1
             "#,
@@ -57,6 +58,7 @@ pub async fn get_crypto_lists(
                 interval: CryptoInterval::parse_from(row.interval.as_deref().unwrap()),
                 limit_count: row.limit_count,
                 r#type: row.r#type,
+                market: row.market,
                 crypto_symbols: crypto_symbols
                     .iter()
                     .map(|row| row.crypto_symbol_id)
@@ -69,7 +71,7 @@ pub async fn get_crypto_lists(
     } else {
         let recs = sqlx::query!(
             r#"
-            SELECT id, hidden, name, interval::TEXT as interval, limit_count, type
+            SELECT id, hidden, name, interval::TEXT as interval, limit_count, type, market as "market: MarketType"
             FROM crypto_list
             "#,
         )
@@ -86,6 +88,7 @@ pub async fn get_crypto_lists(
                 interval: CryptoInterval::parse_from(row.interval.as_deref().unwrap()),
                 limit_count: row.limit_count,
                 r#type: row.r#type,
+                market: row.market,
                 crypto_symbols: vec![],
             })
             .collect();
@@ -107,14 +110,15 @@ pub async fn create_crypto_list(
     // Insert the new crypto list and get its id
     let res = sqlx::query!(
         r#"
-        INSERT INTO crypto_list (name, interval, limit_count, type)
-        VALUES ($1, $2, $3, $4)
+        INSERT INTO crypto_list (name, interval, limit_count, type, market)
+        VALUES ($1, $2, $3, $4, $5)
         RETURNING id
         "#,
         create_crypto_list.name,
         create_crypto_list.interval.clone() as CryptoInterval,
         create_crypto_list.limit_count,
         create_crypto_list.r#type,
+        create_crypto_list.market.clone() as MarketType,
     )
     .fetch_one(&**pool)
     .await
@@ -4,6 +4,7 @@ use sqlx::PgPool;
 use std::env;
 
 use crate::objects::objects::Status;
+use crate::utils::db::PoolMetrics;
 
 #[get("/health")]
 pub async fn health_check() -> Json<Status> {
@@ -13,6 +14,12 @@ pub async fn health_check() -> Json<Status> {
     })
 }
 
+// Connections of the database pool
+#[get("/health/pool")]
+pub async fn pool_metrics(pool: &State<PgPool>) -> Json<PoolMetrics> {
+    Json(PoolMetrics::new(pool))
+}
+
 // /// Produce an infinite series of `"hello"`s, one per second.
 // #[get("/infinite-hellos")]
 // pub fn hello() -> TextStream![&'static str] {
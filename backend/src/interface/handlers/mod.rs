@@ -1,8 +1,10 @@
+pub mod checkpoints;
 pub mod crypto_lists;
 pub mod crypto_symbols;
 pub mod general;
 pub mod indicators;
 pub mod mh_algorithms;
 pub mod mh_objects;
+pub mod results;
 pub mod streams;
 pub mod tasks;
@@ -0,0 +1,78 @@
+use rocket::serde::json::Json;
+use rocket::{get, State};
+use sqlx::PgPool;
+
+use crate::objects::objects::Result as TaskResult;
+
+// Define a route to get the results (all of them, or the one with the given id)
+#[get("/result?<id>")]
+pub async fn get_results(pool: &State<PgPool>, id: Option<i32>) -> Json<Vec<TaskResult>> {
+    let results = match id {
+        Some(id) => {
+            sqlx::query_as!(
+                TaskResult,
+                r#"
+                SELECT id, results, other_parameters
+                FROM result
+                WHERE id = $1
+                "#,
+                id
+            )
+            .fetch_all(&**pool)
+            .await
+        }
+        None => {
+            sqlx::query_as!(
+                TaskResult,
+                r#"
+                SELECT id, results, other_parameters
+                FROM result
+                "#,
+            )
+            .fetch_all(&**pool)
+            .await
+        }
+    }
+    .unwrap();
+
+    Json(results)
+}
+
+// Not a route, but a function to be used by the tasks
+pub async fn create_task_result(
+    pool: &State<PgPool>,
+    task_id: i32,
+    results: String,
+    other_parameters: Option<String>,
+) -> Result<i32, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+
+    let result_id = sqlx::query!(
+        r#"
+        INSERT INTO result (results, other_parameters)
+        VALUES ($1, $2)
+        RETURNING id
+        "#,
+        results,
+        other_parameters
+    )
+    .fetch_one(&mut *transaction)
+    .await?
+    .id;
+
+    sqlx::query!(
+        r#"
+        UPDATE task
+        SET result_id = $2
+        WHERE id = $1
+        "#,
+        task_id,
+        result_id
+    )
+    .execute(&mut *transaction)
+    .await?;
+
+    transaction.commit().await?;
+
+    Ok(result_id)
+}
@@ -2,39 +2,76 @@ use rocket::get;
 use rocket::response::stream::TextStream;
 use rocket::State;
 use serde::Serialize;
-use tokio::sync::watch;
+use tokio::sync::broadcast;
+
+use crate::binance::klines::progress::KlinesProgress;
+use crate::metaheuristic::Progress;
+use crate::objects::objects::TaskState;
+
+// Updates kept for the subscribers that fall behind (the oldest ones are dropped after that)
+const TASK_UPDATES_CAPACITY: usize = 1024;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct TaskUpdate {
     pub task_id: i32,
     pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>, // progress of the optimisation run (running tasks only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub klines_progress: Option<KlinesProgress>, // progress of the klines acquisition of a symbol
 }
 
 impl std::fmt::Display for TaskUpdate {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{{ \"task_id\": {}, \"state\": \"{}\" }}",
-            self.task_id, self.state
-        )
+        write!(f, "{}", serde_json::to_string(self).unwrap())
     }
 }
 
-#[derive(Debug)]
+/// Every update is delivered to each subscriber (the state changes are not replaced by the
+/// progress updates sent after them)
+#[derive(Debug, Clone)]
 pub struct TaskStateChannel {
-    pub sender: watch::Sender<TaskUpdate>,
+    pub sender: broadcast::Sender<TaskUpdate>,
 }
 
 impl TaskStateChannel {
     pub fn new() -> Self {
         Self {
-            sender: watch::channel(TaskUpdate {
-                task_id: 0,
-                state: "created".to_string(),
-            })
-            .0,
+            sender: broadcast::channel(TASK_UPDATES_CAPACITY).0,
         }
     }
+
+    // Sending fails only when nobody is subscribed, the update is then dropped
+    fn send(&self, update: TaskUpdate) {
+        let _ = self.sender.send(update);
+    }
+
+    pub fn send_state(&self, task_id: i32, state: &TaskState) {
+        self.send(TaskUpdate {
+            task_id,
+            state: state.convert_to(),
+            progress: None,
+            klines_progress: None,
+        });
+    }
+
+    pub fn send_progress(&self, task_id: i32, progress: &Progress) {
+        self.send(TaskUpdate {
+            task_id,
+            state: TaskState::Running.convert_to(),
+            progress: Some(progress.clone()),
+            klines_progress: None,
+        });
+    }
+
+    pub fn send_klines_progress(&self, task_id: i32, klines_progress: &KlinesProgress) {
+        self.send(TaskUpdate {
+            task_id,
+            state: TaskState::Running.convert_to(),
+            progress: None,
+            klines_progress: Some(klines_progress.clone()),
+        });
+    }
 }
 
 /// Send task updates to the client
@@ -42,9 +79,46 @@ impl TaskStateChannel {
 pub fn hello(state: &State<TaskStateChannel>) -> TextStream![String] {
     let mut rx = state.sender.subscribe();
     TextStream! {
-        while let Ok(_) = rx.changed().await {
-            let a = rx.borrow().clone();
-            yield format!("{}", a).to_string();
+        loop {
+            match rx.recv().await {
+                Ok(update) => yield update.to_string(),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    println!("Task updates client too slow, {} updates skipped", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_changes_not_lost_between_progress_updates() {
+        let channel = TaskStateChannel::new();
+        let mut rx = channel.sender.subscribe();
+        let progress = Progress::new(1, Some(10), 20, &[], &[]);
+
+        channel.send_state(1, &TaskState::Running);
+        for _ in 0..10 {
+            channel.send_progress(1, &progress);
+        }
+        channel.send_state(1, &TaskState::Completed);
+        channel.send_progress(2, &progress);
+
+        let mut states = Vec::new();
+        while let Ok(update) = rx.try_recv() {
+            if update.progress.is_none() {
+                states.push((update.task_id, update.state));
+            }
         }
+        assert_eq!(
+            states,
+            vec![(1, "RUNNING".to_string()), (1, "COMPLETED".to_string())]
+        );
     }
 }
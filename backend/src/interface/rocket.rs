@@ -1,15 +1,13 @@
-use rocket::fairing::AdHoc;
 use rocket::http::Method;
 use rocket::Build;
 use rocket_cors::{AllowedHeaders, AllowedOrigins};
-use sqlx::postgres::PgPoolOptions;
-use std::env;
+use sqlx::PgPool;
 
 use super::handlers::streams::TaskStateChannel;
 use super::routes;
 
-// Define the Rocket instance
-pub fn rocket() -> rocket::Rocket<Build> {
+// Define the Rocket instance, with the pool of the application
+pub fn rocket(pool: PgPool) -> rocket::Rocket<Build> {
     // Configure CORS
     let cors = rocket_cors::CorsOptions {
         allowed_origins: AllowedOrigins::all(),
@@ -24,23 +22,11 @@ pub fn rocket() -> rocket::Rocket<Build> {
     .to_cors()
     .expect("CORS configuration error");
 
-    // Check if the DATABASE_URL environment variable is set
-    let _ = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-
     // Return the Rocket instance
     rocket::build()
         // .attach(Db::fairing())
         .configure(rocket::Config::figment().merge(("port", 9797)))
-        .attach(AdHoc::on_ignite("Database", |rocket| async {
-            let database_url: String = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-            let pool = PgPoolOptions::new()
-                .max_connections(5)
-                .connect(&database_url)
-                .await
-                .expect("Failed to create pool.");
-
-            rocket.manage(pool)
-        }))
+        .manage(pool)
         .manage(TaskStateChannel::new())
         .mount("/api", routes::get_routes())
         .attach(cors)
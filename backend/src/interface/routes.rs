@@ -6,6 +6,7 @@ pub fn get_routes() -> Vec<Route> {
         // General
         // general::hello,
         general::health_check,
+        general::pool_metrics,
         general::purge_hidden_orphans,
         // Crypto Symbols
         crypto_symbols::get_crypto_symbols,
@@ -25,6 +26,8 @@ pub fn get_routes() -> Vec<Route> {
         tasks::create_task,
         tasks::queue_task,
         tasks::cancel_task,
+        // Results
+        results::get_results,
         // Indicators
         indicators::get_indicators,
         indicators::get_indicators_in_combination,
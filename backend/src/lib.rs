@@ -1,6 +1,8 @@
 pub mod binance;
 pub mod interface;
+pub mod listener;
 pub mod manager;
+pub mod market_data;
 pub mod metaheuristic;
 pub mod objects;
 pub mod strategy;
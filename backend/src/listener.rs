@@ -1,17 +1,466 @@
-use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::env;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ReceivedData {
-    received_message: String,
-    received_value: i32,
-    processed: bool,
+use crate::binance::klines::utils::{self, KlineSeries};
+use crate::market_data::RetryPolicy;
+use crate::objects::{intervals::CryptoInterval, klines::Kline, markets::MarketType};
+
+const REFRESH_INTERVAL_DEFAULT: Duration = Duration::from_secs(60);
+// Streams allowed on a connection by Binance (the futures markets have the lowest limit)
+const MAX_STREAMS_PER_CONNECTION: usize = 200;
+
+/// Kline stream of a symbol on the Binance WebSocket API
+#[derive(Debug, Clone)]
+pub struct KlineStream {
+    pub symbol: String,
+    pub interval: CryptoInterval,
+}
+
+impl KlineStream {
+    // Name of the stream (ex: btcusdt@kline_5m)
+    pub fn name(&self) -> String {
+        format!(
+            "{}@kline_{}",
+            self.symbol.to_lowercase(),
+            self.interval.to_binance_string()
+        )
+    }
+}
+
+/// Kline received once closed (final values)
+#[derive(Debug, Clone)]
+pub struct ClosedKline {
+    pub symbol: String,
+    pub interval: CryptoInterval,
+    pub kline: Kline,
+}
+
+// Message of a combined stream: {"stream": "btcusdt@kline_5m", "data": {"e": "kline", ...}}
+#[derive(Deserialize)]
+struct StreamMessage {
+    data: KlineEvent,
+}
+
+#[derive(Deserialize)]
+struct KlineEvent {
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "k")]
+    kline: KlineData,
+}
+
+#[derive(Deserialize)]
+struct KlineData {
+    #[serde(rename = "t")]
+    open_time: i64,
+    #[serde(rename = "T")]
+    close_time: i64,
+    #[serde(rename = "i")]
+    interval: String,
+    #[serde(rename = "o")]
+    open: String,
+    #[serde(rename = "h")]
+    high: String,
+    #[serde(rename = "l")]
+    low: String,
+    #[serde(rename = "c")]
+    close: String,
+    #[serde(rename = "v")]
+    volume: String,
+    #[serde(rename = "q")]
+    quote_asset_volume: String,
+    #[serde(rename = "n")]
+    number_of_trades: i64,
+    #[serde(rename = "V")]
+    taker_buy_base_asset_volume: String,
+    #[serde(rename = "Q")]
+    taker_buy_quote_asset_volume: String,
+    #[serde(rename = "x")]
+    closed: bool,
+}
+
+/// Keeps the klines of the active crypto lists of a market up to date with the Binance streams
+#[derive(Debug, Clone)]
+pub struct KlineListener {
+    market: MarketType,
+    stream_url: String,
+    refresh_interval: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl KlineListener {
+    pub fn new(market: &MarketType) -> Self {
+        KlineListener {
+            market: market.clone(),
+            stream_url: market.to_binance_stream_url(),
+            refresh_interval: REFRESH_INTERVAL_DEFAULT,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    // Listener of each market, unless disabled by KLINE_STREAMING=false
+    // (or when the market data comes from files)
+    pub fn all_from_env() -> Vec<KlineListener> {
+        let enabled = env::var("KLINE_STREAMING").as_deref() != Ok("false")
+            && env::var("MARKET_DATA_PROVIDER").as_deref() != Ok("file");
+        match enabled {
+            true => MarketType::all().iter().map(KlineListener::new).collect(),
+            false => vec![],
+        }
+    }
+
+    // Another base URL for the streams (testnet, local server)
+    pub fn with_url(mut self, stream_url: &str) -> Self {
+        self.stream_url = stream_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Delay between two checks of the active crypto lists
+    pub fn with_refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+
+    /// Store the closed klines of the active crypto lists (runs forever)
+    pub async fn start(&self, pool: PgPool) {
+        let (kline_sender, kline_receiver) = mpsc::channel::<ClosedKline>(1024);
+        tokio::spawn(store_klines(
+            pool.clone(),
+            self.market.clone(),
+            kline_receiver,
+        ));
+
+        // The streams are updated when the active crypto lists change
+        let (streams_sender, mut streams_receiver) = watch::channel(Vec::<KlineStream>::new());
+        let market = self.market.clone();
+        let refresh_interval = self.refresh_interval;
+        tokio::spawn(async move {
+            loop {
+                match get_active_streams(&pool, &market).await {
+                    Ok(streams) => {
+                        streams_sender.send_if_modified(|current| {
+                            let changed = stream_names(current) != stream_names(&streams);
+                            *current = streams;
+                            changed
+                        });
+                    }
+                    Err(e) => println!("[LISTENER] Error getting the active streams: {:?}", e),
+                }
+                sleep(refresh_interval).await;
+            }
+        });
+
+        // One connection per chunk of streams, all of them opened again when the streams change
+        loop {
+            let streams = streams_receiver.borrow_and_update().clone();
+            let connections: Vec<_> = connection_chunks(&streams)
+                .into_iter()
+                .map(|chunk| {
+                    tokio::spawn(self.clone().listen(
+                        chunk,
+                        kline_sender.clone(),
+                        streams_receiver.clone(),
+                    ))
+                })
+                .collect();
+
+            let changed = streams_receiver.changed().await;
+            for connection in connections {
+                connection.abort();
+            }
+            if changed.is_err() {
+                return;
+            }
+        }
+    }
+
+    // Listen to the streams of a connection, connecting again after a delay when the connection
+    // is closed (the initial backoff) or fails (the backoff of the attempt)
+    async fn listen(
+        self,
+        streams: Vec<KlineStream>,
+        sender: mpsc::Sender<ClosedKline>,
+        mut changes: watch::Receiver<Vec<KlineStream>>,
+    ) {
+        let mut attempt = 0;
+        loop {
+            match self.stream_klines(&streams, &sender, &mut changes).await {
+                Ok(()) => attempt = 0,
+                Err(e) => println!("[LISTENER] {:?} stream error: {}", self.market, e),
+            }
+            sleep(self.retry_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send the closed klines of the streams to the channel, until the connection is closed
+    /// or the streams change
+    pub async fn stream_klines(
+        &self,
+        streams: &[KlineStream],
+        sender: &mpsc::Sender<ClosedKline>,
+        changes: &mut watch::Receiver<Vec<KlineStream>>,
+    ) -> Result<(), String> {
+        let url = format!(
+            "{}/stream?streams={}",
+            self.stream_url,
+            stream_names(streams).join("/")
+        );
+        let (mut socket, _) = connect_async(&url).await.map_err(|e| e.to_string())?;
+        println!(
+            "[LISTENER] Listening to {} {:?} stream(s)",
+            streams.len(),
+            self.market
+        );
+
+        // The pings of the server are answered while reading
+        loop {
+            tokio::select! {
+                message = socket.next() => match message {
+                    Some(Ok(Message::Text(text))) => match parse_kline_message(text.as_str()) {
+                        Ok(Some(closed_kline)) => sender
+                            .send(closed_kline)
+                            .await
+                            .map_err(|e| e.to_string())?,
+                        Ok(None) => {}
+                        Err(e) => println!("[LISTENER] Invalid message: {}", e),
+                    },
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e.to_string()),
+                },
+                changed = changes.changed() => {
+                    let _ = socket.close(None).await;
+                    return changed.map_err(|e| e.to_string());
+                }
+            }
+        }
+    }
+}
+
+// Streams of each connection (at most MAX_STREAMS_PER_CONNECTION each)
+fn connection_chunks(streams: &[KlineStream]) -> Vec<Vec<KlineStream>> {
+    streams
+        .chunks(MAX_STREAMS_PER_CONNECTION)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+fn stream_names(streams: &[KlineStream]) -> Vec<String> {
+    streams.iter().map(KlineStream::name).collect()
 }
 
-// Method 1: Using a channel to receive updates
-pub async fn listen_for_updates(mut rx: mpsc::Receiver<i32>) {
-    while let Some(data) = rx.recv().await {
-        println!("Received update: {:?}", data);
-        // Process the data here
+// Kline of a message, if it is closed
+fn parse_kline_message(text: &str) -> Result<Option<ClosedKline>, String> {
+    let event = serde_json::from_str::<StreamMessage>(text)
+        .map_err(|e| e.to_string())?
+        .data;
+    let data = event.kline;
+    if !data.closed {
+        return Ok(None);
+    }
+
+    let interval = CryptoInterval::parse_from_binance(&data.interval)
+        .ok_or(format!("Unknown interval: {}", data.interval))?;
+    let time = |millis: i64| {
+        DateTime::<Utc>::from_timestamp_millis(millis).ok_or(format!("Invalid time: {}", millis))
+    };
+    let number = |value: &str| {
+        value
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid number: {}", value))
+    };
+
+    Ok(Some(ClosedKline {
+        symbol: event.symbol,
+        interval,
+        kline: Kline {
+            open_time: time(data.open_time)?,
+            open: number(&data.open)?,
+            high: number(&data.high)?,
+            low: number(&data.low)?,
+            close: number(&data.close)?,
+            volume: number(&data.volume)?,
+            close_time: time(data.close_time)?,
+            quote_asset_volume: number(&data.quote_asset_volume)?,
+            number_of_trades: data.number_of_trades,
+            taker_buy_base_asset_volume: number(&data.taker_buy_base_asset_volume)?,
+            taker_buy_quote_asset_volume: number(&data.taker_buy_quote_asset_volume)?,
+        },
+    }))
+}
+
+// Streams of the symbols of the crypto lists that are not hidden
+pub async fn get_active_streams(
+    pool: &PgPool,
+    market: &MarketType,
+) -> Result<Vec<KlineStream>, sqlx::Error> {
+    let rows: Vec<(String, CryptoInterval)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT cs.symbol, cl.interval
+        FROM crypto_list cl
+        JOIN crypto_list_x_crypto_symbol clxcs ON cl.id = clxcs.crypto_list_id
+        JOIN crypto_symbol cs ON cs.id = clxcs.crypto_symbol_id
+        WHERE NOT cl.hidden AND cs.market = $1
+        ORDER BY cs.symbol
+        "#,
+    )
+    .bind(market)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(symbol, interval)| KlineStream { symbol, interval })
+        .collect())
+}
+
+// Upsert the klines received
+async fn store_klines(pool: PgPool, market: MarketType, mut receiver: mpsc::Receiver<ClosedKline>) {
+    while let Some(closed_kline) = receiver.recv().await {
+        let series = KlineSeries::new(&market, &closed_kline.symbol, &closed_kline.interval);
+        if let Err(e) = utils::upsert_kline(&pool, &series, &closed_kline.kline).await {
+            println!("[LISTENER] Error storing kline of {:?}: {:?}", series, e);
+        }
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::SinkExt;
+    use std::time::Instant;
+    use tokio::net::TcpListener;
+
+    fn kline_message(open_time: i64, closed: bool) -> String {
+        serde_json::json!({
+            "stream": "btcusdt@kline_1m",
+            "data": {
+                "e": "kline", "E": open_time + 60_000, "s": "BTCUSDT",
+                "k": {
+                    "t": open_time, "T": open_time + 59_999, "s": "BTCUSDT", "i": "1m",
+                    "f": 100, "L": 200, "o": "42000.5", "c": "42010.0", "h": "42020.0",
+                    "l": "41990.0", "v": "12.5", "n": 101, "x": closed, "q": "525000.0",
+                    "V": "6.0", "Q": "252000.0", "B": "0"
+                }
+            }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_kline_message() {
+        assert!(parse_kline_message(&kline_message(1704067200000, false))
+            .unwrap()
+            .is_none());
+
+        let closed_kline = parse_kline_message(&kline_message(1704067200000, true))
+            .unwrap()
+            .unwrap();
+        assert_eq!(closed_kline.symbol, "BTCUSDT");
+        assert!(matches!(closed_kline.interval, CryptoInterval::Int1m));
+        assert_eq!(
+            closed_kline.kline.open_time.timestamp_millis(),
+            1704067200000
+        );
+        assert_eq!(closed_kline.kline.close, 42010.0);
+        assert_eq!(closed_kline.kline.number_of_trades, 101);
+        assert_eq!(closed_kline.kline.taker_buy_quote_asset_volume, 252000.0);
+
+        assert!(parse_kline_message("{\"result\": null, \"id\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_connection_chunks() {
+        let streams: Vec<KlineStream> = (0..450)
+            .map(|i| KlineStream {
+                symbol: format!("SYMBOL{}", i),
+                interval: CryptoInterval::Int1m,
+            })
+            .collect();
+
+        let chunks = connection_chunks(&streams);
+        let sizes: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![200, 200, 50]);
+        assert_eq!(stream_names(&chunks.concat()), stream_names(&streams));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_after_close() {
+        // The server closes every connection at once
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (accepted_sender, mut accepted) = mpsc::channel(4);
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                accepted_sender.send(Instant::now()).await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                socket.close(None).await.unwrap();
+            }
+        });
+
+        let kline_listener = KlineListener {
+            retry_policy: RetryPolicy {
+                initial_backoff: Duration::from_millis(200),
+                ..RetryPolicy::default()
+            },
+            ..KlineListener::new(&MarketType::UsdM).with_url(&url)
+        };
+        let streams = vec![KlineStream {
+            symbol: "BTCUSDT".to_string(),
+            interval: CryptoInterval::Int1m,
+        }];
+        let (sender, _receiver) = mpsc::channel(16);
+        let (_streams_sender, changes) = watch::channel(streams.clone());
+        let connection = tokio::spawn(kline_listener.listen(streams, sender, changes));
+
+        let first = accepted.recv().await.unwrap();
+        let second = accepted.recv().await.unwrap();
+        connection.abort();
+        assert!(second - first >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_stream_klines() {
+        // Local stand-in of the Binance streams: an open kline, then two closed ones
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for (open_time, closed) in [(0, false), (0, true), (60_000, true)] {
+                let message = kline_message(1704067200000 + open_time, closed);
+                socket.send(Message::text(message)).await.unwrap();
+            }
+            socket.close(None).await.unwrap();
+        });
+
+        let kline_listener = KlineListener::new(&MarketType::UsdM).with_url(&url);
+        let streams = vec![KlineStream {
+            symbol: "BTCUSDT".to_string(),
+            interval: CryptoInterval::Int1m,
+        }];
+        let (sender, mut receiver) = mpsc::channel(16);
+        let (_streams_sender, mut changes) = watch::channel(streams.clone());
+        kline_listener
+            .stream_klines(&streams, &sender, &mut changes)
+            .await
+            .unwrap();
+
+        let first = receiver.recv().await.unwrap();
+        let second = receiver.recv().await.unwrap();
+        assert_eq!(first.kline.open_time.timestamp_millis(), 1704067200000);
+        assert_eq!(second.kline.open_time.timestamp_millis(), 1704067260000);
+        assert!(receiver.try_recv().is_err());
     }
 }
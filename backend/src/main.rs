@@ -14,10 +14,8 @@
 // use chrono::Utc;
 use dotenv::dotenv;
 
-// mod listener; // Add this line to import the listener module
-
 use backend::interface::rocket;
-// use backend::utils::loading;
+use backend::listener;
 // use backend::binance;
 use backend::manager;
 use backend::metaheuristic::mh;
@@ -29,24 +27,41 @@ async fn main() {
     // Load the environment variables from the .env file
     assert!(dotenv().is_ok());
 
+    // Pool shared by the whole application
+    let pool_settings = backend::utils::db::PoolSettings::from_env().unwrap();
+    let pool = backend::utils::db::create_pool(&pool_settings)
+        .await
+        .unwrap();
+    println!("Database pool: {:?}", pool_settings);
+
+    // Apply the database migrations (and the demo data, if requested) before anything uses it
+    backend::utils::db::prepare_database(&pool).await.unwrap();
+
     // Create the Rocket application
-    let rocket_app = rocket::rocket();
+    let rocket_app = rocket::rocket(pool.clone());
 
     // Create the TaskManager
-    let task_manager = manager::TaskManager::new(streams::TaskStateChannel {
-        sender: rocket_app
+    let task_manager = manager::TaskManager::new(
+        pool.clone(),
+        rocket_app
             .state::<streams::TaskStateChannel>()
             .unwrap()
-            .sender
             .clone(),
-    })
-    .await;
+    );
 
     // Start the TaskManager
     tokio::spawn(async move {
         task_manager.start().await.unwrap();
     });
 
+    // Keep the klines of the active crypto lists up to date
+    for kline_listener in listener::KlineListener::all_from_env() {
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            kline_listener.start(pool).await;
+        });
+    }
+
     // Run the Rocket application
     rocket_app.launch().await.unwrap();
 
@@ -137,8 +152,6 @@ async fn main() {
 
     // return;
 
-    // loading::test_print_loading();
-
     // Wait 3 seconds before running the Rocket application
     // tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
 
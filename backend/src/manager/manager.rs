@@ -1,7 +1,6 @@
 use sqlx::Pool;
 use sqlx::Postgres;
 use std::collections::HashMap;
-use std::env;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex,
@@ -11,7 +10,9 @@ use std::time::{Duration, Instant};
 
 use super::objects::{TaskLists, ThreadStatus};
 use crate::{
+    binance::klines::progress::{KlinesProgress, KlinesProgressReporter},
     interface::handlers::{streams::TaskStateChannel, tasks},
+    metaheuristic::{Progress, ProgressReporter},
     objects::objects::TaskState,
 };
 
@@ -27,24 +28,9 @@ pub struct TaskManager {
 }
 
 impl TaskManager {
-    pub fn new_with_pool(pool: Pool<Postgres>, task_channel: TaskStateChannel) -> Self {
+    pub fn new(pool: Pool<Postgres>, task_channel: TaskStateChannel) -> Self {
         Self {
             pool,
-            task_channel: task_channel,
-            tasks_processor: TaskLists::new(),
-            statuses: Arc::new(Mutex::new(HashMap::new())),
-            cancel_flags: Arc::new(Mutex::new(HashMap::new())),
-            max_threads: MAX_THREADS_DEFAULT,
-        }
-    }
-
-    pub async fn new(task_channel: TaskStateChannel) -> Self {
-        Self {
-            pool: sqlx::postgres::PgPoolOptions::new()
-                .max_connections(5)
-                .connect(&env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
-                .await
-                .unwrap(),
             task_channel,
             tasks_processor: TaskLists::new(),
             statuses: Arc::new(Mutex::new(HashMap::new())),
@@ -191,12 +177,36 @@ impl TaskManager {
 
         let task = tasks.unwrap();
 
+        // The manager publishes the progress of the task on the task updates channel
+        let progress_reporter = self.progress_reporter(task_id);
+        let klines_reporter = self.klines_progress_reporter(task_id);
+
+        // The task runs on the runtime of the application, which drives the connections of the
+        // shared pool (they would be unusable once the runtime of a task is dropped)
+        let pool = self.pool.clone();
+        let runtime = tokio::runtime::Handle::current();
         self.spawn_monitored_thread(task_id, move |should_cancel| {
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async { task.execute(should_cancel).await })
+            runtime.block_on(async {
+                task.execute(&pool, should_cancel, progress_reporter, klines_reporter)
+                    .await
+            })
         });
     }
 
+    fn progress_reporter(&self, task_id: i32) -> ProgressReporter {
+        let channel = self.task_channel.clone();
+        ProgressReporter::new(Arc::new(move |progress: &Progress| {
+            channel.send_progress(task_id, progress);
+        }))
+    }
+
+    fn klines_progress_reporter(&self, task_id: i32) -> KlinesProgressReporter {
+        let channel = self.task_channel.clone();
+        KlinesProgressReporter::new(Arc::new(move |klines_progress: &KlinesProgress| {
+            channel.send_klines_progress(task_id, klines_progress);
+        }))
+    }
+
     async fn handle_task_pending(
         &self,
         task_id: i32,
@@ -255,25 +265,13 @@ impl TaskManager {
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Should not be called for a running task,
         // only if the task is in the running state but not actually running
-        // So : task failed
+        // (ex: the backend was restarted during the task)
+        // So : task restarted, it resumes from its last checkpoint (if any)
 
-        // Set the task state to failed
-        let res = tasks::update_task_state(
-            &rocket::State::from(&self.pool),
-            &self.task_channel,
-            task_id,
-            TaskState::Failed,
-        )
-        .await;
+        println!("Task resumed - ID: {}", task_id);
+        self.start_task(task_id).await;
 
-        if res.is_ok() {
-            // Remove the task from the running list
-            self.tasks_processor.remove_running_task(task_id);
-        }
-
-        println!("Task failed - ID: {}", task_id);
-
-        res
+        Ok(())
     }
 
     fn spawn_monitored_thread<F>(&self, task_id: i32, work: F)
@@ -0,0 +1,395 @@
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use reqwest::{Client, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use super::rate_limit::{until_next_minute, RateLimiter, RetryPolicy};
+use super::{KlinesRequest, MarketDataProviderTrait};
+use crate::binance::symbols;
+use crate::objects::{klines::Kline, markets::MarketType, objects::CryptoSymbolSimple};
+
+// Pause when the API bans the IP (418) without saying for how long
+const BANNED_PAUSE_SECS: u64 = 120;
+
+// Weight budget of each market, shared by all the providers of the process (the limits are per IP)
+static RATE_LIMITERS: Lazy<Mutex<HashMap<MarketType, RateLimiter>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Binance REST API of a market (spot, USDⓈ-M or COIN-M futures)
+#[derive(Debug, Clone)]
+pub struct BinanceProvider {
+    market: MarketType,
+    api_url: String,
+    client: Client,
+    rate_limiter: RateLimiter,
+    retry_policy: RetryPolicy,
+}
+
+// Outcome of a failed request
+enum RequestError {
+    RateLimited(Duration), // 429 / 418, retried after the given duration
+    Retryable(String),     // network error, server error, invalid response
+    Fatal(String),         // invalid request
+}
+
+impl BinanceProvider {
+    pub fn new(market: &MarketType) -> Self {
+        BinanceProvider {
+            market: market.clone(),
+            api_url: market.to_binance_api_url(),
+            client: Client::new(),
+            rate_limiter: RATE_LIMITERS
+                .lock()
+                .unwrap()
+                .entry(market.clone())
+                .or_insert_with(|| RateLimiter::new(market.to_binance_weight_limit()))
+                .clone(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    // Another base URL for the API (testnet, mock server)
+    pub fn with_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // Weight budget shared with other providers of the same API
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    // One request to the klines endpoint
+    async fn request_klines(&self, params: &[(&str, String)]) -> Result<Vec<Kline>, RequestError> {
+        let response = self
+            .client
+            .get(format!(
+                "{}{}/klines",
+                self.api_url,
+                self.market.to_binance_api_path()
+            ))
+            .query(params)
+            .send()
+            .await
+            .map_err(|e| RequestError::Retryable(format!("Failed to get klines: {}", e)))?;
+
+        // Weight used by all the requests of the IP in the current minute
+        if let Some(used_weight) = header_value(&response, "x-mbx-used-weight-1m")
+            .or_else(|| header_value(&response, "x-mbx-used-weight"))
+        {
+            self.rate_limiter.record_used_weight(used_weight as u32);
+        }
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::IM_A_TEAPOT {
+            let retry_after = header_value(&response, "retry-after")
+                .map(Duration::from_secs)
+                .unwrap_or(match status {
+                    StatusCode::IM_A_TEAPOT => Duration::from_secs(BANNED_PAUSE_SECS),
+                    _ => until_next_minute(),
+                });
+            return Err(RequestError::RateLimited(retry_after));
+        }
+
+        let body = response.text().await.map_err(|e| {
+            RequestError::Retryable(format!("Failed to convert response to string: {}", e))
+        })?;
+        if status.is_server_error() {
+            return Err(RequestError::Retryable(format!(
+                "Server error {}: {}",
+                status, body
+            )));
+        }
+        if !status.is_success() {
+            return Err(RequestError::Fatal(format!(
+                "Request error {}: {}",
+                status, body
+            )));
+        }
+
+        let data: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|e| RequestError::Retryable(format!("Failed to parse klines: {}", e)))?;
+        data.as_array()
+            .ok_or(RequestError::Retryable(format!(
+                "Failed to get klines: {}",
+                body
+            )))?
+            .iter()
+            .map(parse_kline)
+            .collect::<Result<Vec<Kline>, String>>()
+            .map_err(RequestError::Retryable)
+    }
+}
+
+fn header_value(response: &Response, name: &str) -> Option<u64> {
+    response
+        .headers()
+        .get(name)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+}
+
+impl MarketDataProviderTrait for BinanceProvider {
+    async fn fetch_klines(&self, request: &KlinesRequest) -> Result<Vec<Kline>, String> {
+        let mut params: Vec<(&str, String)> = vec![
+            ("symbol", request.symbol.clone()),
+            ("interval", request.interval.to_binance_string()),
+            ("limit", request.limit.to_string()),
+        ];
+        if let Some(start_time) = request.start_time {
+            params.push(("startTime", start_time.to_string()));
+        }
+        if let Some(end_time) = request.end_time {
+            params.push(("endTime", end_time.to_string()));
+        }
+
+        let time_human = request
+            .start_time
+            .or(request.end_time)
+            .and_then(|time| Utc.timestamp_millis_opt(time as i64).single());
+        if let Some(time_human) = time_human {
+            print!("Fetching klines... Time : {:?}", time_human.to_rfc2822());
+        }
+
+        let mut attempt = 0;
+        loop {
+            self.rate_limiter.acquire().await;
+
+            // Rate limited requests wait for the pause of the rate limiter, the others back off
+            let (error, backoff) = match self.request_klines(&params).await {
+                Ok(klines) => return Ok(klines),
+                Err(RequestError::Fatal(message)) => return Err(message),
+                Err(RequestError::RateLimited(retry_after)) => {
+                    self.rate_limiter.pause(retry_after);
+                    ("Rate limited".to_string(), retry_after)
+                }
+                Err(RequestError::Retryable(message)) => {
+                    (message, self.retry_policy.backoff(attempt))
+                }
+            };
+
+            if attempt >= self.retry_policy.max_retries {
+                return Err(format!("{} (after {} retries)", error, attempt));
+            }
+            println!("{} / retry {} in {:?}", error, attempt + 1, backoff);
+            if !self.rate_limiter.is_paused() {
+                sleep(backoff).await;
+            }
+            attempt += 1;
+        }
+    }
+
+    async fn fetch_symbols(&self) -> Result<Vec<CryptoSymbolSimple>, String> {
+        let mut symbols: Vec<CryptoSymbolSimple> = Vec::new();
+        symbols::get_symbols_actual_info(&self.market, &self.api_url, &mut symbols).await?;
+        Ok(symbols)
+    }
+}
+
+// Kline from the array of the Binance API (same format for all the markets)
+// [open_time, "open", "high", "low", "close", "volume", close_time, "quote_asset_volume", number_of_trades, ...]
+fn parse_kline(kline: &serde_json::Value) -> Result<Kline, String> {
+    let kline = kline.as_array().ok_or("Failed to get kline")?;
+    if kline.len() < 11 {
+        return Err(format!("Incomplete kline: {:?}", kline));
+    }
+
+    let time = |value: &serde_json::Value| {
+        value
+            .as_i64()
+            .and_then(DateTime::<Utc>::from_timestamp_millis)
+            .ok_or(format!("Invalid time: {}", value))
+    };
+    let number = |value: &serde_json::Value| {
+        value
+            .as_str()
+            .and_then(|value| value.parse::<f64>().ok())
+            .ok_or(format!("Invalid number: {}", value))
+    };
+
+    Ok(Kline {
+        open_time: time(&kline[0])?,
+        open: number(&kline[1])?,
+        high: number(&kline[2])?,
+        low: number(&kline[3])?,
+        close: number(&kline[4])?,
+        volume: number(&kline[5])?,
+        close_time: time(&kline[6])?,
+        quote_asset_volume: number(&kline[7])?,
+        number_of_trades: kline[8].as_i64().ok_or("Invalid number of trades")?,
+        taker_buy_base_asset_volume: number(&kline[9])?,
+        taker_buy_quote_asset_volume: number(&kline[10])?,
+    })
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const KLINES_BODY: &str = r#"[[1700000000000,"1.0","2.0","0.5","1.5","10.0",1700000059999,"15.0",7,"4.0","6.0","0"]]"#;
+
+    fn http_response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+        let headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}\r\n", name, value))
+            .collect();
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    // Local HTTP server answering the requests with the given responses, in order
+    async fn mock_server(responses: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let requests_server = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buffer = [0u8; 4096];
+                let _ = stream.read(&mut buffer).await;
+                requests_server.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+        (url, requests)
+    }
+
+    fn test_provider(url: &str, max_retries: usize) -> BinanceProvider {
+        BinanceProvider::new(&MarketType::UsdM)
+            .with_url(url)
+            .with_rate_limiter(RateLimiter::new(2400))
+            .with_retry_policy(RetryPolicy {
+                max_retries,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(5),
+            })
+    }
+
+    fn test_request() -> KlinesRequest {
+        KlinesRequest {
+            symbol: "BTCUSDT".to_string(),
+            interval: crate::objects::intervals::CryptoInterval::Int1m,
+            limit: 250,
+            start_time: Some(1700000000000),
+            end_time: None,
+        }
+    }
+
+    #[test]
+    fn test_shared_rate_limiter() {
+        // The providers of a market share its weight budget, the other markets have their own
+        let provider = BinanceProvider::new(&MarketType::CoinM);
+        provider.rate_limiter().record_used_weight(17);
+        assert_eq!(
+            BinanceProvider::new(&MarketType::CoinM)
+                .rate_limiter()
+                .used_weight(),
+            17
+        );
+        assert_ne!(
+            BinanceProvider::new(&MarketType::Spot)
+                .rate_limiter()
+                .used_weight(),
+            17
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_rate_limit() {
+        let (url, requests) = mock_server(vec![
+            http_response("429 Too Many Requests", &[("Retry-After", "0")], ""),
+            http_response("502 Bad Gateway", &[], "upstream error"),
+            http_response("200 OK", &[("X-MBX-USED-WEIGHT-1M", "42")], KLINES_BODY),
+        ])
+        .await;
+        let provider = test_provider(&url, 3);
+
+        let klines = provider.fetch_klines(&test_request()).await.unwrap();
+        assert_eq!(klines.len(), 1);
+        assert_eq!(klines[0].number_of_trades, 7);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+        assert_eq!(provider.rate_limiter().used_weight(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        // Server errors are retried until the limit
+        let (url, requests) = mock_server(vec![
+            http_response("500 Internal Server Error", &[], ""),
+            http_response("503 Service Unavailable", &[], ""),
+        ])
+        .await;
+        assert!(test_provider(&url, 1)
+            .fetch_klines(&test_request())
+            .await
+            .is_err());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Invalid requests are not retried
+        let (url, requests) = mock_server(vec![http_response(
+            "400 Bad Request",
+            &[],
+            r#"{"code":-1121,"msg":"Invalid symbol."}"#,
+        )])
+        .await;
+        let error = test_provider(&url, 3)
+            .fetch_klines(&test_request())
+            .await
+            .unwrap_err();
+        assert!(error.contains("Invalid symbol"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_parse_kline() {
+        let kline = parse_kline(&serde_json::json!([
+            1700000000000i64,
+            "37000.1",
+            "37100.0",
+            "36900.5",
+            "37050.0",
+            "120.5",
+            1700000059999i64,
+            "4460000.0",
+            1500,
+            "60.2",
+            "2230000.0",
+            "0"
+        ]))
+        .unwrap();
+        assert_eq!(kline.open_time.timestamp_millis(), 1700000000000);
+        assert_eq!(kline.close, 37050.0);
+        assert_eq!(kline.number_of_trades, 1500);
+
+        assert!(parse_kline(&serde_json::json!([1700000000000i64, "37000.1"])).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+use chrono::{DateTime, Utc};
+use parquet::file::reader::SerializedFileReader;
+use parquet::record::Field;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::{select_klines, KlinesRequest, MarketDataProviderTrait};
+use crate::objects::{intervals::CryptoInterval, klines::Kline, objects::CryptoSymbolSimple};
+
+// Open times above this value are in microseconds (recent Binance archives)
+const MICROSECONDS_THRESHOLD: i64 = 100_000_000_000_000;
+
+/// Klines read from CSV or Parquet dumps, such as the Binance public data archives (extracted) :
+/// every file of the directory (and its subdirectories) named '{SYMBOL}-{interval}...' is used,
+/// ex: 'BTCUSDT-1h-2024-01.csv', 'BTCUSDT-1h.parquet'
+#[derive(Debug, Clone)]
+pub struct FileProvider {
+    directory: PathBuf,
+    // Klines already read, by symbol and interval
+    loaded: Arc<Mutex<HashMap<String, Arc<Vec<Kline>>>>>,
+}
+
+impl FileProvider {
+    pub fn new(directory: &str) -> Self {
+        FileProvider {
+            directory: PathBuf::from(directory),
+            loaded: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // All the klines of the symbol and interval, sorted by open time
+    fn load_klines(
+        &self,
+        symbol: &str,
+        interval: &CryptoInterval,
+    ) -> Result<Arc<Vec<Kline>>, String> {
+        let prefix = format!("{}-{}", symbol, interval.to_binance_string());
+        if let Some(klines) = self.loaded.lock().unwrap().get(&prefix) {
+            return Ok(klines.clone());
+        }
+
+        let mut klines: Vec<Kline> = Vec::new();
+        for path in self.data_files()? {
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            let matches = file_name
+                .strip_prefix(&prefix)
+                .is_some_and(|rest| rest.starts_with('-') || rest.starts_with('.'));
+            if !matches {
+                continue;
+            }
+
+            println!("Reading klines from {}", path.display());
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("parquet") => klines.extend(read_parquet(&path, interval)?),
+                _ => klines.extend(read_csv(&path, interval)?),
+            }
+        }
+        if klines.is_empty() {
+            return Err(format!(
+                "No klines file for {} in {}",
+                prefix,
+                self.directory.display()
+            ));
+        }
+
+        // Files may overlap (monthly and daily dumps)
+        klines.sort_by_key(|kline| kline.open_time);
+        klines.dedup_by_key(|kline| kline.open_time);
+
+        let klines = Arc::new(klines);
+        self.loaded.lock().unwrap().insert(prefix, klines.clone());
+        Ok(klines)
+    }
+
+    // CSV and Parquet files of the directory and its subdirectories
+    fn data_files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files: Vec<PathBuf> = Vec::new();
+        let mut directories: Vec<PathBuf> = vec![self.directory.clone()];
+        while let Some(directory) = directories.pop() {
+            let entries = fs::read_dir(&directory)
+                .map_err(|e| format!("Failed to read {}: {}", directory.display(), e))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if matches!(
+                    path.extension().and_then(|extension| extension.to_str()),
+                    Some("csv") | Some("parquet")
+                ) {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+impl MarketDataProviderTrait for FileProvider {
+    async fn fetch_klines(&self, request: &KlinesRequest) -> Result<Vec<Kline>, String> {
+        let klines = self.load_klines(&request.symbol, &request.interval)?;
+        Ok(select_klines(&klines, request))
+    }
+
+    // Symbols with at least one file (no volume in the dumps)
+    async fn fetch_symbols(&self) -> Result<Vec<CryptoSymbolSimple>, String> {
+        let mut symbols: Vec<String> = self
+            .data_files()?
+            .iter()
+            .filter_map(|path| {
+                let file_name = path.file_name()?.to_string_lossy().to_string();
+                let (symbol, _) = file_name.split_once('-')?;
+                Some(symbol.to_string())
+            })
+            .collect();
+        symbols.sort();
+        symbols.dedup();
+
+        Ok(symbols
+            .into_iter()
+            .map(|symbol| CryptoSymbolSimple {
+                symbol,
+                volume: 0.0,
+                available: true,
+            })
+            .collect())
+    }
+}
+
+// Time in milliseconds or microseconds
+fn parse_time(time: i64) -> Result<DateTime<Utc>, String> {
+    let millis = if time >= MICROSECONDS_THRESHOLD {
+        time / 1000
+    } else {
+        time
+    };
+    DateTime::<Utc>::from_timestamp_millis(millis).ok_or(format!("Invalid time: {}", time))
+}
+
+// Kline from the columns of the Binance archives, the missing optional columns are left empty
+// open_time, open, high, low, close, volume, [close_time, quote_volume, count, taker_buy_volume, taker_buy_quote_volume]
+fn build_kline(
+    open_time: i64,
+    prices: [f64; 5],
+    close_time: Option<i64>,
+    volumes: [Option<f64>; 3],
+    number_of_trades: Option<i64>,
+    interval: &CryptoInterval,
+) -> Result<Kline, String> {
+    let open_time = parse_time(open_time)?;
+    let close_time = match close_time {
+        Some(close_time) => parse_time(close_time)?,
+        None => {
+            open_time + chrono::Duration::minutes(interval.to_minutes())
+                - chrono::Duration::milliseconds(1)
+        }
+    };
+    Ok(Kline {
+        open_time,
+        open: prices[0],
+        high: prices[1],
+        low: prices[2],
+        close: prices[3],
+        volume: prices[4],
+        close_time,
+        quote_asset_volume: volumes[0].unwrap_or(0.0),
+        number_of_trades: number_of_trades.unwrap_or(0),
+        taker_buy_base_asset_volume: volumes[1].unwrap_or(0.0),
+        taker_buy_quote_asset_volume: volumes[2].unwrap_or(0.0),
+    })
+}
+
+// Klines of a CSV file, with or without a header line
+fn read_csv(path: &Path, interval: &CryptoInterval) -> Result<Vec<Kline>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut klines: Vec<Kline> = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let open_time = match record
+            .get(0)
+            .and_then(|value| value.trim().parse::<i64>().ok())
+        {
+            Some(open_time) => open_time,
+            None if line == 0 => continue, // header
+            None => {
+                return Err(format!(
+                    "Invalid open time in {}, line {}",
+                    path.display(),
+                    line + 1
+                ))
+            }
+        };
+
+        let number =
+            |index: usize| -> Option<f64> { record.get(index)?.trim().parse::<f64>().ok() };
+        let integer =
+            |index: usize| -> Option<i64> { record.get(index)?.trim().parse::<i64>().ok() };
+
+        let mut prices = [0.0; 5];
+        for (i, price) in prices.iter_mut().enumerate() {
+            *price = number(i + 1).ok_or(format!(
+                "Invalid kline in {}, line {}",
+                path.display(),
+                line + 1
+            ))?;
+        }
+        klines.push(build_kline(
+            open_time,
+            prices,
+            integer(6),
+            [number(7), number(9), number(10)],
+            integer(8),
+            interval,
+        )?);
+    }
+
+    Ok(klines)
+}
+
+// Klines of a Parquet file, with the columns named as in the Binance archives or as in the database
+fn read_parquet(path: &Path, interval: &CryptoInterval) -> Result<Vec<Kline>, String> {
+    let reader = SerializedFileReader::try_from(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut klines: Vec<Kline> = Vec::new();
+    for row in reader {
+        let row = row.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let columns: HashMap<&str, &Field> = row
+            .get_column_iter()
+            .map(|(name, field)| (name.as_str(), field))
+            .collect();
+
+        let number = |names: &[&str]| -> Option<f64> {
+            names
+                .iter()
+                .find_map(|name| field_to_f64(columns.get(name)?))
+        };
+        let integer = |names: &[&str]| -> Option<i64> {
+            names
+                .iter()
+                .find_map(|name| field_to_i64(columns.get(name)?))
+        };
+
+        let open_time =
+            integer(&["open_time"]).ok_or(format!("Missing open time in {}", path.display()))?;
+        let mut prices = [0.0; 5];
+        for (price, name) in prices
+            .iter_mut()
+            .zip(["open", "high", "low", "close", "volume"])
+        {
+            *price = number(&[name]).ok_or(format!("Missing {} in {}", name, path.display()))?;
+        }
+        klines.push(build_kline(
+            open_time,
+            prices,
+            integer(&["close_time"]),
+            [
+                number(&["quote_volume", "quote_asset_volume"]),
+                number(&["taker_buy_volume", "taker_buy_base_asset_volume"]),
+                number(&["taker_buy_quote_volume", "taker_buy_quote_asset_volume"]),
+            ],
+            integer(&["count", "number_of_trades"]),
+            interval,
+        )?);
+    }
+
+    Ok(klines)
+}
+
+fn field_to_f64(field: &Field) -> Option<f64> {
+    match field {
+        Field::Double(value) => Some(*value),
+        Field::Float(value) => Some(*value as f64),
+        Field::Str(value) => value.parse::<f64>().ok(),
+        field => field_to_i64(field).map(|value| value as f64),
+    }
+}
+
+fn field_to_i64(field: &Field) -> Option<i64> {
+    match field {
+        Field::Long(value) => Some(*value),
+        Field::Int(value) => Some(*value as i64),
+        Field::ULong(value) => Some(*value as i64),
+        Field::UInt(value) => Some(*value as i64),
+        Field::TimestampMillis(value) => Some(*value),
+        Field::TimestampMicros(value) => Some(*value),
+        Field::Str(value) => value.parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::data_type::{DoubleType, Int64Type};
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("market_data_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    fn request(start_time: Option<u64>, end_time: Option<u64>, limit: usize) -> KlinesRequest {
+        KlinesRequest {
+            symbol: "BTCUSDT".to_string(),
+            interval: CryptoInterval::Int1h,
+            limit,
+            start_time,
+            end_time,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_csv_files() {
+        let directory = test_directory("csv");
+        // Archive with a header, and an overlapping one without (microseconds)
+        fs::write(
+            directory.join("BTCUSDT-1h-2024-01.csv"),
+            "open_time,open,high,low,close,volume,close_time,quote_volume,count,taker_buy_volume,taker_buy_quote_volume,ignore\n\
+             1704067200000,1,2,0.5,1.5,10,1704070799999,15,7,4,6,0\n\
+             1704070800000,1.5,2,1,1.8,12,1704074399999,20,8,5,9,0\n",
+        )
+        .unwrap();
+        fs::create_dir_all(directory.join("daily")).unwrap();
+        fs::write(
+            directory.join("daily").join("BTCUSDT-1h-2024-02-01.csv"),
+            "1704070800000000,1.5,2,1,1.8,12,1704074399999999,20,8,5,9,0\n\
+             1704074400000000,1.8,2.5,1.7,2.4,9,1704077999999999,21,9,4,10,0\n",
+        )
+        .unwrap();
+        fs::write(directory.join("ETHUSDT-1h.csv"), "0,1,1,1,1,1\n").unwrap();
+        fs::write(directory.join("BTCUSDT-12h.csv"), "0,9,9,9,9,9\n").unwrap();
+
+        let provider = FileProvider::new(directory.to_str().unwrap());
+        let klines = provider
+            .fetch_klines(&request(None, None, 10))
+            .await
+            .unwrap();
+        assert_eq!(klines.len(), 3);
+        assert_eq!(klines[2].open_time.timestamp_millis(), 1704074400000);
+        assert_eq!(klines[2].close, 2.4);
+        assert_eq!(klines[1].number_of_trades, 8);
+
+        // Most recent klines up to the end time, or first ones from the start time
+        let klines = provider
+            .fetch_klines(&request(None, Some(1704070800000), 1))
+            .await
+            .unwrap();
+        assert_eq!(klines[0].open_time.timestamp_millis(), 1704070800000);
+        let klines = provider
+            .fetch_klines(&request(Some(1704067200001), None, 1))
+            .await
+            .unwrap();
+        assert_eq!(klines[0].open_time.timestamp_millis(), 1704070800000);
+
+        // Columns missing in the file are completed
+        let klines = provider
+            .fetch_klines(&KlinesRequest {
+                symbol: "ETHUSDT".to_string(),
+                ..request(None, None, 10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(klines[0].close_time.timestamp_millis(), 3599999);
+
+        let symbols = provider.fetch_symbols().await.unwrap();
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].symbol, "BTCUSDT");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_parquet_file() {
+        let directory = test_directory("parquet");
+        let schema = Arc::new(
+            parse_message_type(
+                "message kline {
+                    REQUIRED INT64 open_time;
+                    REQUIRED DOUBLE open;
+                    REQUIRED DOUBLE high;
+                    REQUIRED DOUBLE low;
+                    REQUIRED DOUBLE close;
+                    REQUIRED DOUBLE volume;
+                }",
+            )
+            .unwrap(),
+        );
+        let file = fs::File::create(directory.join("BTCUSDT-1h.parquet")).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, Default::default()).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut column = 0;
+        while let Some(mut column_writer) = row_group.next_column().unwrap() {
+            if column == 0 {
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&[0, 3600000], None, None)
+                    .unwrap();
+            } else {
+                column_writer
+                    .typed::<DoubleType>()
+                    .write_batch(&[column as f64, column as f64 + 0.5], None, None)
+                    .unwrap();
+            }
+            column_writer.close().unwrap();
+            column += 1;
+        }
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let provider = FileProvider::new(directory.to_str().unwrap());
+        let klines = provider
+            .fetch_klines(&request(None, None, 10))
+            .await
+            .unwrap();
+        assert_eq!(klines.len(), 2);
+        assert_eq!(klines[1].open_time.timestamp_millis(), 3600000);
+        assert_eq!(klines[1].close, 4.5);
+        assert_eq!(klines[1].volume, 5.5);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
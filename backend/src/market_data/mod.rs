@@ -0,0 +1,109 @@
+mod binance;
+mod file;
+mod rate_limit;
+
+pub use binance::BinanceProvider;
+pub use file::FileProvider;
+pub use rate_limit::{RateLimiter, RetryPolicy};
+
+use std::env;
+use std::future::Future;
+use std::path::Path;
+
+use crate::objects::{
+    intervals::CryptoInterval, klines::Kline, markets::MarketType, objects::CryptoSymbolSimple,
+};
+
+/// Klines requested from a market data provider, following the Binance API semantics :
+/// at most `limit` klines, the first ones from `start_time` or the last ones up to `end_time`
+/// (open times in milliseconds, bounds included)
+#[derive(Debug, Clone)]
+pub struct KlinesRequest {
+    pub symbol: String,
+    pub interval: CryptoInterval,
+    pub limit: usize,
+    pub start_time: Option<u64>,
+    pub end_time: Option<u64>,
+}
+
+pub trait MarketDataProviderTrait {
+    /// Klines of the request, sorted by open time
+    fn fetch_klines(
+        &self,
+        request: &KlinesRequest,
+    ) -> impl Future<Output = Result<Vec<Kline>, String>> + Send;
+
+    /// Symbols known by the provider, with their volume and availability
+    fn fetch_symbols(&self)
+        -> impl Future<Output = Result<Vec<CryptoSymbolSimple>, String>> + Send;
+}
+
+/// Source of the market data (klines and symbols)
+#[derive(Debug, Clone)]
+pub enum MarketDataProvider {
+    Binance(BinanceProvider),
+    File(FileProvider),
+}
+
+impl MarketDataProvider {
+    // Provider of the market selected by the environment :
+    // MARKET_DATA_PROVIDER=binance (default) or MARKET_DATA_PROVIDER=file with MARKET_DATA_DIR
+    // (the files of each market are in its directory of the Binance archives, ex: MARKET_DATA_DIR/spot)
+    pub fn from_env(market: &MarketType) -> Result<MarketDataProvider, String> {
+        match env::var("MARKET_DATA_PROVIDER").as_deref() {
+            Ok("binance") | Ok("binance_futures") | Err(_) => {
+                Ok(MarketDataProvider::Binance(BinanceProvider::new(market)))
+            }
+            Ok("file") => {
+                let directory = env::var("MARKET_DATA_DIR")
+                    .map_err(|_| "MARKET_DATA_DIR must be set for the file provider".to_string())?;
+                let directory = Path::new(&directory).join(market.to_archive_directory());
+                Ok(MarketDataProvider::File(FileProvider::new(
+                    &directory.to_string_lossy(),
+                )))
+            }
+            Ok(name) => Err(format!("Unknown market data provider: {}", name)),
+        }
+    }
+}
+
+impl MarketDataProviderTrait for MarketDataProvider {
+    async fn fetch_klines(&self, request: &KlinesRequest) -> Result<Vec<Kline>, String> {
+        match self {
+            MarketDataProvider::Binance(provider) => provider.fetch_klines(request).await,
+            MarketDataProvider::File(provider) => provider.fetch_klines(request).await,
+        }
+    }
+
+    async fn fetch_symbols(&self) -> Result<Vec<CryptoSymbolSimple>, String> {
+        match self {
+            MarketDataProvider::Binance(provider) => provider.fetch_symbols().await,
+            MarketDataProvider::File(provider) => provider.fetch_symbols().await,
+        }
+    }
+}
+
+// Klines of the request among klines sorted by open time (for providers holding all the klines)
+pub fn select_klines(klines: &[Kline], request: &KlinesRequest) -> Vec<Kline> {
+    let open_time = |kline: &Kline| kline.open_time.timestamp_millis() as u64;
+
+    let start = match request.start_time {
+        Some(start_time) => klines.partition_point(|kline| open_time(kline) < start_time),
+        None => 0,
+    };
+    let end = match request.end_time {
+        Some(end_time) => klines.partition_point(|kline| open_time(kline) <= end_time),
+        None => klines.len(),
+    };
+    if start >= end {
+        return Vec::new();
+    }
+
+    // Without a start time, the most recent klines before the end time
+    let selected = &klines[start..end];
+    if request.start_time.is_none() && selected.len() > request.limit {
+        selected[selected.len() - request.limit..].to_vec()
+    } else {
+        selected.iter().take(request.limit).cloned().collect()
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+// Share of the weight limit used before waiting for the next minute (the limit is never reached)
+const WEIGHT_SAFETY_RATIO: f64 = 0.8;
+
+/// Retries of the failed requests, with an exponential backoff
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Delay before the retry following the given attempt (0 = first request)
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.min(16) as u32);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[derive(Debug)]
+struct RateLimitState {
+    used_weight: u32,
+    paused_until: Option<Instant>,
+}
+
+/// Request weight budget of an API (per minute), shared by all the requests made with it
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    weight_limit: u32,
+    state: Arc<Mutex<RateLimitState>>,
+}
+
+impl RateLimiter {
+    pub fn new(weight_limit: u32) -> Self {
+        RateLimiter {
+            weight_limit,
+            state: Arc::new(Mutex::new(RateLimitState {
+                used_weight: 0,
+                paused_until: None,
+            })),
+        }
+    }
+
+    /// Wait until a request can be sent
+    pub async fn acquire(&self) {
+        loop {
+            let remaining = self
+                .state
+                .lock()
+                .unwrap()
+                .paused_until
+                .and_then(|paused_until| paused_until.checked_duration_since(Instant::now()));
+            match remaining {
+                Some(remaining) if !remaining.is_zero() => sleep(remaining).await,
+                _ => break,
+            }
+        }
+    }
+
+    /// Weight used in the current minute, as reported by the API (pauses until the next minute when
+    /// it gets close to the limit)
+    pub fn record_used_weight(&self, used_weight: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.used_weight = used_weight;
+        if used_weight as f64 >= self.weight_limit as f64 * WEIGHT_SAFETY_RATIO {
+            println!(
+                "Request weight {} / {}, waiting for the next minute",
+                used_weight, self.weight_limit
+            );
+            Self::pause_state(&mut state, until_next_minute());
+        }
+    }
+
+    /// No request for the given duration (rate limited by the API)
+    pub fn pause(&self, duration: Duration) {
+        Self::pause_state(&mut self.state.lock().unwrap(), duration);
+    }
+
+    pub fn used_weight(&self) -> u32 {
+        self.state.lock().unwrap().used_weight
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .paused_until
+            .is_some_and(|paused_until| paused_until > Instant::now())
+    }
+
+    // The longest pause wins
+    fn pause_state(state: &mut RateLimitState, duration: Duration) {
+        let paused_until = Instant::now() + duration;
+        if state
+            .paused_until
+            .is_none_or(|current| current < paused_until)
+        {
+            state.paused_until = Some(paused_until);
+        }
+    }
+}
+
+// The weights are counted by calendar minute
+pub fn until_next_minute() -> Duration {
+    let millis = chrono::Utc::now().timestamp_millis().rem_euclid(60_000) as u64;
+    Duration::from_millis(60_000 - millis)
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(usize::MAX), Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        let rate_limiter = RateLimiter::new(1000);
+        rate_limiter.record_used_weight(500);
+        assert!(!rate_limiter.is_paused());
+
+        // Close to the limit : paused until the next minute
+        rate_limiter.record_used_weight(850);
+        assert!(rate_limiter.is_paused());
+        assert_eq!(rate_limiter.used_weight(), 850);
+
+        // A shorter pause does not shorten the current one
+        let rate_limiter = RateLimiter::new(1000);
+        rate_limiter.pause(Duration::from_millis(30));
+        rate_limiter.pause(Duration::from_millis(1));
+        let start = Instant::now();
+        rate_limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(25));
+        assert!(!rate_limiter.is_paused());
+    }
+}
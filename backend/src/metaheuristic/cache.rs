@@ -0,0 +1,145 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use super::objects::{Evaluation, Variable};
+
+// Exact representation of a variable (floats compared bit for bit, except for -0.0 == 0.0)
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum VariableKey {
+    Float(u64),
+    Integer(i64),
+    Boolean(bool),
+    Categorical(usize),
+}
+
+fn cache_key(variables: &[Variable]) -> Vec<VariableKey> {
+    variables
+        .iter()
+        .map(|variable| match variable {
+            Variable::Float(value) if *value == 0.0 => VariableKey::Float(0),
+            Variable::Float(value) => VariableKey::Float(value.to_bits()),
+            Variable::Integer(value) => VariableKey::Integer(*value),
+            Variable::Boolean(value) => VariableKey::Boolean(*value),
+            Variable::Categorical(value) => VariableKey::Categorical(*value),
+        })
+        .collect()
+}
+
+/// Evaluations already computed during a run, keyed by the exact variables of the solution
+/// (the evaluation must only depend on the variables)
+#[derive(Debug, Default)]
+pub struct FitnessCache {
+    evaluations: RwLock<HashMap<Vec<VariableKey>, Evaluation>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// Use of the cache during a run
+#[derive(Clone, Debug, Serialize)]
+pub struct CacheStats {
+    pub lookups: usize,
+    pub hits: usize,
+    pub hit_rate: f64,
+}
+
+impl FitnessCache {
+    pub fn new() -> Self {
+        FitnessCache::default()
+    }
+
+    /// Evaluation of the variables, computed with `evaluate` if it is not in the cache yet
+    pub fn get_or_evaluate(
+        &self,
+        variables: &[Variable],
+        evaluate: impl FnOnce() -> Evaluation,
+    ) -> Evaluation {
+        let key = cache_key(variables);
+        if let Some(evaluation) = self.evaluations.read().unwrap().get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return evaluation.clone();
+        }
+
+        // Computed without holding the lock, so that other threads are not blocked
+        // (the same variables may then be evaluated twice at the same time, the result is the same)
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let evaluation = evaluate();
+        self.evaluations
+            .write()
+            .unwrap()
+            .insert(key, evaluation.clone());
+        evaluation
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let lookups = hits + self.misses.load(Ordering::Relaxed);
+        CacheStats {
+            lookups,
+            hits,
+            hit_rate: if lookups > 0 {
+                hits as f64 / lookups as f64
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_cache_hits() {
+        let cache = FitnessCache::new();
+        let computed = AtomicUsize::new(0);
+        let evaluate = |variables: &[Variable]| {
+            cache.get_or_evaluate(variables, || {
+                computed.fetch_add(1, Ordering::Relaxed);
+                Evaluation::new(vec![1.0])
+            })
+        };
+
+        evaluate(&[Variable::Integer(14), Variable::Float(0.0)]);
+        evaluate(&[Variable::Integer(14), Variable::Float(-0.0)]);
+        evaluate(&[Variable::Integer(14), Variable::Float(0.5)]);
+        evaluate(&[Variable::Integer(14), Variable::Float(0.5)]);
+        evaluate(&[Variable::Boolean(true), Variable::Float(0.5)]);
+
+        assert_eq!(computed.load(Ordering::Relaxed), 3);
+        let stats = cache.stats();
+        assert_eq!(stats.lookups, 5);
+        assert_eq!(stats.hits, 2);
+        assert!((stats.hit_rate - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let cache = FitnessCache::new();
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..10 {
+                        cache.get_or_evaluate(&[Variable::Integer(i)], || {
+                            Evaluation::new(vec![i as f64])
+                        });
+                    }
+                });
+            }
+        });
+
+        let stats = cache.stats();
+        assert_eq!(stats.lookups, 40);
+        assert_eq!(
+            cache
+                .get_or_evaluate(&[Variable::Integer(3)], || Evaluation::new(vec![0.0]))
+                .objectives,
+            vec![3.0]
+        );
+    }
+}
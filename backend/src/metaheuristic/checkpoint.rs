@@ -0,0 +1,87 @@
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+use super::objects::{Solution, Variable};
+use super::termination::TerminationState;
+
+/// State of a run, saved periodically so that an interrupted run can be resumed
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub iteration: usize,         // generations (or iterations) already done
+    pub solutions: Vec<Solution>, // population (NSGA-II) or archive (descent)
+    pub state: CheckpointState,
+    #[serde(default)]
+    pub evaluations: usize,
+    #[serde(default)]
+    pub reference_point: Vec<f64>, // hypervolume reference point of the run
+    #[serde(default)]
+    pub termination: TerminationState,
+}
+
+/// State specific to each algorithm (a run only resumes from its own kind)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "algorithm")]
+pub enum CheckpointState {
+    NSGAII {
+        rng: ChaCha8Rng,
+    },
+    IslandNSGAII {
+        island_rngs: Vec<ChaCha8Rng>, // generator of each island
+    },
+    Descent {
+        rng: ChaCha8Rng,
+        current: Solution,
+        iterations_without_improvement: usize,
+        step_size: f64,
+        successes: usize, // successful moves since the last step size adaptation
+        restarts: usize,  // restarts already done
+        tabu_list: Vec<Vec<Variable>>, // recently visited solutions
+    },
+}
+
+/// How often to save the state of a run, where to save it, and the state to resume from
+#[derive(Clone)]
+pub struct Checkpointing {
+    pub interval: usize,
+    pub resume_from: Option<Checkpoint>,
+    pub save: Arc<dyn Fn(&Checkpoint) + Send + Sync>,
+}
+
+impl Checkpointing {
+    pub fn new(
+        interval: usize,
+        resume_from: Option<Checkpoint>,
+        save: Arc<dyn Fn(&Checkpoint) + Send + Sync>,
+    ) -> Self {
+        Checkpointing {
+            interval: interval.max(1),
+            resume_from,
+            save,
+        }
+    }
+
+    /// Save the checkpoint built by `build` if one is due at this iteration
+    pub fn save_if_due(&self, iteration: usize, build: impl FnOnce() -> Checkpoint) {
+        if iteration.checked_rem(self.interval) == Some(0) {
+            (self.save)(&build());
+        }
+    }
+}
+
+impl fmt::Debug for Checkpointing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpointing")
+            .field("interval", &self.interval)
+            .field(
+                "resume_from",
+                &self
+                    .resume_from
+                    .as_ref()
+                    .map(|checkpoint| checkpoint.iteration),
+            )
+            .finish()
+    }
+}
@@ -0,0 +1,186 @@
+use super::objects::{Variable, VariableDefinition};
+
+/// Constraint between several variables of the optimization problem
+/// (indices refer to positions in the variables vector)
+#[derive(Clone, Debug, PartialEq)]
+pub enum VariableConstraint {
+    // The variables must be in ascending order
+    // (strictly if they are all integers, ex: MACD short period < long period)
+    Ascending(Vec<usize>),
+}
+
+impl VariableConstraint {
+    // Same constraint with the indices shifted by the given offset
+    pub fn offset(&self, offset: usize) -> VariableConstraint {
+        match self {
+            VariableConstraint::Ascending(indices) => {
+                VariableConstraint::Ascending(indices.iter().map(|i| i + offset).collect())
+            }
+        }
+    }
+
+    pub fn is_satisfied(&self, variables: &[Variable]) -> bool {
+        match self {
+            VariableConstraint::Ascending(indices) => {
+                let strict = all_integers(variables, indices);
+                indices.windows(2).all(|pair| {
+                    match (
+                        numeric_value(&variables[pair[0]]),
+                        numeric_value(&variables[pair[1]]),
+                    ) {
+                        (Some(a), Some(b)) if strict => a < b,
+                        (Some(a), Some(b)) => a <= b,
+                        _ => true,
+                    }
+                })
+            }
+        }
+    }
+
+    // Modify the variables so that the constraint is satisfied (if the bounds allow it)
+    pub fn repair(&self, variables: &mut [Variable], definitions: &[VariableDefinition]) {
+        if self.is_satisfied(variables) {
+            return;
+        }
+
+        match self {
+            VariableConstraint::Ascending(indices) => {
+                let n = indices.len();
+                let strict = all_integers(variables, indices);
+                let step = if strict { 1.0 } else { 0.0 };
+
+                // Sort the values, then push them inside the bounds of their new position
+                let mut values: Vec<f64> = indices
+                    .iter()
+                    .filter_map(|&i| numeric_value(&variables[i]))
+                    .collect();
+                if values.len() != n {
+                    return;
+                }
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+                let bounds: Vec<(f64, f64)> = indices
+                    .iter()
+                    .map(|&i| numeric_bounds(&definitions[i]))
+                    .collect();
+
+                // Forward pass : each value is above the previous one and above its minimum
+                for k in 0..n {
+                    values[k] = values[k].max(bounds[k].0);
+                    if k > 0 {
+                        values[k] = values[k].max(values[k - 1] + step);
+                    }
+                }
+
+                // Backward pass : each value is below the next one and below its maximum
+                for k in (0..n).rev() {
+                    values[k] = values[k].min(bounds[k].1);
+                    if k < n - 1 {
+                        values[k] = values[k].min(values[k + 1] - step);
+                    }
+                    values[k] = values[k].max(bounds[k].0);
+                }
+
+                for (k, &i) in indices.iter().enumerate() {
+                    variables[i] = match variables[i] {
+                        Variable::Integer(_) => Variable::Integer(values[k].round() as i64),
+                        _ => Variable::Float(definitions[i].quantize(values[k])),
+                    };
+                }
+            }
+        }
+    }
+}
+
+// Repair the variables so that they satisfy all the constraints
+pub fn repair_variables(
+    variables: &mut [Variable],
+    definitions: &[VariableDefinition],
+    constraints: &[VariableConstraint],
+) {
+    for constraint in constraints {
+        constraint.repair(variables, definitions);
+    }
+}
+
+fn all_integers(variables: &[Variable], indices: &[usize]) -> bool {
+    indices
+        .iter()
+        .all(|&i| matches!(variables[i], Variable::Integer(_)))
+}
+
+fn numeric_value(variable: &Variable) -> Option<f64> {
+    match variable {
+        Variable::Float(value) => Some(*value),
+        Variable::Integer(value) => Some(*value as f64),
+        Variable::Boolean(_) | Variable::Categorical(_) => None,
+    }
+}
+
+fn numeric_bounds(definition: &VariableDefinition) -> (f64, f64) {
+    match definition {
+        VariableDefinition::Float(min, max) => (*min, *max),
+        VariableDefinition::Integer(min, max) => (*min as f64, *max as f64),
+        VariableDefinition::SteppedFloat(min, max, _) => (*min, *max),
+        VariableDefinition::Boolean | VariableDefinition::Categorical(_) => {
+            (f64::NEG_INFINITY, f64::INFINITY)
+        }
+    }
+}
+
+//// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascending_repair_integers() {
+        let definitions = vec![
+            VariableDefinition::Integer(1, 40),
+            VariableDefinition::Integer(1, 40),
+            VariableDefinition::Integer(1, 40),
+        ];
+        let constraint = VariableConstraint::Ascending(vec![0, 1, 2]);
+
+        let mut variables = vec![
+            Variable::Integer(26),
+            Variable::Integer(9),
+            Variable::Integer(9),
+        ];
+        assert!(!constraint.is_satisfied(&variables));
+        constraint.repair(&mut variables, &definitions);
+        assert!(constraint.is_satisfied(&variables));
+
+        let mut variables = vec![
+            Variable::Integer(40),
+            Variable::Integer(40),
+            Variable::Integer(40),
+        ];
+        constraint.repair(&mut variables, &definitions);
+        assert!(constraint.is_satisfied(&variables));
+        assert!(matches!(variables[2], Variable::Integer(40)));
+        assert!(matches!(variables[1], Variable::Integer(39)));
+        assert!(matches!(variables[0], Variable::Integer(38)));
+    }
+
+    #[test]
+    fn test_ascending_repair_floats_with_offset() {
+        let definitions = vec![
+            VariableDefinition::Boolean,
+            VariableDefinition::Float(0.0, 1.0),
+            VariableDefinition::Float(0.0, 1.0),
+        ];
+        let constraint = VariableConstraint::Ascending(vec![0, 1]).offset(1);
+        assert_eq!(constraint, VariableConstraint::Ascending(vec![1, 2]));
+
+        let mut variables = vec![
+            Variable::Boolean(true),
+            Variable::Float(0.618),
+            Variable::Float(0.382),
+        ];
+        repair_variables(&mut variables, &definitions, &[constraint.clone()]);
+        assert!(constraint.is_satisfied(&variables));
+        assert!(matches!(variables[1], Variable::Float(v) if v == 0.382));
+        assert!(matches!(variables[2], Variable::Float(v) if v == 0.618));
+    }
+}
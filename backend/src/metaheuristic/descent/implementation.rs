@@ -1,13 +1,30 @@
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 
+use crate::metaheuristic::checkpoint::{Checkpoint, CheckpointState, Checkpointing};
+use crate::metaheuristic::constraints::{repair_variables, VariableConstraint};
+use crate::metaheuristic::hypervolume::{reference_point, solutions_hypervolume};
 use crate::metaheuristic::objects::{
-    MetaheuristicInfo, MetaheuristicTrait, Solution, Variable, VariableDefinition,
-    VariableDefinitionInfo,
+    fit_solutions_variables, parse_seed, random_seed, seed_info, Evaluation, MetaheuristicInfo,
+    MetaheuristicTrait, Solution, Variable, VariableDefinition, VariableDefinitionInfo,
+};
+use crate::metaheuristic::progress::{Progress, ProgressReporter};
+use crate::metaheuristic::termination::{
+    parse_optional, Termination, TerminationState, DEFAULT_MAX_GENERATIONS,
 };
 use crate::objects::{indicators::Indicator, klines::KlineCollection};
 
+const MAX_ITERATIONS_WITHOUT_IMPROVEMENT_DEFAULT: usize = 1000;
+const ADAPTATION_WINDOW: usize = 10; // iterations between two step size adaptations
+const TARGET_SUCCESS_RATE: f64 = 0.2; // 1/5th success rule
+const STEP_SIZE_INCREASE: f64 = 1.5;
+const STEP_SIZE_DECREASE: f64 = 0.82; // ~ 1.5^(-1/2), the step size shrinks faster than it grows
+const RESTART_CANDIDATES: usize = 10; // random points among which a restart point is chosen
+const TABU_ATTEMPTS: usize = 10; // neighbors drawn before accepting a tabu one
+
 #[derive(Clone, Debug)]
 pub struct MultiObjectiveDescent {
     step_size: f64,
@@ -15,6 +32,15 @@ pub struct MultiObjectiveDescent {
     max_iterations_without_improvement: usize,
     archive_size: usize,
     num_objectives: usize,
+    variable_constraints: Vec<VariableConstraint>,
+    seed: u64,
+    checkpointing: Option<Checkpointing>,
+    progress_reporter: Option<ProgressReporter>,
+    termination: Termination,
+    initial_solutions: Vec<Vec<Variable>>, // variables of solutions from a previous run
+    adaptive_step: bool,                   // adapt the step size with the 1/5th success rule
+    restarts: usize,                       // restarts from a distant point when the search stalls
+    tabu_size: usize,                      // recently visited solutions that are not visited again
 }
 
 impl MultiObjectiveDescent {
@@ -31,6 +57,15 @@ impl MultiObjectiveDescent {
             max_iterations_without_improvement,
             archive_size,
             num_objectives,
+            variable_constraints: Vec::new(),
+            seed: random_seed(),
+            checkpointing: None,
+            progress_reporter: None,
+            termination: Termination::max_generations(DEFAULT_MAX_GENERATIONS),
+            initial_solutions: Vec::new(),
+            adaptive_step: false,
+            restarts: 0,
+            tabu_size: 0,
         }
     }
 
@@ -54,16 +89,100 @@ impl MultiObjectiveDescent {
         if !step_size.is_ok() || !archive_size.is_ok() {
             return Err("Invalid parameters for the algorithm".to_string());
         }
+        let seed = parse_seed(json)?;
+        let termination = Termination::from_json(json)?;
+        let max_iterations_without_improvement =
+            parse_optional(json, "max_iterations_without_improvement")?
+                .unwrap_or(MAX_ITERATIONS_WITHOUT_IMPROVEMENT_DEFAULT);
 
         Ok(MultiObjectiveDescent {
             step_size: step_size.unwrap(),
             variable_definitions: variable_definitions,
-            max_iterations_without_improvement: 1000,
+            max_iterations_without_improvement,
             archive_size: archive_size.unwrap(),
             num_objectives: num_objectives,
+            variable_constraints: Vec::new(),
+            seed,
+            checkpointing: None,
+            progress_reporter: None,
+            termination,
+            initial_solutions: Vec::new(),
+            adaptive_step: parse_optional(json, "adaptive_step")?.unwrap_or(false),
+            restarts: parse_optional(json, "restarts")?.unwrap_or(0),
+            tabu_size: parse_optional(json, "tabu_size")?.unwrap_or(0),
         })
     }
 
+    /// Set the constraints between variables that every generated solution must satisfy
+    pub fn with_variable_constraints(
+        mut self,
+        variable_constraints: Vec<VariableConstraint>,
+    ) -> Self {
+        self.variable_constraints = variable_constraints;
+        self
+    }
+
+    /// Set the seed of the random number generator (same seed and data, same run)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Save the state of the run periodically (and resume from a previous state, if any)
+    pub fn with_checkpointing(mut self, checkpointing: Checkpointing) -> Self {
+        if let Some(checkpoint) = &checkpointing.resume_from {
+            self.seed = checkpoint.seed;
+        }
+        self.checkpointing = Some(checkpointing);
+        self
+    }
+
+    /// Set the conditions ending the run
+    pub fn with_termination(mut self, termination: Termination) -> Self {
+        self.termination = termination;
+        self
+    }
+
+    /// Report the progress of the run after each iteration
+    pub fn with_progress_reporter(mut self, progress_reporter: ProgressReporter) -> Self {
+        self.progress_reporter = Some(progress_reporter);
+        self
+    }
+
+    /// Adapt the step size with the 1/5th success rule, restart when the search stalls,
+    /// and avoid the last visited solutions
+    pub fn with_search_options(
+        mut self,
+        adaptive_step: bool,
+        restarts: usize,
+        tabu_size: usize,
+    ) -> Self {
+        self.adaptive_step = adaptive_step;
+        self.restarts = restarts;
+        self.tabu_size = tabu_size;
+        self
+    }
+
+    /// Start the archive from the variables of previous solutions (warm start),
+    /// the solutions that do not fit the variable definitions are ignored
+    pub fn with_initial_solutions(mut self, initial_solutions: Vec<Vec<Variable>>) -> Self {
+        self.initial_solutions =
+            fit_solutions_variables(&initial_solutions, &self.variable_definitions);
+        self
+    }
+
+    fn repair(&self, variables: &mut [Variable]) {
+        repair_variables(
+            variables,
+            &self.variable_definitions,
+            &self.variable_constraints,
+        );
+    }
+
     pub fn get_info() -> MetaheuristicInfo {
         MetaheuristicInfo {
             name: "Multi-Objective Simple Descent".to_string(),
@@ -74,26 +193,70 @@ impl MultiObjectiveDescent {
                     description: "Initial step size for variable perturbation".to_string(),
                     variable_type: "float".to_string(),
                     bounds: Some((0.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
                 },
                 VariableDefinitionInfo {
                     name: "archive_size".to_string(),
                     description: "Maximum size of the non-dominated solutions archive".to_string(),
                     variable_type: "integer".to_string(),
                     bounds: Some((1.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
                 },
                 VariableDefinitionInfo {
                     name: "num_objectives".to_string(),
                     description: "Number of objectives to optimize".to_string(),
                     variable_type: "integer".to_string(),
                     bounds: Some((2.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
                 },
-            ],
+                VariableDefinitionInfo {
+                    name: "max_iterations_without_improvement".to_string(),
+                    description: format!(
+                        "Stop after this many iterations without archive improvement (optional, {} by default)",
+                        MAX_ITERATIONS_WITHOUT_IMPROVEMENT_DEFAULT
+                    ),
+                    variable_type: "integer".to_string(),
+                    bounds: Some((1.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
+                },
+                VariableDefinitionInfo {
+                    name: "adaptive_step".to_string(),
+                    description: "Adapt the step size with the 1/5th success rule (optional, false by default)".to_string(),
+                    variable_type: "boolean".to_string(),
+                    bounds: None,
+                    step: None,
+                    choices: None,
+                },
+                VariableDefinitionInfo {
+                    name: "restarts".to_string(),
+                    description: "Restarts from a point far from the archive when the search stalls (optional, 0 by default)".to_string(),
+                    variable_type: "integer".to_string(),
+                    bounds: Some((0.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
+                },
+                VariableDefinitionInfo {
+                    name: "tabu_size".to_string(),
+                    description: "Number of recently visited solutions that are not visited again (optional, 0 by default)".to_string(),
+                    variable_type: "integer".to_string(),
+                    bounds: Some((0.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
+                },
+                seed_info(),
+            ]
+            .into_iter()
+            .chain(Termination::get_info())
+            .collect(),
         }
     }
 
-    fn initialize_solution(&self) -> Solution {
-        let mut rng = rand::thread_rng();
-        let variables: Vec<Variable> = self
+    fn initialize_solution(&self, rng: &mut impl Rng) -> Solution {
+        let mut variables: Vec<Variable> = self
             .variable_definitions
             .iter()
             .map(|def| match def {
@@ -102,14 +265,83 @@ impl MultiObjectiveDescent {
                     Variable::Integer(rng.gen_range(*min..=*max))
                 }
                 VariableDefinition::Boolean => Variable::Boolean(rng.gen_bool(0.5)),
+                VariableDefinition::Categorical(choices) => {
+                    Variable::Categorical(rng.gen_range(0..choices.len().max(1)))
+                }
+                VariableDefinition::SteppedFloat(min, _, step) => {
+                    Variable::Float(min + rng.gen_range(0..=def.get_steps_count()) as f64 * step)
+                }
             })
             .collect();
+        self.repair(&mut variables);
 
         Solution::new(variables, vec![0.0; self.num_objectives])
     }
 
-    fn generate_neighbor(&self, current: &Solution) -> Solution {
-        let mut rng = rand::thread_rng();
+    /// Random point far from the archive : the farthest of a few random candidates
+    fn initialize_distant_solution(&self, archive: &[Solution], rng: &mut impl Rng) -> Solution {
+        let candidates: Vec<Solution> = (0..RESTART_CANDIDATES)
+            .map(|_| self.initialize_solution(rng))
+            .collect();
+
+        candidates
+            .into_iter()
+            .map(|candidate| {
+                let distance = archive
+                    .iter()
+                    .map(|solution| self.distance(&candidate.variables, &solution.variables))
+                    .fold(f64::INFINITY, f64::min);
+                (candidate, distance)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(candidate, _)| candidate)
+            .unwrap()
+    }
+
+    /// Distance between two solutions, each variable being normalised by its range
+    fn distance(&self, a: &[Variable], b: &[Variable]) -> f64 {
+        self.variable_definitions
+            .iter()
+            .zip(a.iter().zip(b))
+            .map(|(definition, pair)| {
+                let difference = match (definition, pair) {
+                    (
+                        VariableDefinition::Float(min, max)
+                        | VariableDefinition::SteppedFloat(min, max, _),
+                        (Variable::Float(x), Variable::Float(y)),
+                    ) if max > min => (x - y) / (max - min),
+                    (
+                        VariableDefinition::Integer(min, max),
+                        (Variable::Integer(x), Variable::Integer(y)),
+                    ) if max > min => (x - y) as f64 / (max - min) as f64,
+                    (_, (x, y)) if x != y => 1.0,
+                    _ => 0.0,
+                };
+                difference * difference
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Largest useful step size : the widest range of the variables
+    fn max_step_size(&self) -> f64 {
+        self.variable_definitions
+            .iter()
+            .map(|definition| match definition {
+                VariableDefinition::Float(min, max)
+                | VariableDefinition::SteppedFloat(min, max, _) => max - min,
+                VariableDefinition::Integer(min, max) => (max - min) as f64,
+                _ => 0.0,
+            })
+            .fold(self.step_size, f64::max)
+    }
+
+    pub(crate) fn generate_neighbor(
+        &self,
+        current: &Solution,
+        step_size: f64,
+        rng: &mut impl Rng,
+    ) -> Solution {
         let mut neighbor = current.clone();
 
         // Randomly select one variable to modify
@@ -120,12 +352,12 @@ impl MultiObjectiveDescent {
             &current.variables[var_idx],
         ) {
             (VariableDefinition::Float(min, max), Variable::Float(val)) => {
-                let perturbation = (rng.gen::<f64>() * 2.0 - 1.0) * self.step_size;
+                let perturbation = (rng.gen::<f64>() * 2.0 - 1.0) * step_size;
                 let new_val = (val + perturbation).clamp(*min, *max);
                 neighbor.variables[var_idx] = Variable::Float(new_val);
             }
             (VariableDefinition::Integer(min, max), Variable::Integer(val)) => {
-                let int_step = (self.step_size.round() as i64).max(1);
+                let int_step = (step_size.round() as i64).max(1);
                 let perturbation = rng.gen_range(-int_step..=int_step);
                 let new_val = (*val + perturbation).clamp(*min, *max);
                 neighbor.variables[var_idx] = Variable::Integer(new_val);
@@ -133,30 +365,41 @@ impl MultiObjectiveDescent {
             (VariableDefinition::Boolean, Variable::Boolean(val)) => {
                 neighbor.variables[var_idx] = Variable::Boolean(!val);
             }
+            (VariableDefinition::Categorical(choices), Variable::Categorical(val)) => {
+                if choices.len() > 1 {
+                    let shift = rng.gen_range(1..choices.len());
+                    neighbor.variables[var_idx] =
+                        Variable::Categorical((val + shift) % choices.len());
+                }
+            }
+            (definition @ VariableDefinition::SteppedFloat(_, _, step), Variable::Float(val)) => {
+                // Move by a whole number of steps
+                let steps = ((step_size / step).round() as i64).max(1);
+                let perturbation = rng.gen_range(-steps..=steps) as f64 * step;
+                neighbor.variables[var_idx] =
+                    Variable::Float(definition.quantize(val + perturbation));
+            }
             _ => panic!("Variable type mismatch"),
         }
+        self.repair(&mut neighbor.variables);
 
         neighbor
     }
 
+    // Constrained-domination, infeasible solutions never dominate feasible ones
     fn dominates(&self, solution1: &Solution, solution2: &Solution) -> bool {
-        let mut one_is_better = false;
-
-        for i in 0..self.num_objectives {
-            if solution1.objectives[i] > solution2.objectives[i] {
-                return false;
-            }
-            if solution1.objectives[i] < solution2.objectives[i] {
-                one_is_better = true;
-            }
-        }
-
-        one_is_better
+        solution1.constrained_dominates(solution2, self.num_objectives)
     }
 
-    fn update_archive(&self, archive: &mut Vec<Solution>, new_solution: Solution) {
+    /// Add the solution to the archive if no archive solution dominates it (returns whether it was added)
+    pub(crate) fn update_archive(
+        &self,
+        archive: &mut Vec<Solution>,
+        new_solution: Solution,
+    ) -> bool {
         // Check if the new solution is dominated by any archive solution
-        if !archive.iter().any(|sol| self.dominates(sol, &new_solution)) {
+        let added = !archive.iter().any(|sol| self.dominates(sol, &new_solution));
+        if added {
             // Remove solutions that are dominated by the new solution
             archive.retain(|sol| !self.dominates(&new_solution, sol));
 
@@ -168,6 +411,17 @@ impl MultiObjectiveDescent {
                 self.trim_archive(archive);
             }
         }
+        added
+    }
+
+    // Add the solution to the archive and return whether it is still there once the archive is
+    // trimmed (a successful move of the 1/5th success rule)
+    fn archive_keeps(&self, archive: &mut Vec<Solution>, new_solution: Solution) -> bool {
+        let variables = new_solution.variables.clone();
+        self.update_archive(archive, new_solution)
+            && archive
+                .iter()
+                .any(|solution| solution.variables == variables)
     }
 
     fn calculate_crowding_distance(&self, solutions: &mut Vec<Solution>) {
@@ -226,7 +480,6 @@ impl MultiObjectiveDescent {
 
     pub fn run<F>(
         &self,
-        max_iterations: usize,
         evaluate: F,
         kline_collections: &Vec<KlineCollection>,
         indicators: &Vec<Indicator>,
@@ -238,39 +491,167 @@ impl MultiObjectiveDescent {
             &Vec<KlineCollection>,
             &Vec<Indicator>,
             &Vec<Vec<VariableDefinition>>,
-        ) -> Vec<f64>,
+        ) -> Evaluation,
     {
-        let mut rng = rand::thread_rng();
-        let mut archive: Vec<Solution> = Vec::new();
+        let resume_from = self
+            .checkpointing
+            .as_ref()
+            .and_then(|checkpointing| checkpointing.resume_from.clone());
+
+        // State of the search options (step size adaptation, restarts and tabu list)
+        let (mut step_size, mut successes, mut restarts, mut tabu_list) = match &resume_from {
+            Some(Checkpoint {
+                state:
+                    CheckpointState::Descent {
+                        step_size,
+                        successes,
+                        restarts,
+                        tabu_list,
+                        ..
+                    },
+                ..
+            }) => (
+                *step_size,
+                *successes,
+                *restarts,
+                tabu_list.iter().cloned().collect::<VecDeque<_>>(),
+            ),
+            _ => (self.step_size, 0, 0, VecDeque::new()),
+        };
+        let max_step_size = self.max_step_size();
+
+        let (
+            mut rng,
+            mut archive,
+            mut current,
+            mut iterations_without_improvement,
+            first_iteration,
+            mut evaluations,
+            mut hv_reference,
+            termination_state,
+        ) = match resume_from {
+            Some(Checkpoint {
+                state:
+                    CheckpointState::Descent {
+                        rng,
+                        current,
+                        iterations_without_improvement,
+                        ..
+                    },
+                solutions,
+                iteration,
+                evaluations,
+                reference_point,
+                termination,
+                ..
+            }) => (
+                rng,
+                solutions,
+                current,
+                iterations_without_improvement,
+                iteration,
+                evaluations,
+                reference_point,
+                termination,
+            ),
+            _ => {
+                let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+                let mut archive: Vec<Solution> = Vec::new();
+
+                // Initialize first solution (the first initial solution, if any)
+                let mut current = match self.initial_solutions.first() {
+                    Some(variables) => {
+                        let mut variables = variables.clone();
+                        self.repair(&mut variables);
+                        Solution::new(variables, vec![0.0; self.num_objectives])
+                    }
+                    None => self.initialize_solution(&mut rng),
+                };
+                current.apply_evaluation(evaluate(
+                    &current.variables,
+                    kline_collections,
+                    indicators,
+                    variable_definitions_sep,
+                ));
+                self.update_archive(&mut archive, current.clone());
+
+                // The other initial solutions go straight to the archive
+                for variables in self.initial_solutions.iter().skip(1) {
+                    let mut variables = variables.clone();
+                    self.repair(&mut variables);
+                    let mut solution = Solution::new(variables, vec![0.0; self.num_objectives]);
+                    solution.apply_evaluation(evaluate(
+                        &solution.variables,
+                        kline_collections,
+                        indicators,
+                        variable_definitions_sep,
+                    ));
+                    self.update_archive(&mut archive, solution);
+                }
+                let evaluations = self.initial_solutions.len().max(1);
+
+                (
+                    rng,
+                    archive,
+                    current,
+                    0,
+                    0,
+                    evaluations,
+                    Vec::new(),
+                    TerminationState::default(),
+                )
+            }
+        };
 
-        // Initialize first solution
-        let mut current = self.initialize_solution();
-        current.objectives = evaluate(
-            &current.variables,
-            kline_collections,
-            indicators,
-            variable_definitions_sep,
-        );
-        self.update_archive(&mut archive, current.clone());
+        // Hypervolume reference point, fixed for the whole run
+        if hv_reference.is_empty() {
+            hv_reference = reference_point(&archive, self.num_objectives);
+        }
+        let mut termination_state = termination_state.started_at(first_iteration, evaluations);
+
+        // A resumed run may have already met its termination criteria
+        let mut iteration = first_iteration;
+        let mut stop = iteration > 0
+            && self.termination.should_stop(
+                &mut termination_state,
+                iteration,
+                evaluations,
+                solutions_hypervolume(&archive, &hv_reference),
+            );
 
-        let mut iterations_without_improvement = 0;
+        while !stop {
+            // Generate a neighbor that is not in the tabu list (a tabu one after a few attempts)
+            let mut neighbor = self.generate_neighbor(&current, step_size, &mut rng);
+            for _ in 1..TABU_ATTEMPTS {
+                if !tabu_list.contains(&neighbor.variables) {
+                    break;
+                }
+                neighbor = self.generate_neighbor(&current, step_size, &mut rng);
+            }
 
-        for _ in 0..max_iterations {
-            // Generate and evaluate neighbor
-            let mut neighbor = self.generate_neighbor(&current);
-            neighbor.objectives = evaluate(
+            // Evaluate neighbor
+            neighbor.apply_evaluation(evaluate(
                 &neighbor.variables,
                 kline_collections,
                 indicators,
                 variable_definitions_sep,
-            );
+            ));
+            evaluations += 1;
 
             // Update archive and check for improvement
             let archive_size_before = archive.len();
-            self.update_archive(&mut archive, neighbor.clone());
+            if self.archive_keeps(&mut archive, neighbor.clone()) {
+                successes += 1;
+            }
 
             // Update current solution if neighbor is non-dominated
             if !self.dominates(&current, &neighbor) {
+                if self.tabu_size > 0 {
+                    tabu_list.push_back(current.variables.clone());
+                    if tabu_list.len() > self.tabu_size {
+                        tabu_list.pop_front();
+                    }
+                }
                 current = neighbor;
 
                 // Check if archive improved
@@ -283,9 +664,85 @@ impl MultiObjectiveDescent {
                 iterations_without_improvement += 1;
             }
 
-            // Early stopping if no improvement for too long
-            if iterations_without_improvement >= self.max_iterations_without_improvement {
-                break;
+            iteration += 1;
+
+            // 1/5th success rule : larger steps while moves often succeed, smaller ones otherwise
+            if self.adaptive_step && iteration.checked_rem(ADAPTATION_WINDOW) == Some(0) {
+                let success_rate = successes as f64 / ADAPTATION_WINDOW as f64;
+                if success_rate > TARGET_SUCCESS_RATE {
+                    step_size = (step_size * STEP_SIZE_INCREASE).min(max_step_size);
+                } else if success_rate < TARGET_SUCCESS_RATE {
+                    step_size = (step_size * STEP_SIZE_DECREASE).max(f64::EPSILON);
+                }
+                successes = 0;
+            }
+
+            let progress = Progress::new(
+                iteration,
+                self.termination.max_generations,
+                evaluations,
+                &archive,
+                &hv_reference,
+            );
+            stop = self.termination.should_stop(
+                &mut termination_state,
+                iteration,
+                evaluations,
+                progress.hypervolume,
+            );
+
+            // No improvement for too long : restart from a point far from the archive, or stop
+            // (the last iteration is still saved and reported)
+            if !stop && iterations_without_improvement >= self.max_iterations_without_improvement {
+                if restarts >= self.restarts {
+                    stop = true;
+                } else {
+                    restarts += 1;
+
+                    current = self.initialize_distant_solution(&archive, &mut rng);
+                    current.apply_evaluation(evaluate(
+                        &current.variables,
+                        kline_collections,
+                        indicators,
+                        variable_definitions_sep,
+                    ));
+                    evaluations += 1;
+                    self.update_archive(&mut archive, current.clone());
+
+                    iterations_without_improvement = 0;
+                    step_size = self.step_size;
+                    successes = 0;
+                }
+            }
+
+            if let Some(checkpointing) = &self.checkpointing {
+                checkpointing.save_if_due(iteration, || Checkpoint {
+                    seed: self.seed,
+                    iteration,
+                    solutions: archive.clone(),
+                    state: CheckpointState::Descent {
+                        rng: rng.clone(),
+                        current: current.clone(),
+                        iterations_without_improvement,
+                        step_size,
+                        successes,
+                        restarts,
+                        tabu_list: tabu_list.iter().cloned().collect(),
+                    },
+                    evaluations,
+                    reference_point: hv_reference.clone(),
+                    termination: termination_state.snapshot(),
+                });
+            }
+
+            if let Some(progress_reporter) = &self.progress_reporter {
+                progress_reporter.report(
+                    &progress.with_timing(
+                        termination_state.elapsed_seconds(),
+                        self.termination
+                            .eta_seconds(&termination_state, iteration, evaluations),
+                    ),
+                );
             }
         }
 
@@ -297,13 +754,12 @@ impl MultiObjectiveDescent {
 impl MetaheuristicTrait for MultiObjectiveDescent {
     fn run(
         &self,
-        num_generations: usize,
         evaluate: impl Fn(
                 &[Variable],
                 &Vec<KlineCollection>,
                 &Vec<Indicator>,
                 &Vec<Vec<VariableDefinition>>,
-            ) -> Vec<f64>
+            ) -> Evaluation
             + Clone
             + Sync
             + Send,
@@ -312,7 +768,6 @@ impl MetaheuristicTrait for MultiObjectiveDescent {
         variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
     ) -> Vec<Solution> {
         self.run(
-            num_generations,
             evaluate,
             kline_collections,
             indicators,
@@ -320,3 +775,177 @@ impl MetaheuristicTrait for MultiObjectiveDescent {
         )
     }
 }
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metaheuristic::test_utils::evaluate;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let algorithm = MultiObjectiveDescent::new(
+            0.5,
+            vec![
+                VariableDefinition::Float(-5.0, 5.0),
+                VariableDefinition::SteppedFloat(0.0, 2.0, 0.25),
+            ],
+            100,
+            10,
+            2,
+        )
+        .with_seed(7)
+        .with_termination(Termination::max_generations(200));
+
+        let first = algorithm.run(evaluate, &vec![], &vec![], &vec![]);
+        let second = algorithm.run(evaluate, &vec![], &vec![], &vec![]);
+        assert_eq!(first, second);
+
+        // Same with the search options (restarts happen quickly)
+        let algorithm = MultiObjectiveDescent {
+            max_iterations_without_improvement: 5,
+            ..algorithm.with_search_options(true, 3, 4)
+        };
+        let first = algorithm.run(evaluate, &vec![], &vec![], &vec![]);
+        let second = algorithm.run(evaluate, &vec![], &vec![], &vec![]);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_stall_stops_after_saving_and_reporting() {
+        let saved = Arc::new(Mutex::new(Vec::<Checkpoint>::new()));
+        let saved_clone = Arc::clone(&saved);
+        let reported = Arc::new(Mutex::new(Vec::<usize>::new()));
+        let reported_clone = Arc::clone(&reported);
+        let algorithm = MultiObjectiveDescent::new(
+            0.5,
+            vec![
+                VariableDefinition::Float(-5.0, 5.0),
+                VariableDefinition::Integer(0, 10),
+            ],
+            3,
+            10,
+            2,
+        )
+        .with_seed(7)
+        .with_termination(Termination::max_generations(1000))
+        .with_checkpointing(Checkpointing::new(
+            1,
+            None,
+            Arc::new(move |checkpoint: &Checkpoint| {
+                saved_clone.lock().unwrap().push(checkpoint.clone())
+            }),
+        ))
+        .with_progress_reporter(ProgressReporter::new(Arc::new(
+            move |progress: &Progress| reported_clone.lock().unwrap().push(progress.generation),
+        )));
+        algorithm.run(evaluate, &vec![], &vec![], &vec![]);
+
+        // The search stalls long before the maximum number of iterations, without restarts :
+        // the stalled iteration is the last one saved and reported
+        let last = saved.lock().unwrap().last().unwrap().clone();
+        assert!(last.iteration < 1000);
+        assert!(matches!(
+            last.state,
+            CheckpointState::Descent {
+                iterations_without_improvement: 3,
+                ..
+            }
+        ));
+        assert_eq!(reported.lock().unwrap().last(), Some(&last.iteration));
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let algorithm = MultiObjectiveDescent {
+            max_iterations_without_improvement: 5,
+            ..MultiObjectiveDescent::new(
+                0.5,
+                vec![
+                    VariableDefinition::Float(-5.0, 5.0),
+                    VariableDefinition::Integer(0, 10),
+                ],
+                100,
+                10,
+                2,
+            )
+            .with_seed(3)
+            .with_termination(Termination::max_generations(60))
+            .with_search_options(true, 3, 4)
+        };
+        let saved = Arc::new(Mutex::new(Vec::<Checkpoint>::new()));
+        let saved_clone = Arc::clone(&saved);
+        let uninterrupted = algorithm
+            .clone()
+            .with_checkpointing(Checkpointing::new(
+                20,
+                None,
+                Arc::new(move |checkpoint: &Checkpoint| {
+                    saved_clone.lock().unwrap().push(checkpoint.clone())
+                }),
+            ))
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        let checkpoint = saved.lock().unwrap()[0].clone();
+        assert_eq!(checkpoint.iteration, 20);
+        let resumed = algorithm
+            .with_checkpointing(Checkpointing::new(
+                20,
+                Some(checkpoint),
+                Arc::new(|_: &Checkpoint| {}),
+            ))
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        assert_eq!(uninterrupted, resumed);
+    }
+
+    #[test]
+    fn test_trimmed_neighbor_not_kept() {
+        let algorithm =
+            MultiObjectiveDescent::new(0.5, vec![VariableDefinition::Float(0.0, 10.0)], 100, 2, 2);
+        let solution = |x: f64| {
+            let mut solution = Solution::new(vec![Variable::Float(x)], vec![]);
+            solution.apply_evaluation(Evaluation::new(vec![x, 10.0 - x]));
+            solution
+        };
+        let mut archive = vec![solution(0.0), solution(10.0)];
+
+        // Not dominated, but the least isolated solution of the full archive
+        assert!(!algorithm.archive_keeps(&mut archive, solution(5.0)));
+        assert!(archive
+            .iter()
+            .all(|solution| solution.variables != vec![Variable::Float(5.0)]));
+
+        let mut archive = vec![solution(0.0)];
+        assert!(algorithm.archive_keeps(&mut archive, solution(5.0)));
+    }
+
+    #[test]
+    fn test_distance() {
+        let algorithm = MultiObjectiveDescent::new(
+            1.0,
+            vec![
+                VariableDefinition::Float(0.0, 10.0),
+                VariableDefinition::Integer(0, 4),
+                VariableDefinition::Boolean,
+            ],
+            100,
+            10,
+            2,
+        );
+        let a = vec![
+            Variable::Float(0.0),
+            Variable::Integer(0),
+            Variable::Boolean(true),
+        ];
+        let b = vec![
+            Variable::Float(6.0),
+            Variable::Integer(4),
+            Variable::Boolean(true),
+        ];
+        assert!((algorithm.distance(&a, &b) - (0.36f64 + 1.0).sqrt()).abs() < 1e-9);
+        assert_eq!(algorithm.distance(&a, &a), 0.0);
+    }
+}
@@ -0,0 +1,109 @@
+use std::cmp::Ordering;
+
+use super::objects::Solution;
+
+/// Hypervolume (minimization) of the objectives of the feasible solutions, bounded by the reference point
+pub fn solutions_hypervolume(solutions: &[Solution], reference_point: &[f64]) -> f64 {
+    let points: Vec<Vec<f64>> = solutions
+        .iter()
+        .filter(|solution| solution.is_feasible())
+        .map(|solution| solution.objectives.clone())
+        .collect();
+
+    hypervolume(&points, reference_point)
+}
+
+/// Volume of the objective space dominated by the points and bounded by the reference point (minimization)
+pub fn hypervolume(points: &[Vec<f64>], reference_point: &[f64]) -> f64 {
+    if reference_point.is_empty() {
+        return 0.0;
+    }
+
+    // Only the points strictly better than the reference point contribute
+    let points: Vec<Vec<f64>> = points
+        .iter()
+        .filter(|point| {
+            point
+                .iter()
+                .zip(reference_point)
+                .all(|(value, reference)| value < reference)
+        })
+        .cloned()
+        .collect();
+
+    slice_volume(points, reference_point, reference_point.len())
+}
+
+// Hypervolume by slicing objectives (HSO) : the last dimension is cut into slices,
+// each slice being the volume of the points below it in one dimension less
+fn slice_volume(mut points: Vec<Vec<f64>>, reference_point: &[f64], dimensions: usize) -> f64 {
+    if points.is_empty() {
+        return 0.0;
+    }
+
+    let d = dimensions - 1;
+    if d == 0 {
+        let best = points
+            .iter()
+            .map(|point| point[0])
+            .fold(f64::INFINITY, f64::min);
+        return reference_point[0] - best;
+    }
+
+    points.sort_by(|a, b| a[d].partial_cmp(&b[d]).unwrap_or(Ordering::Equal));
+
+    let mut volume = 0.0;
+    for i in 0..points.len() {
+        let upper = match points.get(i + 1) {
+            Some(next) => next[d],
+            None => reference_point[d],
+        };
+        let depth = upper - points[i][d];
+        if depth > 0.0 {
+            volume += slice_volume(points[..=i].to_vec(), reference_point, d) * depth;
+        }
+    }
+
+    volume
+}
+
+/// Reference point for the hypervolume : the worst value of each objective, plus a 10% margin of its range
+pub fn reference_point(solutions: &[Solution], num_objectives: usize) -> Vec<f64> {
+    (0..num_objectives)
+        .map(|m| {
+            let values = solutions.iter().map(|solution| solution.objectives[m]);
+            let worst = values.clone().fold(f64::NEG_INFINITY, f64::max);
+            let best = values.fold(f64::INFINITY, f64::min);
+            let range = worst - best;
+            if range > 0.0 {
+                worst + 0.1 * range
+            } else {
+                worst + 1.0
+            }
+        })
+        .collect()
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hypervolume_2d() {
+        let points = vec![vec![1.0, 3.0], vec![2.0, 2.0], vec![3.0, 1.0]];
+        assert!((hypervolume(&points, &[4.0, 4.0]) - 6.0).abs() < 1e-9);
+
+        // Dominated points and points outside the reference point do not add volume
+        let points = vec![vec![1.0, 1.0], vec![2.0, 2.0], vec![5.0, 0.0]];
+        assert!((hypervolume(&points, &[3.0, 3.0]) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_hypervolume_3d() {
+        let points = vec![vec![0.0, 0.0, 1.0], vec![1.0, 1.0, 0.0]];
+        // 2*2*1 (first point) + 1*1*2 (second point) - 1*1*1 (overlap)
+        assert!((hypervolume(&points, &[2.0, 2.0, 2.0]) - 5.0).abs() < 1e-9);
+    }
+}
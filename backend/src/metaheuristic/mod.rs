@@ -1,10 +1,25 @@
+mod cache;
+mod checkpoint;
+mod constraints;
 mod descent;
+mod hypervolume;
 pub mod mh;
 mod nsga2;
 mod objects;
+mod progress;
+mod termination;
+#[cfg(test)]
+pub mod test_utils;
 
+pub use cache::{CacheStats, FitnessCache};
+pub use checkpoint::{Checkpoint, CheckpointState, Checkpointing};
+pub use constraints::{repair_variables, VariableConstraint};
 pub use descent::MultiObjectiveDescent;
-pub use nsga2::NSGAII;
+pub use hypervolume::{hypervolume, reference_point, solutions_hypervolume};
+pub use nsga2::{IslandNSGAII, LocalSearch, MigrationTopology, NSGAII};
 pub use objects::{
-    Metaheuristic, MetaheuristicInfo, MetaheuristicTrait, Variable, VariableDefinition,
+    parse_seed, Evaluation, Metaheuristic, MetaheuristicInfo, MetaheuristicTrait, Solution,
+    Variable, VariableDefinition, MAX_SEED,
 };
+pub use progress::{Progress, ProgressReporter};
+pub use termination::{parse_optional, Termination, TerminationState, DEFAULT_MAX_GENERATIONS};
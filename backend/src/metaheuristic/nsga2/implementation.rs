@@ -1,13 +1,22 @@
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 
+use crate::metaheuristic::checkpoint::{Checkpoint, CheckpointState, Checkpointing};
+use crate::metaheuristic::constraints::{repair_variables, VariableConstraint};
+use crate::metaheuristic::descent::MultiObjectiveDescent;
+use crate::metaheuristic::hypervolume::{reference_point, solutions_hypervolume};
 use crate::metaheuristic::objects::{
-    MetaheuristicInfo, MetaheuristicTrait, Solution, Variable, VariableDefinition,
-    VariableDefinitionInfo,
+    fit_solutions_variables, parse_seed, random_seed, seed_info, Evaluation, MetaheuristicInfo,
+    MetaheuristicTrait, Solution, Variable, VariableDefinition, VariableDefinitionInfo,
 };
+use crate::metaheuristic::progress::{Progress, ProgressReporter};
+use crate::metaheuristic::termination::{Termination, TerminationState, DEFAULT_MAX_GENERATIONS};
 use crate::objects::{indicators::Indicator, klines::KlineCollection};
 
+use super::memetic::LocalSearch;
+
 // Parallelize the NSGA-II algorithm using Rayon
 use rayon::prelude::*;
 // use std::sync::Arc;
@@ -15,11 +24,18 @@ use rayon::prelude::*;
 /// The main NSGA-II algorithm implementation
 #[derive(Clone, Debug)]
 pub struct NSGAII {
-    population_size: usize,
+    pub(super) population_size: usize,
     variable_definitions: Vec<VariableDefinition>,
-    num_objectives: usize,
+    pub(super) num_objectives: usize,
     mutation_rate: f64,
     crossover_rate: f64,
+    variable_constraints: Vec<VariableConstraint>,
+    pub(super) seed: u64,
+    pub(super) checkpointing: Option<Checkpointing>,
+    pub(super) progress_reporter: Option<ProgressReporter>,
+    pub(super) termination: Termination,
+    pub(super) initial_solutions: Vec<Vec<Variable>>, // variables of solutions from a previous run
+    local_search: Option<LocalSearch>,                // refinement of the first front (memetic)
 }
 
 impl NSGAII {
@@ -36,6 +52,13 @@ impl NSGAII {
             num_objectives,
             mutation_rate,
             crossover_rate,
+            variable_constraints: Vec::new(),
+            seed: random_seed(),
+            checkpointing: None,
+            progress_reporter: None,
+            termination: Termination::max_generations(DEFAULT_MAX_GENERATIONS),
+            initial_solutions: Vec::new(),
+            local_search: None,
         }
     }
 
@@ -72,6 +95,9 @@ impl NSGAII {
         if !population_size.is_ok() || !mutation_rate.is_ok() || !crossover_rate.is_ok() {
             return Err("Invalid parameters for the algorithm".to_string());
         }
+        let seed = parse_seed(json)?;
+        let termination = Termination::from_json(json)?;
+        let local_search = LocalSearch::from_json(json)?;
 
         Ok(NSGAII {
             population_size: population_size.unwrap(),
@@ -79,9 +105,79 @@ impl NSGAII {
             num_objectives: num_objectives,
             mutation_rate: mutation_rate.unwrap(),
             crossover_rate: crossover_rate.unwrap(),
+            variable_constraints: Vec::new(),
+            seed,
+            checkpointing: None,
+            progress_reporter: None,
+            termination,
+            initial_solutions: Vec::new(),
+            local_search,
         })
     }
 
+    /// Set the constraints between variables that every generated solution must satisfy
+    pub fn with_variable_constraints(
+        mut self,
+        variable_constraints: Vec<VariableConstraint>,
+    ) -> Self {
+        self.variable_constraints = variable_constraints;
+        self
+    }
+
+    /// Set the seed of the random number generator (same seed and data, same run)
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Save the state of the run periodically (and resume from a previous state, if any)
+    pub fn with_checkpointing(mut self, checkpointing: Checkpointing) -> Self {
+        if let Some(checkpoint) = &checkpointing.resume_from {
+            self.seed = checkpoint.seed;
+        }
+        self.checkpointing = Some(checkpointing);
+        self
+    }
+
+    /// Set the conditions ending the run
+    pub fn with_termination(mut self, termination: Termination) -> Self {
+        self.termination = termination;
+        self
+    }
+
+    /// Report the progress of the run after each generation
+    pub fn with_progress_reporter(mut self, progress_reporter: ProgressReporter) -> Self {
+        self.progress_reporter = Some(progress_reporter);
+        self
+    }
+
+    /// Refine the first front periodically with a local search (memetic algorithm)
+    pub fn with_local_search(mut self, local_search: LocalSearch) -> Self {
+        self.local_search = Some(local_search);
+        self
+    }
+
+    /// Start the initial population from the variables of previous solutions (warm start),
+    /// the solutions that do not fit the variable definitions are ignored
+    pub fn with_initial_solutions(mut self, initial_solutions: Vec<Vec<Variable>>) -> Self {
+        self.initial_solutions =
+            fit_solutions_variables(&initial_solutions, &self.variable_definitions);
+        self
+    }
+
+    /// Repair the variables of a solution so that they satisfy the variable constraints
+    fn repair(&self, variables: &mut [Variable]) {
+        repair_variables(
+            variables,
+            &self.variable_definitions,
+            &self.variable_constraints,
+        );
+    }
+
     pub fn get_info() -> MetaheuristicInfo {
         MetaheuristicInfo {
             name: "NSGA-II".to_string(),
@@ -92,36 +188,54 @@ impl NSGAII {
                     description: "Number of solutions in the population".to_string(),
                     variable_type: "integer".to_string(),
                     bounds: Some((1.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
                 },
                 VariableDefinitionInfo {
                     name: "num_objectives".to_string(),
                     description: "Number of objectives to optimize".to_string(),
                     variable_type: "integer".to_string(),
                     bounds: Some((1.0, f64::INFINITY)),
+                    step: None,
+                    choices: None,
                 },
                 VariableDefinitionInfo {
                     name: "mutation_rate".to_string(),
                     description: "Probability of mutation for each variable".to_string(),
                     variable_type: "float".to_string(),
                     bounds: Some((0.0, 1.0)),
+                    step: None,
+                    choices: None,
                 },
                 VariableDefinitionInfo {
                     name: "crossover_rate".to_string(),
                     description: "Probability of crossover for each pair of parents".to_string(),
                     variable_type: "float".to_string(),
                     bounds: Some((0.0, 1.0)),
+                    step: None,
+                    choices: None,
                 },
-            ],
+            ]
+            .into_iter()
+            .chain(LocalSearch::get_info())
+            .chain(std::iter::once(seed_info()))
+            .chain(Termination::get_info())
+            .collect(),
         }
     }
 
-    /// Initialize random population
-    fn initialize_population(&self) -> Vec<Solution> {
-        let mut rng = rand::thread_rng();
+    /// Initialize population : initial solutions first (if any), then random solutions
+    fn initialize_population(&self, rng: &mut impl Rng) -> Vec<Solution> {
         let mut population = Vec::with_capacity(self.population_size);
 
-        for _ in 0..self.population_size {
-            let variables: Vec<Variable> = self
+        for initial_variables in self.initial_solutions.iter().take(self.population_size) {
+            let mut variables = initial_variables.clone();
+            self.repair(&mut variables);
+            population.push(Solution::new(variables, vec![0.0; self.num_objectives]));
+        }
+
+        while population.len() < self.population_size {
+            let mut variables: Vec<Variable> = self
                 .variable_definitions
                 .iter()
                 .map(|def| match def {
@@ -132,8 +246,15 @@ impl NSGAII {
                         Variable::Integer(rng.gen_range(*min..=*max))
                     }
                     VariableDefinition::Boolean => Variable::Boolean(rng.gen_bool(0.5)),
+                    VariableDefinition::Categorical(choices) => {
+                        Variable::Categorical(rng.gen_range(0..choices.len().max(1)))
+                    }
+                    VariableDefinition::SteppedFloat(min, _, step) => Variable::Float(
+                        min + rng.gen_range(0..=def.get_steps_count()) as f64 * step,
+                    ),
                 })
                 .collect();
+            self.repair(&mut variables);
             population.push(Solution::new(variables, vec![0.0; self.num_objectives]));
         }
 
@@ -259,8 +380,7 @@ impl NSGAII {
     }
 
     /// Tournament selection
-    fn tournament_selection(&self, population: &[Solution]) -> usize {
-        let mut rng = rand::thread_rng();
+    fn tournament_selection(&self, population: &[Solution], rng: &mut impl Rng) -> usize {
         let a = rng.gen_range(0..population.len());
         let b = rng.gen_range(0..population.len());
 
@@ -276,8 +396,12 @@ impl NSGAII {
     }
 
     /// Simulated Binary Crossover (SBX)
-    fn crossover(&self, parent1: &Solution, parent2: &Solution) -> (Solution, Solution) {
-        let mut rng = rand::thread_rng();
+    fn crossover(
+        &self,
+        parent1: &Solution,
+        parent2: &Solution,
+        rng: &mut impl Rng,
+    ) -> (Solution, Solution) {
         let mut child1 = parent1.clone();
         let mut child2 = parent2.clone();
 
@@ -286,7 +410,8 @@ impl NSGAII {
 
             for (i, def) in self.variable_definitions.iter().enumerate() {
                 match def {
-                    VariableDefinition::Float(min, max) => {
+                    VariableDefinition::Float(min, max)
+                    | VariableDefinition::SteppedFloat(min, max, _) => {
                         if rng.gen::<f64>() < 0.5 {
                             if let (Variable::Float(y1), Variable::Float(y2)) =
                                 (&parent1.variables[i], &parent2.variables[i])
@@ -308,8 +433,8 @@ impl NSGAII {
                                 let c1 = 0.5 * ((y1 + y2) - betaq * (y2 - y1));
                                 let c2 = 0.5 * ((y1 + y2) + betaq * (y2 - y1));
 
-                                child1.variables[i] = Variable::Float(c1.clamp(*min, *max));
-                                child2.variables[i] = Variable::Float(c2.clamp(*min, *max));
+                                child1.variables[i] = Variable::Float(def.quantize(c1));
+                                child2.variables[i] = Variable::Float(def.quantize(c2));
                             }
                         }
                     }
@@ -349,28 +474,31 @@ impl NSGAII {
                             }
                         }
                     }
-                    VariableDefinition::Boolean => {
-                        // For boolean variables, randomly swap between parents
+                    VariableDefinition::Boolean | VariableDefinition::Categorical(_) => {
+                        // For boolean and categorical variables, randomly swap between parents
                         if rng.gen::<f64>() < 0.5 {
                             std::mem::swap(&mut child1.variables[i], &mut child2.variables[i]);
                         }
                     }
                 }
             }
+
+            self.repair(&mut child1.variables);
+            self.repair(&mut child2.variables);
         }
 
         (child1, child2)
     }
 
     /// Modified mutation to handle mixed variables
-    fn mutate(&self, solution: &mut Solution) {
-        let mut rng = rand::thread_rng();
+    fn mutate(&self, solution: &mut Solution, rng: &mut impl Rng) {
         let eta_m = 20.0; // Distribution index for mutation
 
         for (i, def) in self.variable_definitions.iter().enumerate() {
             if rng.gen::<f64>() < self.mutation_rate {
                 match def {
-                    VariableDefinition::Float(min, max) => {
+                    VariableDefinition::Float(min, max)
+                    | VariableDefinition::SteppedFloat(min, max, _) => {
                         if let Variable::Float(y) = solution.variables[i] {
                             let delta1 = (y - min) / (max - min);
                             let delta2 = (max - y) / (max - min);
@@ -389,7 +517,7 @@ impl NSGAII {
                             }
 
                             let mutated = y + deltaq * (max - min);
-                            solution.variables[i] = Variable::Float(mutated.clamp(*min, *max));
+                            solution.variables[i] = Variable::Float(def.quantize(mutated));
                         }
                     }
                     VariableDefinition::Integer(min, max) => {
@@ -425,25 +553,26 @@ impl NSGAII {
                             solution.variables[i] = Variable::Boolean(!b);
                         }
                     }
+                    VariableDefinition::Categorical(choices) => {
+                        // Switch to another choice
+                        if let Variable::Categorical(c) = solution.variables[i] {
+                            if choices.len() > 1 {
+                                let shift = rng.gen_range(1..choices.len());
+                                solution.variables[i] =
+                                    Variable::Categorical((c + shift) % choices.len());
+                            }
+                        }
+                    }
                 }
             }
         }
+
+        self.repair(&mut solution.variables);
     }
 
-    /// Check if solution a dominates solution b
+    /// Check if solution a dominates solution b (constrained-domination)
     fn dominates(&self, a: &Solution, b: &Solution) -> bool {
-        let mut one_is_better = false;
-
-        for i in 0..self.num_objectives {
-            if a.objectives[i] > b.objectives[i] {
-                return false;
-            }
-            if a.objectives[i] < b.objectives[i] {
-                one_is_better = true;
-            }
-        }
-
-        one_is_better
+        a.constrained_dominates(b, self.num_objectives)
     }
 
     // /// Parallelized offspring generation
@@ -467,10 +596,157 @@ impl NSGAII {
     //         .collect()
     // }
 
+    /// Sort a population by rank, then by decreasing crowding distance (best solutions first)
+    pub(super) fn rank_population(&self, population: &mut Vec<Solution>) {
+        // Non-dominated sorting
+        let fronts = self.non_dominated_sort(population);
+
+        // Calculate crowding distance for each front
+        for front in &fronts {
+            self.calculate_crowding_distance(population, front);
+        }
+
+        population.sort_by(|a, b| match a.rank.cmp(&b.rank) {
+            Ordering::Equal => b
+                .crowding_distance
+                .partial_cmp(&a.crowding_distance)
+                .unwrap_or(Ordering::Equal),
+            other => other,
+        });
+    }
+
+    /// Random population, evaluated
+    pub(super) fn initial_population<F>(
+        &self,
+        rng: &mut impl Rng,
+        evaluate: &F,
+        kline_collections: &Vec<KlineCollection>,
+        indicators: &Vec<Indicator>,
+        variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
+    ) -> Vec<Solution>
+    where
+        F: Fn(
+            &[Variable],
+            &Vec<KlineCollection>,
+            &Vec<Indicator>,
+            &Vec<Vec<VariableDefinition>>,
+        ) -> Evaluation,
+    {
+        let mut population = self.initialize_population(rng);
+
+        // Parallel evaluation of initial population
+        population.iter_mut().for_each(|solution| {
+            solution.apply_evaluation(evaluate(
+                &solution.variables,
+                kline_collections,
+                indicators,
+                variable_definitions_sep,
+            ));
+        });
+
+        population
+    }
+
+    /// One generation : offspring creation and evaluation, then selection among parents and offspring
+    pub(super) fn next_generation<F>(
+        &self,
+        mut population: Vec<Solution>,
+        rng: &mut impl Rng,
+        evaluate: &F,
+        kline_collections: &Vec<KlineCollection>,
+        indicators: &Vec<Indicator>,
+        variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
+    ) -> Vec<Solution>
+    where
+        F: Fn(
+            &[Variable],
+            &Vec<KlineCollection>,
+            &Vec<Indicator>,
+            &Vec<Vec<VariableDefinition>>,
+        ) -> Evaluation,
+    {
+        // Create offspring population
+        let mut offspring: Vec<Solution> = Vec::with_capacity(self.population_size);
+
+        while offspring.len() < self.population_size {
+            // Selection
+            let parent1_idx = self.tournament_selection(&population, rng);
+            let parent2_idx = self.tournament_selection(&population, rng);
+
+            // Crossover
+            let (mut child1, mut child2) =
+                self.crossover(&population[parent1_idx], &population[parent2_idx], rng);
+
+            // Mutation
+            self.mutate(&mut child1, rng);
+            self.mutate(&mut child2, rng);
+
+            offspring.push(child1);
+            if offspring.len() < self.population_size {
+                offspring.push(child2);
+            }
+        }
+
+        // Generate offspring in parallel
+        // let offspring = self.generate_offspring_parallel(&population, evaluate.clone().into());
+
+        // Parallel evaluation of offspring
+        offspring.iter_mut().for_each(|child| {
+            child.apply_evaluation(evaluate(
+                &child.variables,
+                kline_collections,
+                indicators,
+                variable_definitions_sep,
+            ));
+        });
+
+        // Combine parent and offspring populations
+        population.extend(offspring);
+
+        // Select next generation
+        self.rank_population(&mut population);
+        population.truncate(self.population_size);
+        population
+    }
+
+    /// Local search on the first front, if due at this generation : the improved solutions
+    /// compete with the population for their place (returns the number of evaluations)
+    pub(super) fn apply_local_search(
+        &self,
+        population: &mut Vec<Solution>,
+        generation: usize,
+        rng: &mut impl Rng,
+        evaluate: impl Fn(&[Variable]) -> Evaluation,
+    ) -> usize {
+        let local_search = match &self.local_search {
+            Some(local_search) if local_search.is_due(generation) => local_search,
+            _ => return 0,
+        };
+
+        // Neighbourhood and archive of the descent (the archive holds at most a population)
+        let descent = MultiObjectiveDescent::new(
+            local_search.step_size,
+            self.variable_definitions.clone(),
+            local_search.steps,
+            self.population_size,
+            self.num_objectives,
+        )
+        .with_variable_constraints(self.variable_constraints.clone());
+
+        let (improved, evaluations) = local_search.refine(&descent, population, rng, evaluate);
+
+        if !improved.is_empty() {
+            population.extend(improved);
+            self.rank_population(population);
+            population.truncate(self.population_size);
+        }
+
+        evaluations
+    }
+
     /// Run the NSGA-II algorithm
     pub fn run<F>(
         &self,
-        generations: usize,
         evaluate: F,
         kline_collections: &Vec<KlineCollection>,
         indicators: &Vec<Indicator>,
@@ -482,12 +758,85 @@ impl NSGAII {
                 &Vec<KlineCollection>,
                 &Vec<Indicator>,
                 &Vec<Vec<VariableDefinition>>,
-            ) -> Vec<f64>
+            ) -> Evaluation
             + Clone
             + Sync
             + Send,
     {
-        let mut population = self.initialize_population();
+        let resume_from = self
+            .checkpointing
+            .as_ref()
+            .and_then(|checkpointing| checkpointing.resume_from.clone());
+
+        // Every random draw goes through this generator, in the main thread and in a fixed order,
+        // so that runs are reproducible whatever the evaluation order
+        let (
+            mut rng,
+            mut population,
+            first_generation,
+            mut evaluations,
+            mut hv_reference,
+            termination_state,
+        ) = match resume_from {
+            Some(Checkpoint {
+                state: CheckpointState::NSGAII { rng },
+                solutions: mut population,
+                iteration,
+                evaluations,
+                reference_point,
+                termination,
+                ..
+            }) => {
+                // Crowding distances are not saved
+                let fronts = self.non_dominated_sort(&mut population);
+                for front in &fronts {
+                    self.calculate_crowding_distance(&mut population, front);
+                }
+                (
+                    rng,
+                    population,
+                    iteration,
+                    evaluations,
+                    reference_point,
+                    termination,
+                )
+            }
+            _ => {
+                let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+                let population = self.initial_population(
+                    &mut rng,
+                    &evaluate,
+                    kline_collections,
+                    indicators,
+                    variable_definitions_sep,
+                );
+
+                (
+                    rng,
+                    population,
+                    0,
+                    self.population_size,
+                    Vec::new(),
+                    TerminationState::default(),
+                )
+            }
+        };
+
+        // Hypervolume reference point, fixed for the whole run
+        if hv_reference.is_empty() {
+            hv_reference = reference_point(&population, self.num_objectives);
+        }
+        let mut termination_state = termination_state.started_at(first_generation, evaluations);
+
+        // A resumed run may have already met its termination criteria
+        let mut generation = first_generation;
+        let mut stop = generation > 0
+            && self.termination.should_stop(
+                &mut termination_state,
+                generation,
+                evaluations,
+                solutions_hypervolume(&population, &hv_reference),
+            );
 
         // println!("Currently have issues with parallel evaluation");
         // return vec![];
@@ -497,73 +846,64 @@ impl NSGAII {
         //     solution.objectives = evaluate(&solution.variables);
         // }
 
-        // Parallel evaluation of initial population
-        population.iter_mut().for_each(|solution| {
-            solution.objectives = evaluate(
-                &solution.variables,
+        while !stop {
+            population = self.next_generation(
+                population,
+                &mut rng,
+                &evaluate,
                 kline_collections,
                 indicators,
                 variable_definitions_sep,
             );
-        });
-
-        for _ in 0..generations {
-            // Create offspring population
-            let mut offspring: Vec<Solution> = Vec::with_capacity(self.population_size);
-
-            while offspring.len() < self.population_size {
-                // Selection
-                let parent1_idx = self.tournament_selection(&population);
-                let parent2_idx = self.tournament_selection(&population);
-
-                // Crossover
-                let (mut child1, mut child2) =
-                    self.crossover(&population[parent1_idx], &population[parent2_idx]);
-
-                // Mutation
-                self.mutate(&mut child1);
-                self.mutate(&mut child2);
+            evaluations += self.population_size;
+            generation += 1;
+
+            // Memetic refinement of the first front
+            evaluations +=
+                self.apply_local_search(&mut population, generation, &mut rng, |variables| {
+                    evaluate(
+                        variables,
+                        kline_collections,
+                        indicators,
+                        variable_definitions_sep,
+                    )
+                });
+
+            let progress = Progress::new(
+                generation,
+                self.termination.max_generations,
+                evaluations,
+                &population,
+                &hv_reference,
+            );
+            stop = self.termination.should_stop(
+                &mut termination_state,
+                generation,
+                evaluations,
+                progress.hypervolume,
+            );
 
-                offspring.push(child1);
-                if offspring.len() < self.population_size {
-                    offspring.push(child2);
-                }
+            if let Some(checkpointing) = &self.checkpointing {
+                checkpointing.save_if_due(generation, || Checkpoint {
+                    seed: self.seed,
+                    iteration: generation,
+                    solutions: population.clone(),
+                    state: CheckpointState::NSGAII { rng: rng.clone() },
+                    evaluations,
+                    reference_point: hv_reference.clone(),
+                    termination: termination_state.snapshot(),
+                });
             }
 
-            // Generate offspring in parallel
-            // let offspring = self.generate_offspring_parallel(&population, evaluate.clone().into());
-
-            // Parallel evaluation of offspring
-            offspring.iter_mut().for_each(|child| {
-                child.objectives = evaluate(
-                    &child.variables,
-                    kline_collections,
-                    indicators,
-                    variable_definitions_sep,
+            if let Some(progress_reporter) = &self.progress_reporter {
+                progress_reporter.report(
+                    &progress.with_timing(
+                        termination_state.elapsed_seconds(),
+                        self.termination
+                            .eta_seconds(&termination_state, generation, evaluations),
+                    ),
                 );
-            });
-
-            // Combine parent and offspring populations
-            population.extend(offspring);
-
-            // Non-dominated sorting
-            let fronts = self.non_dominated_sort(&mut population);
-
-            // Calculate crowding distance for each front
-            for front in &fronts {
-                self.calculate_crowding_distance(&mut population, front);
             }
-
-            // Select next generation
-            population.sort_by(|a, b| match a.rank.cmp(&b.rank) {
-                Ordering::Equal => b
-                    .crowding_distance
-                    .partial_cmp(&a.crowding_distance)
-                    .unwrap_or(Ordering::Equal),
-                other => other,
-            });
-
-            population.truncate(self.population_size);
         }
 
         population
@@ -574,13 +914,12 @@ impl NSGAII {
 impl MetaheuristicTrait for NSGAII {
     fn run(
         &self,
-        num_generations: usize,
         evaluate: impl Fn(
                 &[Variable],
                 &Vec<KlineCollection>,
                 &Vec<Indicator>,
                 &Vec<Vec<VariableDefinition>>,
-            ) -> Vec<f64>
+            ) -> Evaluation
             + Clone
             + Sync
             + Send,
@@ -589,7 +928,6 @@ impl MetaheuristicTrait for NSGAII {
         variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
     ) -> Vec<Solution> {
         self.run(
-            num_generations,
             evaluate,
             kline_collections,
             indicators,
@@ -597,3 +935,95 @@ impl MetaheuristicTrait for NSGAII {
         )
     }
 }
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metaheuristic::test_utils::{evaluate, make_definitions};
+    use std::sync::{Arc, Mutex};
+
+    fn algorithm_with_seed(seed: u64, generations: usize) -> NSGAII {
+        NSGAII::new(10, make_definitions(), 2, 0.2, 0.9)
+            .with_seed(seed)
+            .with_termination(Termination::max_generations(generations))
+    }
+
+    fn run_with_seed(seed: u64) -> Vec<Solution> {
+        algorithm_with_seed(seed, 5).run(evaluate, &vec![], &vec![], &vec![])
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let first = run_with_seed(42);
+        let second = run_with_seed(42);
+        assert_eq!(first, second);
+
+        let other = run_with_seed(43);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_initial_solutions() {
+        let algorithm = algorithm_with_seed(42, 5).with_initial_solutions(vec![
+            vec![Variable::Float(1.5), Variable::Integer(3)],
+            vec![Variable::Float(1.5)], // does not fit, ignored
+        ]);
+        let population = algorithm.initialize_population(&mut ChaCha8Rng::seed_from_u64(42));
+
+        assert_eq!(population.len(), 10);
+        assert_eq!(
+            population[0].variables,
+            vec![Variable::Float(1.5), Variable::Integer(3)]
+        );
+    }
+
+    #[test]
+    fn test_local_search() {
+        let evaluations = std::sync::atomic::AtomicUsize::new(0);
+        let counted_evaluate =
+            |vars: &[Variable],
+             klines: &Vec<KlineCollection>,
+             indicators: &Vec<Indicator>,
+             definitions: &Vec<Vec<VariableDefinition>>| {
+                evaluations.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                evaluate(vars, klines, indicators, definitions)
+            };
+
+        // Local search after the 2nd and 4th generations
+        let population = algorithm_with_seed(42, 4)
+            .with_local_search(LocalSearch::new(2))
+            .run(counted_evaluate, &vec![], &vec![], &vec![]);
+
+        assert_eq!(population.len(), 10);
+        // 10 initial solutions, 4 generations of 10 offspring, then at least one refined member twice
+        assert!(evaluations.into_inner() >= 10 + 4 * 10 + 2 * 10);
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let saved = Arc::new(Mutex::new(Vec::<Checkpoint>::new()));
+        let saved_clone = Arc::clone(&saved);
+        let checkpointing = Checkpointing::new(
+            3,
+            None,
+            Arc::new(move |checkpoint: &Checkpoint| {
+                saved_clone.lock().unwrap().push(checkpoint.clone())
+            }),
+        );
+        let uninterrupted = algorithm_with_seed(42, 6)
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        // Resume from the checkpoint of the 3rd generation (seed taken from the checkpoint)
+        let checkpoint = saved.lock().unwrap()[0].clone();
+        assert_eq!(checkpoint.iteration, 3);
+        let checkpointing = Checkpointing::new(3, Some(checkpoint), Arc::new(|_: &Checkpoint| {}));
+        let resumed = algorithm_with_seed(0, 6)
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        assert_eq!(uninterrupted, resumed);
+    }
+}
@@ -0,0 +1,623 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use std::thread;
+
+use super::implementation::NSGAII;
+use crate::metaheuristic::checkpoint::{Checkpoint, CheckpointState, Checkpointing};
+use crate::metaheuristic::constraints::VariableConstraint;
+use crate::metaheuristic::hypervolume::{reference_point, solutions_hypervolume};
+use crate::metaheuristic::objects::{
+    Evaluation, MetaheuristicInfo, MetaheuristicTrait, Solution, Variable, VariableDefinition,
+    VariableDefinitionInfo,
+};
+use crate::metaheuristic::progress::{Progress, ProgressReporter};
+use crate::metaheuristic::termination::{parse_optional, Termination, TerminationState};
+use crate::objects::{indicators::Indicator, klines::KlineCollection};
+
+const NUM_ISLANDS_DEFAULT: usize = 4;
+const MIGRATION_INTERVAL_DEFAULT: usize = 5;
+const MIGRATION_SIZE_DEFAULT: usize = 2;
+
+/// Islands receiving the migrants of each island
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MigrationTopology {
+    Ring,           // to the next island only
+    FullyConnected, // to every other island
+}
+
+impl MigrationTopology {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ring" => Some(MigrationTopology::Ring),
+            "fully_connected" => Some(MigrationTopology::FullyConnected),
+            _ => None,
+        }
+    }
+
+    /// Islands sending their migrants to the given island
+    fn sources(&self, island: usize, num_islands: usize) -> Vec<usize> {
+        match self {
+            MigrationTopology::Ring => vec![(island + num_islands - 1) % num_islands],
+            MigrationTopology::FullyConnected => {
+                (0..num_islands).filter(|&other| other != island).collect()
+            }
+        }
+    }
+}
+
+/// Island model of NSGA-II : subpopulations evolving on separate threads,
+/// exchanging their best solutions every few generations
+#[derive(Clone, Debug)]
+pub struct IslandNSGAII {
+    nsga2: NSGAII, // settings and operators of each island (population size per island)
+    num_islands: usize,
+    migration_interval: usize, // generations between two migrations
+    migration_size: usize,     // solutions sent by each island at each migration
+    topology: MigrationTopology,
+}
+
+impl IslandNSGAII {
+    pub fn new(
+        nsga2: NSGAII,
+        num_islands: usize,
+        migration_interval: usize,
+        migration_size: usize,
+        topology: MigrationTopology,
+    ) -> Self {
+        IslandNSGAII {
+            nsga2,
+            num_islands: num_islands.max(1),
+            migration_interval: migration_interval.max(1),
+            migration_size,
+            topology,
+        }
+    }
+
+    pub fn new_from_json(
+        json: &serde_json::Value,
+        variable_definitions: Vec<VariableDefinition>,
+        num_objectives: usize,
+    ) -> Result<Self, String> {
+        let nsga2 = NSGAII::new_from_json(json, variable_definitions, num_objectives)?;
+
+        let topology = match json.get("topology").and_then(|value| value.as_str()) {
+            Some(name) if !name.trim().is_empty() => MigrationTopology::from_name(name.trim())
+                .ok_or_else(|| "Invalid parameter topology for the algorithm".to_string())?,
+            _ => MigrationTopology::Ring,
+        };
+
+        Ok(IslandNSGAII::new(
+            nsga2,
+            parse_optional(json, "num_islands")?.unwrap_or(NUM_ISLANDS_DEFAULT),
+            parse_optional(json, "migration_interval")?.unwrap_or(MIGRATION_INTERVAL_DEFAULT),
+            parse_optional(json, "migration_size")?.unwrap_or(MIGRATION_SIZE_DEFAULT),
+            topology,
+        ))
+    }
+
+    pub fn with_variable_constraints(
+        mut self,
+        variable_constraints: Vec<VariableConstraint>,
+    ) -> Self {
+        self.nsga2 = self.nsga2.with_variable_constraints(variable_constraints);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.nsga2 = self.nsga2.with_seed(seed);
+        self
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.nsga2.get_seed()
+    }
+
+    /// Start the islands from the variables of previous solutions (shared out between the islands)
+    pub fn with_initial_solutions(mut self, initial_solutions: Vec<Vec<Variable>>) -> Self {
+        self.nsga2 = self.nsga2.with_initial_solutions(initial_solutions);
+        self
+    }
+
+    /// Save the state of the run periodically (checkpoints are only saved after a migration)
+    pub fn with_checkpointing(mut self, checkpointing: Checkpointing) -> Self {
+        self.nsga2 = self.nsga2.with_checkpointing(checkpointing);
+        self
+    }
+
+    pub fn with_termination(mut self, termination: Termination) -> Self {
+        self.nsga2 = self.nsga2.with_termination(termination);
+        self
+    }
+
+    /// Report the progress of the run after each migration
+    pub fn with_progress_reporter(mut self, progress_reporter: ProgressReporter) -> Self {
+        self.nsga2 = self.nsga2.with_progress_reporter(progress_reporter);
+        self
+    }
+
+    pub fn get_info() -> MetaheuristicInfo {
+        let nsga2_info = NSGAII::get_info();
+        let mut parameters = nsga2_info.parameters;
+        parameters[0].description =
+            "Number of solutions in the population of each island".to_string();
+        let island_parameters = vec![
+            VariableDefinitionInfo {
+                name: "num_islands".to_string(),
+                description: format!(
+                    "Number of subpopulations evolving in parallel (optional, {} by default)",
+                    NUM_ISLANDS_DEFAULT
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "migration_interval".to_string(),
+                description: format!(
+                    "Generations between two migrations (optional, {} by default)",
+                    MIGRATION_INTERVAL_DEFAULT
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "migration_size".to_string(),
+                description: format!(
+                    "Best solutions sent by each island at each migration (optional, {} by default)",
+                    MIGRATION_SIZE_DEFAULT
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((0.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "topology".to_string(),
+                description: "Islands receiving the migrants : the next one (ring) or all the others (fully_connected) (optional, ring by default)".to_string(),
+                variable_type: "categorical".to_string(),
+                bounds: None,
+                step: None,
+                choices: Some(vec!["ring".to_string(), "fully_connected".to_string()]),
+            },
+        ];
+        // Island parameters right after the NSGA-II ones (before the seed and termination criteria)
+        let position = parameters
+            .iter()
+            .position(|parameter| parameter.name == "seed")
+            .unwrap_or(parameters.len());
+        parameters.splice(position..position, island_parameters);
+
+        MetaheuristicInfo {
+            name: "Island NSGA-II".to_string(),
+            description: "NSGA-II with subpopulations evolving in parallel and exchanging their best solutions".to_string(),
+            parameters,
+        }
+    }
+
+    /// Each island receives the best solutions of its sources, and keeps the best of its population and the migrants
+    fn migrate(&self, populations: &mut [Vec<Solution>]) {
+        // Populations are sorted, best solutions first
+        let migrants: Vec<Vec<Solution>> = populations
+            .iter()
+            .map(|population| {
+                population
+                    .iter()
+                    .take(self.migration_size)
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+
+        for (island, population) in populations.iter_mut().enumerate() {
+            if self.num_islands > 1 {
+                for source in self.topology.sources(island, self.num_islands) {
+                    population.extend(migrants[source].iter().cloned());
+                }
+                self.nsga2.rank_population(population);
+                population.truncate(self.nsga2.population_size);
+            }
+            // Ranks and crowding distances among the remaining solutions only (as when resuming a run)
+            self.nsga2.rank_population(population);
+        }
+    }
+
+    /// All the solutions of the islands, best first
+    fn merge(&self, populations: &[Vec<Solution>]) -> Vec<Solution> {
+        let mut solutions: Vec<Solution> = populations.iter().flatten().cloned().collect();
+        self.nsga2.rank_population(&mut solutions);
+        solutions
+    }
+
+    /// Run the island model
+    pub fn run<F>(
+        &self,
+        evaluate: F,
+        kline_collections: &Vec<KlineCollection>,
+        indicators: &Vec<Indicator>,
+        variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
+    ) -> Vec<Solution>
+    where
+        F: Fn(
+                &[Variable],
+                &Vec<KlineCollection>,
+                &Vec<Indicator>,
+                &Vec<Vec<VariableDefinition>>,
+            ) -> Evaluation
+            + Clone
+            + Sync
+            + Send,
+    {
+        let nsga2 = &self.nsga2;
+        let seed = nsga2.seed;
+        let resume_from = nsga2
+            .checkpointing
+            .as_ref()
+            .and_then(|checkpointing| checkpointing.resume_from.clone());
+
+        // Each island draws from its own stream of the seeded generator, so that runs are
+        // reproducible whatever the scheduling of the threads
+        let (mut islands, first_generation, mut evaluations, mut hv_reference, termination_state) =
+            match resume_from {
+                Some(Checkpoint {
+                    state: CheckpointState::IslandNSGAII { island_rngs },
+                    solutions,
+                    iteration,
+                    evaluations,
+                    reference_point,
+                    termination,
+                    ..
+                }) if island_rngs.len() == self.num_islands => {
+                    let islands: Vec<(Vec<Solution>, ChaCha8Rng)> = solutions
+                        .chunks(nsga2.population_size)
+                        .map(|population| {
+                            let mut population = population.to_vec();
+                            nsga2.rank_population(&mut population);
+                            population
+                        })
+                        .zip(island_rngs)
+                        .collect();
+                    (
+                        islands,
+                        iteration,
+                        evaluations,
+                        reference_point,
+                        termination,
+                    )
+                }
+                _ => {
+                    let islands: Vec<(Vec<Solution>, ChaCha8Rng)> = (0..self.num_islands)
+                        .map(|island| {
+                            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                            rng.set_stream(island as u64);
+                            // Initial solutions dealt out in turn to the islands
+                            let mut island_nsga2 = nsga2.clone();
+                            island_nsga2.initial_solutions = nsga2
+                                .initial_solutions
+                                .iter()
+                                .skip(island)
+                                .step_by(self.num_islands)
+                                .cloned()
+                                .collect();
+                            let mut population = island_nsga2.initial_population(
+                                &mut rng,
+                                &evaluate,
+                                kline_collections,
+                                indicators,
+                                variable_definitions_sep,
+                            );
+                            nsga2.rank_population(&mut population);
+                            (population, rng)
+                        })
+                        .collect();
+                    (
+                        islands,
+                        0,
+                        self.num_islands * nsga2.population_size,
+                        Vec::new(),
+                        TerminationState::default(),
+                    )
+                }
+            };
+
+        let populations = |islands: &[(Vec<Solution>, ChaCha8Rng)]| -> Vec<Vec<Solution>> {
+            islands
+                .iter()
+                .map(|(population, _)| population.clone())
+                .collect()
+        };
+
+        // Hypervolume reference point, fixed for the whole run
+        if hv_reference.is_empty() {
+            hv_reference =
+                reference_point(&self.merge(&populations(&islands)), nsga2.num_objectives);
+        }
+        let mut termination_state = termination_state.started_at(first_generation, evaluations);
+
+        // A resumed run may have already met its termination criteria
+        let mut generation = first_generation;
+        let mut stop = generation > 0
+            && nsga2.termination.should_stop(
+                &mut termination_state,
+                generation,
+                evaluations,
+                solutions_hypervolume(&self.merge(&populations(&islands)), &hv_reference),
+            );
+
+        while !stop {
+            // Generations until the next migration (without going past the maximum number of generations)
+            let epoch = match nsga2.termination.max_generations {
+                Some(max) => self
+                    .migration_interval
+                    .min(max.saturating_sub(generation).max(1)),
+                None => self.migration_interval,
+            };
+
+            // Islands evolve independently, one thread each. The evaluations may block on the
+            // runtime of the task (database access), so the threads enter it
+            let runtime = tokio::runtime::Handle::try_current().ok();
+            let evolved: Vec<(Vec<Solution>, ChaCha8Rng, usize)> = thread::scope(|scope| {
+                let handles: Vec<_> = islands
+                    .into_iter()
+                    .map(|(mut population, mut rng)| {
+                        let evaluate = &evaluate;
+                        let runtime = runtime.clone();
+                        scope.spawn(move || {
+                            let _guard = runtime.as_ref().map(|runtime| runtime.enter());
+                            let mut local_search_evaluations = 0;
+                            for g in 1..=epoch {
+                                population = nsga2.next_generation(
+                                    population,
+                                    &mut rng,
+                                    evaluate,
+                                    kline_collections,
+                                    indicators,
+                                    variable_definitions_sep,
+                                );
+                                // Memetic refinement of the first front of the island
+                                local_search_evaluations += nsga2.apply_local_search(
+                                    &mut population,
+                                    generation + g,
+                                    &mut rng,
+                                    |variables| {
+                                        evaluate(
+                                            variables,
+                                            kline_collections,
+                                            indicators,
+                                            variable_definitions_sep,
+                                        )
+                                    },
+                                );
+                            }
+                            (population, rng, local_search_evaluations)
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect()
+            });
+            evaluations += epoch * self.num_islands * nsga2.population_size
+                + evolved
+                    .iter()
+                    .map(|(_, _, evaluations)| evaluations)
+                    .sum::<usize>();
+            islands = evolved
+                .into_iter()
+                .map(|(population, rng, _)| (population, rng))
+                .collect();
+            let previous_generation = generation;
+            generation += epoch;
+
+            // Migration
+            let mut island_populations = populations(&islands);
+            self.migrate(&mut island_populations);
+            for ((population, _), migrated) in islands.iter_mut().zip(island_populations) {
+                *population = migrated;
+            }
+
+            let merged = self.merge(&populations(&islands));
+            let progress = Progress::new(
+                generation,
+                nsga2.termination.max_generations,
+                evaluations,
+                &merged,
+                &hv_reference,
+            );
+            stop = nsga2.termination.should_stop(
+                &mut termination_state,
+                generation,
+                evaluations,
+                progress.hypervolume,
+            );
+
+            // Save if a checkpoint was due during the last generations
+            if let Some(checkpointing) = &nsga2.checkpointing {
+                if generation / checkpointing.interval
+                    > previous_generation / checkpointing.interval
+                {
+                    (checkpointing.save)(&Checkpoint {
+                        seed,
+                        iteration: generation,
+                        solutions: islands
+                            .iter()
+                            .flat_map(|(population, _)| population.iter().cloned())
+                            .collect(),
+                        state: CheckpointState::IslandNSGAII {
+                            island_rngs: islands.iter().map(|(_, rng)| rng.clone()).collect(),
+                        },
+                        evaluations,
+                        reference_point: hv_reference.clone(),
+                        termination: termination_state.snapshot(),
+                    });
+                }
+            }
+
+            if let Some(progress_reporter) = &nsga2.progress_reporter {
+                progress_reporter.report(
+                    &progress.with_timing(
+                        termination_state.elapsed_seconds(),
+                        nsga2
+                            .termination
+                            .eta_seconds(&termination_state, generation, evaluations),
+                    ),
+                );
+            }
+        }
+
+        self.merge(&populations(&islands))
+    }
+}
+
+// Implement the MetaheuristicTrait for IslandNSGAII
+impl MetaheuristicTrait for IslandNSGAII {
+    fn run(
+        &self,
+        evaluate: impl Fn(
+                &[Variable],
+                &Vec<KlineCollection>,
+                &Vec<Indicator>,
+                &Vec<Vec<VariableDefinition>>,
+            ) -> Evaluation
+            + Clone
+            + Sync
+            + Send,
+        kline_collections: &Vec<KlineCollection>,
+        indicators: &Vec<Indicator>,
+        variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
+    ) -> Vec<Solution> {
+        self.run(
+            evaluate,
+            kline_collections,
+            indicators,
+            variable_definitions_sep,
+        )
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metaheuristic::test_utils::{evaluate, make_definitions};
+    use std::sync::{Arc, Mutex};
+
+    fn algorithm(topology: MigrationTopology, generations: usize) -> IslandNSGAII {
+        let nsga2 = NSGAII::new(8, make_definitions(), 2, 0.2, 0.9);
+        IslandNSGAII::new(nsga2, 3, 2, 2, topology)
+            .with_seed(42)
+            .with_termination(Termination::max_generations(generations))
+    }
+
+    #[test]
+    fn test_topology_sources() {
+        assert_eq!(MigrationTopology::Ring.sources(0, 4), vec![3]);
+        assert_eq!(MigrationTopology::Ring.sources(2, 4), vec![1]);
+        assert_eq!(MigrationTopology::FullyConnected.sources(1, 3), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        for topology in [MigrationTopology::Ring, MigrationTopology::FullyConnected] {
+            let first = algorithm(topology, 5).run(evaluate, &vec![], &vec![], &vec![]);
+            let second = algorithm(topology, 5).run(evaluate, &vec![], &vec![], &vec![]);
+            assert_eq!(first.len(), 3 * 8);
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        let saved = Arc::new(Mutex::new(Vec::<Checkpoint>::new()));
+        let saved_clone = Arc::clone(&saved);
+        let checkpointing = Checkpointing::new(
+            2,
+            None,
+            Arc::new(move |checkpoint: &Checkpoint| {
+                saved_clone.lock().unwrap().push(checkpoint.clone())
+            }),
+        );
+        let uninterrupted = algorithm(MigrationTopology::Ring, 6)
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        let checkpoint = saved.lock().unwrap()[0].clone();
+        assert_eq!(checkpoint.iteration, 2);
+        assert!(matches!(
+            &checkpoint.state,
+            CheckpointState::IslandNSGAII { island_rngs } if island_rngs.len() == 3
+        ));
+        let checkpointing = Checkpointing::new(2, Some(checkpoint), Arc::new(|_: &Checkpoint| {}));
+        let resumed = algorithm(MigrationTopology::Ring, 6)
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        assert_eq!(uninterrupted, resumed);
+    }
+
+    #[test]
+    fn test_checkpoint_of_another_algorithm_ignored() {
+        let saved = Arc::new(Mutex::new(Vec::<Checkpoint>::new()));
+        let saved_clone = Arc::clone(&saved);
+        let checkpointing = Checkpointing::new(
+            2,
+            None,
+            Arc::new(move |checkpoint: &Checkpoint| {
+                saved_clone.lock().unwrap().push(checkpoint.clone())
+            }),
+        );
+        NSGAII::new(8, make_definitions(), 2, 0.2, 0.9)
+            .with_seed(42)
+            .with_termination(Termination::max_generations(2))
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        // The NSGA-II checkpoint has no generator for the islands : the run starts over
+        let checkpoint = saved.lock().unwrap()[0].clone();
+        let checkpointing = Checkpointing::new(2, Some(checkpoint), Arc::new(|_: &Checkpoint| {}));
+        let resumed = algorithm(MigrationTopology::Ring, 4)
+            .with_checkpointing(checkpointing)
+            .run(evaluate, &vec![], &vec![], &vec![]);
+
+        assert_eq!(
+            resumed,
+            algorithm(MigrationTopology::Ring, 4).run(evaluate, &vec![], &vec![], &vec![])
+        );
+    }
+
+    #[test]
+    fn test_evaluations_on_the_runtime() {
+        // Like a task, the run is inside the block_on of a multi-thread runtime and its
+        // evaluations block on that runtime
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .enable_all()
+            .build()
+            .unwrap();
+        let solutions = runtime.block_on(async {
+            algorithm(MigrationTopology::Ring, 4).run(
+                |variables, kline_collections, indicators, variable_definitions_sep| {
+                    crate::utils::runtime::block_on(tokio::task::yield_now());
+                    evaluate(
+                        variables,
+                        kline_collections,
+                        indicators,
+                        variable_definitions_sep,
+                    )
+                },
+                &vec![],
+                &vec![],
+                &vec![],
+            )
+        });
+
+        assert_eq!(
+            solutions,
+            algorithm(MigrationTopology::Ring, 4).run(evaluate, &vec![], &vec![], &vec![])
+        );
+    }
+}
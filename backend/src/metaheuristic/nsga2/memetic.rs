@@ -0,0 +1,165 @@
+use rand::prelude::*;
+
+use crate::metaheuristic::descent::MultiObjectiveDescent;
+use crate::metaheuristic::objects::{Evaluation, Solution, Variable, VariableDefinitionInfo};
+use crate::metaheuristic::termination::parse_optional;
+
+const LOCAL_SEARCH_SIZE_DEFAULT: usize = 5;
+const LOCAL_SEARCH_STEPS_DEFAULT: usize = 10;
+const LOCAL_SEARCH_STEP_SIZE_DEFAULT: f64 = 1.0;
+
+/// Local search applied periodically to the first front of NSGA-II (memetic algorithm),
+/// with the neighbourhood of the descent
+#[derive(Clone, Debug)]
+pub struct LocalSearch {
+    pub interval: usize, // generations between two local searches
+    pub size: usize,     // members of the first front refined
+    pub steps: usize,    // neighbors evaluated for each member
+    pub step_size: f64,  // step size of the neighbourhood
+}
+
+impl LocalSearch {
+    pub fn new(interval: usize) -> Self {
+        LocalSearch {
+            interval: interval.max(1),
+            size: LOCAL_SEARCH_SIZE_DEFAULT,
+            steps: LOCAL_SEARCH_STEPS_DEFAULT,
+            step_size: LOCAL_SEARCH_STEP_SIZE_DEFAULT,
+        }
+    }
+
+    /// Parse the local search from the parameters of an algorithm (None if no interval is given)
+    pub fn from_json(json: &serde_json::Value) -> Result<Option<Self>, String> {
+        let interval: usize = match parse_optional(json, "local_search_interval")? {
+            Some(interval) if interval > 0 => interval,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(LocalSearch {
+            size: parse_optional(json, "local_search_size")?.unwrap_or(LOCAL_SEARCH_SIZE_DEFAULT),
+            steps: parse_optional(json, "local_search_steps")?
+                .unwrap_or(LOCAL_SEARCH_STEPS_DEFAULT),
+            step_size: parse_optional(json, "local_search_step_size")?
+                .unwrap_or(LOCAL_SEARCH_STEP_SIZE_DEFAULT),
+            ..LocalSearch::new(interval)
+        }))
+    }
+
+    /// Parameters information for the local search
+    pub fn get_info() -> Vec<VariableDefinitionInfo> {
+        vec![
+            VariableDefinitionInfo {
+                name: "local_search_interval".to_string(),
+                description: "Generations between two local searches on the first front (optional, no local search if empty)".to_string(),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "local_search_size".to_string(),
+                description: format!(
+                    "Members of the first front refined by the local search (optional, {} by default)",
+                    LOCAL_SEARCH_SIZE_DEFAULT
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "local_search_steps".to_string(),
+                description: format!(
+                    "Neighbors evaluated for each refined member (optional, {} by default)",
+                    LOCAL_SEARCH_STEPS_DEFAULT
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "local_search_step_size".to_string(),
+                description: format!(
+                    "Step size of the local search neighbourhood (optional, {} by default)",
+                    LOCAL_SEARCH_STEP_SIZE_DEFAULT
+                ),
+                variable_type: "float".to_string(),
+                bounds: Some((0.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+        ]
+    }
+
+    pub fn is_due(&self, generation: usize) -> bool {
+        generation.checked_rem(self.interval) == Some(0)
+    }
+
+    /// Descent from the best members of the first front (population sorted, best first) :
+    /// returns the neighbors that entered the archive of the front, and the number of evaluations
+    pub fn refine(
+        &self,
+        descent: &MultiObjectiveDescent,
+        population: &[Solution],
+        rng: &mut impl Rng,
+        evaluate: impl Fn(&[Variable]) -> Evaluation,
+    ) -> (Vec<Solution>, usize) {
+        let mut archive: Vec<Solution> = population
+            .iter()
+            .filter(|solution| solution.rank == 0)
+            .cloned()
+            .collect();
+        let members: Vec<Solution> = archive.iter().take(self.size).cloned().collect();
+
+        let mut improved: Vec<Solution> = Vec::new();
+        let mut evaluations = 0;
+        for member in members {
+            let mut current = member;
+            for _ in 0..self.steps {
+                let mut neighbor = descent.generate_neighbor(&current, self.step_size, rng);
+                neighbor.apply_evaluation(evaluate(&neighbor.variables));
+                evaluations += 1;
+
+                // Keep moving from the neighbors that are not dominated by the front
+                if descent.update_archive(&mut archive, neighbor.clone()) {
+                    improved.push(neighbor.clone());
+                    current = neighbor;
+                }
+            }
+        }
+
+        (improved, evaluations)
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        assert!(LocalSearch::from_json(&serde_json::json!({}))
+            .unwrap()
+            .is_none());
+        assert!(
+            LocalSearch::from_json(&serde_json::json!({ "local_search_interval": "0" }))
+                .unwrap()
+                .is_none()
+        );
+
+        let local_search = LocalSearch::from_json(&serde_json::json!({
+            "local_search_interval": "3",
+            "local_search_steps": 4
+        }))
+        .unwrap()
+        .unwrap();
+        assert_eq!(local_search.interval, 3);
+        assert_eq!(local_search.steps, 4);
+        assert_eq!(local_search.size, LOCAL_SEARCH_SIZE_DEFAULT);
+        assert!(local_search.is_due(6));
+        assert!(!local_search.is_due(7));
+    }
+}
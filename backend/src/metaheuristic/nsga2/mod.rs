@@ -1,3 +1,7 @@
 mod implementation;
+mod island;
+mod memetic;
 
 pub use implementation::NSGAII;
+pub use island::{IslandNSGAII, MigrationTopology};
+pub use memetic::LocalSearch;
@@ -1,15 +1,18 @@
 use serde::{Deserialize, Serialize};
 
+use super::checkpoint::Checkpointing;
 use super::descent::MultiObjectiveDescent;
-use super::nsga2::NSGAII;
+use super::nsga2::{IslandNSGAII, NSGAII};
+use super::progress::ProgressReporter;
 use crate::objects::{indicators::Indicator, klines::KlineCollection};
 
 /// Represents a variable in the optimization problem
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Variable {
     Float(f64),
     Integer(i64),
     Boolean(bool),
+    Categorical(usize), // index of the choice
 }
 
 /// Defines the bounds and type for each variable
@@ -18,13 +21,113 @@ pub enum VariableDefinition {
     Float(f64, f64),   // (min, max)
     Integer(i64, i64), // (min, max)
     Boolean,
+    Categorical(Vec<String>),    // choices (ex: ["SMA", "EMA", "WMA"])
+    SteppedFloat(f64, f64, f64), // (min, max, step)
 }
 
-/// Represents a solution in the optimization problem
+impl VariableDefinition {
+    /// Clamp a float value to the bounds (and round it to the grid for stepped floats)
+    pub fn quantize(&self, value: f64) -> f64 {
+        match self {
+            VariableDefinition::Float(min, max) => value.clamp(*min, *max),
+            VariableDefinition::Integer(min, max) => value.round().clamp(*min as f64, *max as f64),
+            VariableDefinition::SteppedFloat(min, _, step) => {
+                let step_index = ((value - min) / step)
+                    .round()
+                    .clamp(0.0, self.get_steps_count() as f64);
+                min + step_index * step
+            }
+            _ => value,
+        }
+    }
+
+    /// Bring a variable back into the domain of the definition (None if the types do not match)
+    pub fn fit(&self, variable: &Variable) -> Option<Variable> {
+        match (self, variable) {
+            (VariableDefinition::Float(..), Variable::Float(value))
+            | (VariableDefinition::SteppedFloat(..), Variable::Float(value)) => {
+                Some(Variable::Float(self.quantize(*value)))
+            }
+            (VariableDefinition::Integer(min, max), Variable::Integer(value)) => {
+                Some(Variable::Integer((*value).clamp(*min, *max)))
+            }
+            (VariableDefinition::Boolean, Variable::Boolean(value)) => {
+                Some(Variable::Boolean(*value))
+            }
+            (VariableDefinition::Categorical(choices), Variable::Categorical(index))
+                if *index < choices.len() =>
+            {
+                Some(Variable::Categorical(*index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of steps between the min and the max of a stepped float
+    pub fn get_steps_count(&self) -> i64 {
+        match self {
+            VariableDefinition::SteppedFloat(min, max, step) if *step > 0.0 => {
+                ((max - min) / step + 1e-9).floor() as i64
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// Variables of previous solutions that fit the variable definitions (the others are dropped),
+/// used to start a run from the results of a previous one
+pub fn fit_solutions_variables(
+    solutions_variables: &[Vec<Variable>],
+    variable_definitions: &[VariableDefinition],
+) -> Vec<Vec<Variable>> {
+    solutions_variables
+        .iter()
+        .filter(|variables| variables.len() == variable_definitions.len())
+        .filter_map(|variables| {
+            variables
+                .iter()
+                .zip(variable_definitions)
+                .map(|(variable, definition)| definition.fit(variable))
+                .collect::<Option<Vec<Variable>>>()
+        })
+        .collect()
+}
+
+/// Result of the evaluation of a solution
+/// (a constraint violation is 0.0 when the constraint is satisfied, positive otherwise)
 #[derive(Clone, Debug)]
+pub struct Evaluation {
+    pub objectives: Vec<f64>,
+    pub constraint_violations: Vec<f64>,
+}
+
+impl Evaluation {
+    pub fn new(objectives: Vec<f64>) -> Self {
+        Evaluation {
+            objectives,
+            constraint_violations: Vec::new(),
+        }
+    }
+
+    pub fn with_constraint_violations(
+        objectives: Vec<f64>,
+        constraint_violations: Vec<f64>,
+    ) -> Self {
+        Evaluation {
+            objectives,
+            constraint_violations,
+        }
+    }
+}
+
+/// Represents a solution in the optimization problem
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Solution {
     pub variables: Vec<Variable>,
     pub objectives: Vec<f64>,
+    pub constraint_violations: Vec<f64>,
+    #[serde(skip_deserializing)]
+    // may be infinite (serialized as null), computed again when needed
     pub crowding_distance: f64,
     pub rank: usize,
 }
@@ -34,11 +137,55 @@ impl Solution {
         Solution {
             variables,
             objectives,
+            constraint_violations: Vec::new(),
             crowding_distance: 0.0,
             rank: 0,
         }
     }
 
+    /// Store the objectives and constraint violations of an evaluation
+    pub fn apply_evaluation(&mut self, evaluation: Evaluation) {
+        self.objectives = evaluation.objectives;
+        self.constraint_violations = evaluation.constraint_violations;
+    }
+
+    /// Sum of all the constraint violations (0.0 if the solution is feasible)
+    pub fn total_violation(&self) -> f64 {
+        self.constraint_violations
+            .iter()
+            .map(|violation| violation.max(0.0))
+            .sum()
+    }
+
+    pub fn is_feasible(&self) -> bool {
+        self.total_violation() <= 0.0
+    }
+
+    /// Check if this solution dominates the other one using constrained-domination (Deb's rules) :
+    /// - a feasible solution dominates an infeasible one
+    /// - between two infeasible solutions, the one with the smaller total violation dominates
+    /// - between two feasible solutions, the usual Pareto dominance is used (minimization)
+    pub fn constrained_dominates(&self, other: &Solution, num_objectives: usize) -> bool {
+        let violation_self = self.total_violation();
+        let violation_other = other.total_violation();
+        if violation_self > 0.0 || violation_other > 0.0 {
+            return violation_self < violation_other;
+        }
+
+        let mut one_is_better = false;
+
+        for i in 0..num_objectives {
+            if self.objectives[i] > other.objectives[i] {
+                return false;
+            }
+            if self.objectives[i] < other.objectives[i] {
+                one_is_better = true;
+            }
+        }
+
+        one_is_better
+    }
+
     pub fn show_short(&self) -> String {
         let vars = self
             .variables
@@ -47,6 +194,7 @@ impl Solution {
                 Variable::Float(f) => f.to_string(),
                 Variable::Integer(i) => i.to_string(),
                 Variable::Boolean(b) => b.to_string(),
+                Variable::Categorical(c) => format!("#{}", c),
             })
             .collect::<Vec<String>>()
             .join(", ");
@@ -57,8 +205,11 @@ impl Solution {
             .collect::<Vec<String>>()
             .join(", ");
         format!(
-            "Variables: [{}], Objectives: [{}], Rank: {}",
-            vars, objs, self.rank
+            "Variables: [{}], Objectives: [{}], Violation: {}, Rank: {}",
+            vars,
+            objs,
+            self.total_violation(),
+            self.rank
         )
     }
 }
@@ -69,6 +220,39 @@ pub struct VariableDefinitionInfo {
     pub description: String,
     pub variable_type: String,
     pub bounds: Option<(f64, f64)>,
+    pub step: Option<f64>,
+    pub choices: Option<Vec<String>>,
+}
+
+impl VariableDefinitionInfo {
+    pub fn from_definition(
+        name: &str,
+        description: &str,
+        definition: &VariableDefinition,
+    ) -> VariableDefinitionInfo {
+        let (variable_type, bounds, step, choices) = match definition {
+            VariableDefinition::Float(min, max) => ("float", Some((*min, *max)), None, None),
+            VariableDefinition::Integer(min, max) => {
+                ("integer", Some((*min as f64, *max as f64)), None, None)
+            }
+            VariableDefinition::Boolean => ("boolean", None, None, None),
+            VariableDefinition::Categorical(choices) => {
+                ("categorical", None, None, Some(choices.clone()))
+            }
+            VariableDefinition::SteppedFloat(min, max, step) => {
+                ("stepped_float", Some((*min, *max)), Some(*step), None)
+            }
+        };
+
+        VariableDefinitionInfo {
+            name: name.to_string(),
+            description: description.to_string(),
+            variable_type: variable_type.to_string(),
+            bounds,
+            step,
+            choices,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -81,25 +265,131 @@ pub struct MetaheuristicInfo {
 #[derive(Clone, Debug)]
 pub enum Metaheuristic {
     NSGAII(NSGAII),
+    IslandNSGAII(IslandNSGAII),
     MultiObjectiveDescent(MultiObjectiveDescent),
 }
 
 impl Metaheuristic {
     pub fn get_all_info() -> Vec<MetaheuristicInfo> {
-        vec![NSGAII::get_info(), MultiObjectiveDescent::get_info()]
+        vec![
+            NSGAII::get_info(),
+            IslandNSGAII::get_info(),
+            MultiObjectiveDescent::get_info(),
+        ]
+    }
+
+    pub fn with_checkpointing(self, checkpointing: Checkpointing) -> Self {
+        match self {
+            Metaheuristic::NSGAII(algorithm) => {
+                Metaheuristic::NSGAII(algorithm.with_checkpointing(checkpointing))
+            }
+            Metaheuristic::IslandNSGAII(algorithm) => {
+                Metaheuristic::IslandNSGAII(algorithm.with_checkpointing(checkpointing))
+            }
+            Metaheuristic::MultiObjectiveDescent(algorithm) => {
+                Metaheuristic::MultiObjectiveDescent(algorithm.with_checkpointing(checkpointing))
+            }
+        }
+    }
+
+    pub fn with_progress_reporter(self, progress_reporter: ProgressReporter) -> Self {
+        match self {
+            Metaheuristic::NSGAII(algorithm) => {
+                Metaheuristic::NSGAII(algorithm.with_progress_reporter(progress_reporter))
+            }
+            Metaheuristic::IslandNSGAII(algorithm) => {
+                Metaheuristic::IslandNSGAII(algorithm.with_progress_reporter(progress_reporter))
+            }
+            Metaheuristic::MultiObjectiveDescent(algorithm) => {
+                Metaheuristic::MultiObjectiveDescent(
+                    algorithm.with_progress_reporter(progress_reporter),
+                )
+            }
+        }
+    }
+
+    /// Start the run from the variables of previous solutions
+    pub fn with_initial_solutions(self, initial_solutions: Vec<Vec<Variable>>) -> Self {
+        match self {
+            Metaheuristic::NSGAII(algorithm) => {
+                Metaheuristic::NSGAII(algorithm.with_initial_solutions(initial_solutions))
+            }
+            Metaheuristic::IslandNSGAII(algorithm) => {
+                Metaheuristic::IslandNSGAII(algorithm.with_initial_solutions(initial_solutions))
+            }
+            Metaheuristic::MultiObjectiveDescent(algorithm) => {
+                Metaheuristic::MultiObjectiveDescent(
+                    algorithm.with_initial_solutions(initial_solutions),
+                )
+            }
+        }
+    }
+
+    /// Seed of the random number generator used by the algorithm
+    pub fn get_seed(&self) -> u64 {
+        match self {
+            Metaheuristic::NSGAII(algorithm) => algorithm.get_seed(),
+            Metaheuristic::IslandNSGAII(algorithm) => algorithm.get_seed(),
+            Metaheuristic::MultiObjectiveDescent(algorithm) => algorithm.get_seed(),
+        }
+    }
+}
+
+/// Largest seed accepted, so that it survives a round trip through JSON numbers (2^53 - 1)
+pub const MAX_SEED: u64 = (1 << 53) - 1;
+
+/// Parse the optional "seed" parameter of an algorithm (a random seed is drawn if missing or empty)
+pub fn parse_seed(json: &serde_json::Value) -> Result<u64, String> {
+    let seed = match json.get("seed") {
+        Some(serde_json::Value::Number(number)) => Some(
+            number
+                .as_u64()
+                .ok_or_else(|| "Invalid seed for the algorithm".to_string())?,
+        ),
+        Some(serde_json::Value::String(string)) if string.trim().is_empty() => None,
+        Some(serde_json::Value::String(string)) => Some(
+            string
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| "Invalid seed for the algorithm".to_string())?,
+        ),
+        Some(serde_json::Value::Null) | None => None,
+        Some(_) => return Err("Invalid seed for the algorithm".to_string()),
+    };
+
+    match seed {
+        Some(seed) if seed > MAX_SEED => Err(format!("The seed must be at most {}", MAX_SEED)),
+        Some(seed) => Ok(seed),
+        None => Ok(random_seed()),
+    }
+}
+
+/// Draw a random seed (at most MAX_SEED)
+pub fn random_seed() -> u64 {
+    rand::random::<u64>() >> 11
+}
+
+/// Parameter information for the seed of the random number generator
+pub fn seed_info() -> VariableDefinitionInfo {
+    VariableDefinitionInfo {
+        name: "seed".to_string(),
+        description: "Seed of the random number generator (optional, random if empty)".to_string(),
+        variable_type: "integer".to_string(),
+        bounds: Some((0.0, MAX_SEED as f64)),
+        step: None,
+        choices: None,
     }
 }
 
 pub trait MetaheuristicTrait {
     fn run(
         &self,
-        num_generations: usize,
         evaluate: impl Fn(
                 &[Variable],
                 &Vec<KlineCollection>,
                 &Vec<Indicator>,
                 &Vec<Vec<VariableDefinition>>,
-            ) -> Vec<f64>
+            ) -> Evaluation
             + Clone
             + Sync
             + Send,
@@ -112,13 +402,12 @@ pub trait MetaheuristicTrait {
 impl MetaheuristicTrait for Metaheuristic {
     fn run(
         &self,
-        num_generations: usize,
         evaluate: impl Fn(
                 &[Variable],
                 &Vec<KlineCollection>,
                 &Vec<Indicator>,
                 &Vec<Vec<VariableDefinition>>,
-            ) -> Vec<f64>
+            ) -> Evaluation
             + Clone
             + Sync
             + Send,
@@ -128,14 +417,18 @@ impl MetaheuristicTrait for Metaheuristic {
     ) -> Vec<Solution> {
         match self {
             Metaheuristic::MultiObjectiveDescent(simple_descent) => simple_descent.run(
-                num_generations,
                 evaluate,
                 kline_collections,
                 indicators,
                 variable_definitions_sep,
             ),
             Metaheuristic::NSGAII(nsga2) => nsga2.run(
-                num_generations,
+                evaluate,
+                kline_collections,
+                indicators,
+                variable_definitions_sep,
+            ),
+            Metaheuristic::IslandNSGAII(island_nsga2) => island_nsga2.run(
                 evaluate,
                 kline_collections,
                 indicators,
@@ -144,3 +437,78 @@ impl MetaheuristicTrait for Metaheuristic {
         }
     }
 }
+
+//// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metaheuristic::test_utils::solution_with;
+
+    #[test]
+    fn test_constrained_dominates_feasible() {
+        let a = solution_with(vec![1.0, 2.0], vec![0.0]);
+        let b = solution_with(vec![2.0, 2.0], vec![0.0]);
+        assert!(a.constrained_dominates(&b, 2));
+        assert!(!b.constrained_dominates(&a, 2));
+        assert!(!a.constrained_dominates(&a, 2));
+    }
+
+    #[test]
+    fn test_constrained_dominates_infeasible() {
+        let feasible = solution_with(vec![10.0, 10.0], vec![0.0, 0.0]);
+        let infeasible = solution_with(vec![0.0, 0.0], vec![0.5, 0.0]);
+        let more_infeasible = solution_with(vec![0.0, 0.0], vec![0.5, 0.5]);
+
+        assert!(feasible.is_feasible());
+        assert!(!infeasible.is_feasible());
+        assert!(feasible.constrained_dominates(&infeasible, 2));
+        assert!(!infeasible.constrained_dominates(&feasible, 2));
+        assert!(infeasible.constrained_dominates(&more_infeasible, 2));
+        assert!(!more_infeasible.constrained_dominates(&infeasible, 2));
+    }
+
+    #[test]
+    fn test_stepped_float_quantize() {
+        let definition = VariableDefinition::SteppedFloat(1.0, 3.2, 0.5);
+        assert_eq!(definition.get_steps_count(), 4);
+        assert_eq!(definition.quantize(1.7), 1.5);
+        assert_eq!(definition.quantize(2.3), 2.5);
+        assert_eq!(definition.quantize(10.0), 3.0);
+        assert_eq!(definition.quantize(-1.0), 1.0);
+    }
+
+    #[test]
+    fn test_fit_solutions_variables() {
+        let definitions = vec![
+            VariableDefinition::Integer(2, 50),
+            VariableDefinition::Categorical(vec!["SMA".to_string(), "EMA".to_string()]),
+        ];
+        let fitted = fit_solutions_variables(
+            &[
+                vec![Variable::Integer(60), Variable::Categorical(1)],
+                vec![Variable::Float(10.0), Variable::Categorical(0)], // wrong type
+                vec![Variable::Integer(10), Variable::Categorical(2)], // unknown choice
+                vec![Variable::Integer(10)],                           // wrong layout
+            ],
+            &definitions,
+        );
+        assert_eq!(
+            fitted,
+            vec![vec![Variable::Integer(50), Variable::Categorical(1)]]
+        );
+    }
+
+    #[test]
+    fn test_parse_seed_bounds() {
+        assert_eq!(
+            parse_seed(&serde_json::json!({"seed": MAX_SEED})),
+            Ok(MAX_SEED)
+        );
+        assert_eq!(
+            parse_seed(&serde_json::json!({"seed": (MAX_SEED + 1).to_string()})),
+            Err(format!("The seed must be at most {}", MAX_SEED))
+        );
+        assert!(random_seed() <= MAX_SEED);
+        assert_eq!(seed_info().bounds, Some((0.0, 9007199254740991.0)));
+    }
+}
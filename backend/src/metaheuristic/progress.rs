@@ -0,0 +1,81 @@
+use serde::Serialize;
+use std::fmt;
+use std::sync::Arc;
+
+use super::hypervolume::solutions_hypervolume;
+use super::objects::Solution;
+
+/// Progress of a run, reported after each generation (or iteration)
+#[derive(Clone, Debug, Serialize)]
+pub struct Progress {
+    pub generation: usize,
+    pub total_generations: Option<usize>,
+    pub evaluations: usize,
+    pub best_objectives: Vec<f64>, // best value of each objective (feasible solutions if any)
+    pub hypervolume: f64,
+    pub elapsed_seconds: f64,
+    pub eta_seconds: Option<f64>,
+}
+
+impl Progress {
+    pub fn new(
+        generation: usize,
+        total_generations: Option<usize>,
+        evaluations: usize,
+        solutions: &[Solution],
+        reference_point: &[f64],
+    ) -> Self {
+        let feasible: Vec<&Solution> = solutions.iter().filter(|s| s.is_feasible()).collect();
+        let candidates = if feasible.is_empty() {
+            solutions.iter().collect()
+        } else {
+            feasible
+        };
+        let best_objectives = (0..reference_point.len())
+            .map(|m| {
+                candidates
+                    .iter()
+                    .map(|solution| solution.objectives[m])
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+
+        Progress {
+            generation,
+            total_generations,
+            evaluations,
+            best_objectives,
+            hypervolume: solutions_hypervolume(solutions, reference_point),
+            elapsed_seconds: 0.0,
+            eta_seconds: None,
+        }
+    }
+
+    pub fn with_timing(mut self, elapsed_seconds: f64, eta_seconds: Option<f64>) -> Self {
+        self.elapsed_seconds = elapsed_seconds;
+        self.eta_seconds = eta_seconds;
+        self
+    }
+}
+
+/// Receives the progress of a run
+#[derive(Clone)]
+pub struct ProgressReporter {
+    callback: Arc<dyn Fn(&Progress) + Send + Sync>,
+}
+
+impl ProgressReporter {
+    pub fn new(callback: Arc<dyn Fn(&Progress) + Send + Sync>) -> Self {
+        ProgressReporter { callback }
+    }
+
+    pub fn report(&self, progress: &Progress) {
+        (self.callback)(progress);
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressReporter").finish()
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Instant;
+
+use super::objects::VariableDefinitionInfo;
+
+pub const DEFAULT_MAX_GENERATIONS: usize = 20;
+const DEFAULT_STAGNATION_TOLERANCE: f64 = 1e-6;
+
+/// Conditions ending a run (the run stops as soon as one of them is met)
+#[derive(Clone, Debug)]
+pub struct Termination {
+    pub max_generations: Option<usize>, // generations for NSGA-II, iterations for the descent
+    pub max_evaluations: Option<usize>,
+    pub max_seconds: Option<f64>,
+    pub stagnation_generations: Option<usize>, // generations without hypervolume improvement
+    pub stagnation_tolerance: f64,             // minimal relative improvement of the hypervolume
+}
+
+impl Termination {
+    pub fn max_generations(max_generations: usize) -> Self {
+        Termination {
+            max_generations: Some(max_generations),
+            max_evaluations: None,
+            max_seconds: None,
+            stagnation_generations: None,
+            stagnation_tolerance: DEFAULT_STAGNATION_TOLERANCE,
+        }
+    }
+
+    /// Parse the termination criteria from the parameters of an algorithm
+    /// (the run is limited to DEFAULT_MAX_GENERATIONS if none is given)
+    pub fn from_json(json: &serde_json::Value) -> Result<Self, String> {
+        let termination = Termination {
+            max_generations: parse_optional(json, "max_generations")?,
+            max_evaluations: parse_optional(json, "max_evaluations")?,
+            max_seconds: parse_optional(json, "max_duration_seconds")?,
+            stagnation_generations: parse_optional(json, "hypervolume_stagnation_generations")?,
+            stagnation_tolerance: parse_optional(json, "hypervolume_stagnation_tolerance")?
+                .unwrap_or(DEFAULT_STAGNATION_TOLERANCE),
+        };
+
+        if termination.max_generations.is_none()
+            && termination.max_evaluations.is_none()
+            && termination.max_seconds.is_none()
+            && termination.stagnation_generations.is_none()
+        {
+            return Ok(Termination {
+                max_generations: Some(DEFAULT_MAX_GENERATIONS),
+                ..termination
+            });
+        }
+
+        Ok(termination)
+    }
+
+    /// Parameters information for the termination criteria
+    pub fn get_info() -> Vec<VariableDefinitionInfo> {
+        vec![
+            VariableDefinitionInfo {
+                name: "max_generations".to_string(),
+                description: format!(
+                    "Maximum number of generations (iterations for a descent) (optional, {} if no criterion is set)",
+                    DEFAULT_MAX_GENERATIONS
+                ),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "max_evaluations".to_string(),
+                description: "Maximum number of evaluations (optional)".to_string(),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "max_duration_seconds".to_string(),
+                description: "Maximum duration of the run in seconds (optional)".to_string(),
+                variable_type: "float".to_string(),
+                bounds: Some((0.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "hypervolume_stagnation_generations".to_string(),
+                description: "Stop after this many generations without hypervolume improvement (optional)"
+                    .to_string(),
+                variable_type: "integer".to_string(),
+                bounds: Some((1.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+            VariableDefinitionInfo {
+                name: "hypervolume_stagnation_tolerance".to_string(),
+                description: format!(
+                    "Minimal relative hypervolume improvement (optional, {:e} by default)",
+                    DEFAULT_STAGNATION_TOLERANCE
+                ),
+                variable_type: "float".to_string(),
+                bounds: Some((0.0, f64::INFINITY)),
+                step: None,
+                choices: None,
+            },
+        ]
+    }
+
+    /// Update the state with the last generation and check if the run should stop
+    pub fn should_stop(
+        &self,
+        state: &mut TerminationState,
+        generation: usize,
+        evaluations: usize,
+        hypervolume: f64,
+    ) -> bool {
+        if hypervolume
+            > state.best_hypervolume + self.stagnation_tolerance * state.best_hypervolume.abs()
+        {
+            state.best_hypervolume = hypervolume;
+            state.last_improvement = generation;
+        }
+
+        self.max_generations.is_some_and(|max| generation >= max)
+            || self.max_evaluations.is_some_and(|max| evaluations >= max)
+            || self
+                .max_seconds
+                .is_some_and(|max| state.elapsed_seconds() >= max)
+            || self
+                .stagnation_generations
+                .is_some_and(|max| generation - state.last_improvement >= max)
+    }
+
+    /// Estimated remaining time, from the pace since the run was (re)started and the closest limit
+    pub fn eta_seconds(
+        &self,
+        state: &TerminationState,
+        generation: usize,
+        evaluations: usize,
+    ) -> Option<f64> {
+        let seconds = state.start.elapsed().as_secs_f64();
+        let generations_done = generation.saturating_sub(state.first_generation);
+        let evaluations_done = evaluations.saturating_sub(state.first_evaluations);
+
+        let mut estimates: Vec<f64> = Vec::new();
+        if let (Some(max), true) = (self.max_generations, generations_done > 0) {
+            estimates
+                .push(seconds / generations_done as f64 * max.saturating_sub(generation) as f64);
+        }
+        if let (Some(max), true) = (self.max_evaluations, evaluations_done > 0) {
+            estimates
+                .push(seconds / evaluations_done as f64 * max.saturating_sub(evaluations) as f64);
+        }
+        if let Some(max) = self.max_seconds {
+            estimates.push((max - state.elapsed_seconds()).max(0.0));
+        }
+
+        estimates.into_iter().reduce(f64::min)
+    }
+}
+
+/// Progress of a run towards its termination, saved in the checkpoints
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TerminationState {
+    #[serde(skip, default = "Instant::now")]
+    start: Instant,
+    #[serde(skip)]
+    first_generation: usize,
+    #[serde(skip)]
+    first_evaluations: usize,
+    previous_seconds: f64, // time spent before the run was resumed
+    best_hypervolume: f64,
+    last_improvement: usize, // generation of the last hypervolume improvement
+}
+
+impl Default for TerminationState {
+    fn default() -> Self {
+        TerminationState {
+            start: Instant::now(),
+            first_generation: 0,
+            first_evaluations: 0,
+            previous_seconds: 0.0,
+            best_hypervolume: 0.0,
+            last_improvement: 0,
+        }
+    }
+}
+
+impl TerminationState {
+    /// Start (or resume) the clock at the given generation
+    pub fn started_at(mut self, generation: usize, evaluations: usize) -> Self {
+        self.start = Instant::now();
+        self.first_generation = generation;
+        self.first_evaluations = evaluations;
+        self
+    }
+
+    /// Time spent on the run, including before it was resumed
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.previous_seconds + self.start.elapsed().as_secs_f64()
+    }
+
+    /// State to save in a checkpoint
+    pub fn snapshot(&self) -> Self {
+        TerminationState {
+            previous_seconds: self.elapsed_seconds(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Parse an optional parameter, given as a number or a string (empty strings are ignored)
+pub fn parse_optional<T: FromStr>(
+    json: &serde_json::Value,
+    key: &str,
+) -> Result<Option<T>, String> {
+    let value = match json.get(key) {
+        Some(serde_json::Value::String(string)) => string.trim().to_string(),
+        Some(serde_json::Value::Number(number)) => number.to_string(),
+        Some(serde_json::Value::Bool(boolean)) => boolean.to_string(),
+        Some(serde_json::Value::Null) | None => return Ok(None),
+        Some(_) => return Err(format!("Invalid parameter {} for the algorithm", key)),
+    };
+
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse::<T>()
+        .map(Some)
+        .map_err(|_| format!("Invalid parameter {} for the algorithm", key))
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        let termination = Termination::from_json(&serde_json::json!({})).unwrap();
+        assert_eq!(termination.max_generations, Some(DEFAULT_MAX_GENERATIONS));
+
+        let termination = Termination::from_json(&serde_json::json!({
+            "max_evaluations": "500",
+            "max_duration_seconds": 60,
+            "max_generations": ""
+        }))
+        .unwrap();
+        assert_eq!(termination.max_generations, None);
+        assert_eq!(termination.max_evaluations, Some(500));
+        assert_eq!(termination.max_seconds, Some(60.0));
+
+        assert!(Termination::from_json(&serde_json::json!({ "max_evaluations": "a" })).is_err());
+    }
+
+    #[test]
+    fn test_hypervolume_stagnation() {
+        let termination = Termination {
+            max_generations: Some(100),
+            stagnation_generations: Some(2),
+            ..Termination::max_generations(100)
+        };
+        let mut state = TerminationState::default().started_at(0, 0);
+
+        assert!(!termination.should_stop(&mut state, 1, 10, 1.0));
+        assert!(!termination.should_stop(&mut state, 2, 20, 2.0));
+        assert!(!termination.should_stop(&mut state, 3, 30, 2.0));
+        assert!(termination.should_stop(&mut state, 4, 40, 2.0));
+    }
+}
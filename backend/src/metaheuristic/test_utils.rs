@@ -0,0 +1,41 @@
+// Fixtures shared by the tests of the metaheuristics
+
+use super::objects::{Evaluation, Solution, Variable, VariableDefinition};
+use crate::objects::{indicators::Indicator, klines::KlineCollection};
+
+// A float and an integer, the variables of the test problem
+pub fn make_definitions() -> Vec<VariableDefinition> {
+    vec![
+        VariableDefinition::Float(-5.0, 5.0),
+        VariableDefinition::Integer(0, 10),
+    ]
+}
+
+// Test problem with two conflicting objectives : x² and (x - y)²
+// (y may be an integer or a float)
+pub fn evaluate(
+    vars: &[Variable],
+    _: &Vec<KlineCollection>,
+    _: &Vec<Indicator>,
+    _: &Vec<Vec<VariableDefinition>>,
+) -> Evaluation {
+    let y = match &vars[1] {
+        Variable::Integer(y) => *y as f64,
+        Variable::Float(y) => *y,
+        _ => panic!("Unexpected variable types"),
+    };
+    match &vars[0] {
+        Variable::Float(x) => Evaluation::new(vec![x.powi(2), (x - y).powi(2)]),
+        _ => panic!("Unexpected variable types"),
+    }
+}
+
+// Solution without variables, with the given evaluation
+pub fn solution_with(objectives: Vec<f64>, constraint_violations: Vec<f64>) -> Solution {
+    let mut solution = Solution::new(vec![], vec![]);
+    solution.apply_evaluation(Evaluation::with_constraint_violations(
+        objectives,
+        constraint_violations,
+    ));
+    solution
+}
@@ -5,6 +5,7 @@ use crate::objects::{
     criteria::Criterion,
     indicators::{
         IchimokuCloud, IndicatorInformation, IndicatorParameter, IndicatorTrait, Variable,
+        VariableConstraint,
     },
     klines::KlineCollection,
 };
@@ -65,6 +66,11 @@ impl IndicatorTrait for IchimokuCloud {
         Self::information()
     }
 
+    // conversion_period < base_period < lagging_span
+    fn get_params_constraints(&self) -> Vec<VariableConstraint> {
+        vec![VariableConstraint::Ascending(vec![0, 1, 2])]
+    }
+
     fn column_names(&self) -> Vec<String> {
         vec![
             format!(
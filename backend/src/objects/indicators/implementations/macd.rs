@@ -5,7 +5,7 @@ use crate::objects::{
     criteria::Criterion,
     indicators::{
         IndicatorInformation, IndicatorParameter, IndicatorTrait,
-        MovingAverageConvergenceDivergence, Variable,
+        MovingAverageConvergenceDivergence, Variable, VariableConstraint,
     },
     klines::KlineCollection,
 };
@@ -68,6 +68,11 @@ impl IndicatorTrait for MovingAverageConvergenceDivergence {
         Self::information()
     }
 
+    // short_period < long_period
+    fn get_params_constraints(&self) -> Vec<VariableConstraint> {
+        vec![VariableConstraint::Ascending(vec![0, 1])]
+    }
+
     fn column_names(&self) -> Vec<String> {
         vec![
             format!(
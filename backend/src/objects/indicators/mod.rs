@@ -10,7 +10,7 @@ pub use combination::*;
 use sqlx::postgres::PgRow;
 pub use types::*;
 
-use crate::metaheuristic::{Variable, VariableDefinition};
+use crate::metaheuristic::{Variable, VariableConstraint, VariableDefinition};
 use crate::objects::{criteria::Criterion, klines::KlineCollection};
 
 impl IndicatorTrait for Indicator {
@@ -52,6 +52,22 @@ impl IndicatorTrait for Indicator {
         }
     }
 
+    fn get_params_constraints(&self) -> Vec<VariableConstraint> {
+        match self {
+            Indicator::MovingAverage(indicator) => indicator.get_params_constraints(),
+            Indicator::ExponentialMovingAverage(indicator) => indicator.get_params_constraints(),
+            Indicator::RelativeStrengthIndex(indicator) => indicator.get_params_constraints(),
+            Indicator::MovingAverageConvergenceDivergence(indicator) => {
+                indicator.get_params_constraints()
+            }
+            Indicator::BollingerBands(indicator) => indicator.get_params_constraints(),
+            Indicator::FibonacciRetracement(indicator) => indicator.get_params_constraints(),
+            Indicator::StochasticOscillator(indicator) => indicator.get_params_constraints(),
+            Indicator::OnBalanceVolume(indicator) => indicator.get_params_constraints(),
+            Indicator::IchimokuCloud(indicator) => indicator.get_params_constraints(),
+        }
+    }
+
     fn get_all_variable_definitions(&self) -> Vec<VariableDefinition> {
         match self {
             Indicator::MovingAverage(indicator) => indicator.get_all_variable_definitions(),
@@ -1,8 +1,8 @@
 use serde::Serialize;
-use sqlx::postgres::PgRow;
+use sqlx::{postgres::PgRow, PgPool};
 
 use crate::binance;
-use crate::metaheuristic::{Variable, VariableDefinition};
+use crate::metaheuristic::{Variable, VariableConstraint, VariableDefinition};
 use crate::objects::{criteria::Criterion, klines::KlineCollection};
 
 #[derive(Debug, Clone)]
@@ -44,6 +44,11 @@ pub trait IndicatorTrait {
         variable_definitions
     }
 
+    // Returns the constraints between the parameters (indices of the parameters variable definitions)
+    fn get_params_constraints(&self) -> Vec<VariableConstraint> {
+        Vec::new()
+    }
+
     // Returns all the variable definitions
     fn get_all_variable_definitions(&self) -> Vec<VariableDefinition> {
         let mut variable_definitions = self.get_params_variable_definitions();
@@ -101,9 +106,10 @@ pub trait IndicatorTrait {
 impl Indicator {
     pub async fn retrieve(
         &mut self,
+        pool: &PgPool,
         kline_collection: &KlineCollection,
     ) -> Result<(), sqlx::Error> {
-        binance::indicators::retrieve::retrieve_indicator(self, &kline_collection).await
+        binance::indicators::retrieve::retrieve_indicator(pool, self, &kline_collection).await
     }
 
     pub fn get_all_indicators_info() -> Vec<IndicatorInformation> {
@@ -1,4 +1,5 @@
 // Import the necessary modules
+use chrono::{DateTime, Datelike, Months, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::Type;
 
@@ -43,6 +44,27 @@ impl CryptoInterval {
         }
     }
 
+    // Parse from Binance string (ex: "5m")
+    pub fn parse_from_binance(interval: &str) -> Option<CryptoInterval> {
+        match interval {
+            "1m" => Some(CryptoInterval::Int1m),
+            "5m" => Some(CryptoInterval::Int5m),
+            "15m" => Some(CryptoInterval::Int15m),
+            "30m" => Some(CryptoInterval::Int30m),
+            "1h" => Some(CryptoInterval::Int1h),
+            "2h" => Some(CryptoInterval::Int2h),
+            "4h" => Some(CryptoInterval::Int4h),
+            "6h" => Some(CryptoInterval::Int6h),
+            "8h" => Some(CryptoInterval::Int8h),
+            "12h" => Some(CryptoInterval::Int12h),
+            "1d" => Some(CryptoInterval::Int1d),
+            "3d" => Some(CryptoInterval::Int3d),
+            "1w" => Some(CryptoInterval::Int1w),
+            "1M" => Some(CryptoInterval::Int1M),
+            _ => None,
+        }
+    }
+
     // To string
     pub fn to_string(&self) -> String {
         match self {
@@ -102,4 +124,34 @@ impl CryptoInterval {
             CryptoInterval::Int1M => 43200,
         }
     }
+
+    // Open time of the kline containing the given time, with the Binance alignment
+    // (weeks start on Monday, months on their first day, the other intervals at multiples of their
+    // duration since the Unix epoch)
+    pub fn get_open_time(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        const DAY_MILLIS: i64 = 86_400_000;
+        let millis = time.timestamp_millis();
+        let open_time = match self {
+            CryptoInterval::Int1M => {
+                return Utc
+                    .with_ymd_and_hms(time.year(), time.month(), 1, 0, 0, 0)
+                    .unwrap()
+            }
+            // The Unix epoch is a Thursday
+            CryptoInterval::Int1w => {
+                let days = millis.div_euclid(DAY_MILLIS);
+                (days - (days + 3).rem_euclid(7)) * DAY_MILLIS
+            }
+            _ => millis - millis.rem_euclid(self.to_minutes() * 60_000),
+        };
+        DateTime::<Utc>::from_timestamp_millis(open_time).unwrap()
+    }
+
+    // Open time of the kline following the one opened at the given time
+    pub fn get_next_open_time(&self, open_time: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            CryptoInterval::Int1M => open_time.checked_add_months(Months::new(1)).unwrap(),
+            _ => open_time + chrono::Duration::minutes(self.to_minutes()),
+        }
+    }
 }
@@ -1,6 +1,7 @@
 // Import the necessary modules
 use chrono::{DateTime, Utc}; // NaiveDateTime
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 
 use super::intervals;
 use crate::binance::{self, klines};
@@ -175,6 +176,7 @@ impl KlineCollection {
 
     pub async fn retrieve_klines_simple(
         &mut self,
+        pool: &PgPool,
         symbol: &CryptoSymbol,
         interval: &intervals::CryptoInterval,
         limit_minutes: i64,
@@ -182,6 +184,7 @@ impl KlineCollection {
         force_fetch: bool,
     ) -> Result<(), sqlx::Error> {
         binance::klines::retrieve::retrieve_klines_simple(
+            pool,
             self,
             symbol,
             interval,
@@ -192,11 +195,34 @@ impl KlineCollection {
         .await
     }
 
+    // Klines opened in [start, end)
+    pub async fn retrieve_klines_range(
+        &mut self,
+        pool: &PgPool,
+        symbol: &CryptoSymbol,
+        interval: &intervals::CryptoInterval,
+        range: (DateTime<Utc>, DateTime<Utc>),
+        training_percentage: f64,
+        force_fetch: bool,
+    ) -> Result<(), sqlx::Error> {
+        binance::klines::retrieve::retrieve_klines_range(
+            pool,
+            self,
+            symbol,
+            interval,
+            range,
+            training_percentage,
+            force_fetch,
+        )
+        .await
+    }
+
     pub async fn retrieve_extended_klines(
         &mut self,
+        pool: &PgPool,
         indicator: &Indicator,
     ) -> Result<(), sqlx::Error> {
-        binance::indicators::retrieve::retrieve_extended_klines(self, indicator).await
+        binance::indicators::retrieve::retrieve_extended_klines(pool, self, indicator).await
     }
 }
 
@@ -204,6 +230,7 @@ impl KlineCollection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::objects::markets::MarketType;
     use chrono::{TimeZone, Utc};
 
     fn create_sample_kline(time: DateTime<Utc>) -> Kline {
@@ -231,6 +258,7 @@ mod tests {
             volume: 100.0,
             last_updated: Utc::now(),
             available: true,
+            market: MarketType::UsdM,
         };
         collection.interval = intervals::CryptoInterval::Int1m;
         collection.training_percentage = 0.7;
@@ -0,0 +1,74 @@
+// Import the necessary modules
+use serde::{Deserialize, Serialize};
+use sqlx::Type;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "market_type")]
+pub enum MarketType {
+    Spot,
+    #[default]
+    UsdM, // USDⓈ-M futures
+    CoinM, // COIN-M futures
+}
+
+impl MarketType {
+    // All the markets
+    pub fn all() -> Vec<MarketType> {
+        vec![MarketType::Spot, MarketType::UsdM, MarketType::CoinM]
+    }
+
+    // Parse from string
+    pub fn parse_from(market: &str) -> MarketType {
+        match market {
+            "Spot" => MarketType::Spot,
+            "UsdM" => MarketType::UsdM,
+            "CoinM" => MarketType::CoinM,
+            _ => MarketType::UsdM,
+        }
+    }
+
+    // Base URL of the Binance REST API
+    pub fn to_binance_api_url(&self) -> String {
+        match self {
+            MarketType::Spot => "https://api.binance.com".to_string(),
+            MarketType::UsdM => "https://fapi.binance.com".to_string(),
+            MarketType::CoinM => "https://dapi.binance.com".to_string(),
+        }
+    }
+
+    // Base URL of the Binance WebSocket market streams
+    pub fn to_binance_stream_url(&self) -> String {
+        match self {
+            MarketType::Spot => "wss://stream.binance.com:9443".to_string(),
+            MarketType::UsdM => "wss://fstream.binance.com".to_string(),
+            MarketType::CoinM => "wss://dstream.binance.com".to_string(),
+        }
+    }
+
+    // Request weight allowed by the Binance REST API per minute
+    pub fn to_binance_weight_limit(&self) -> u32 {
+        match self {
+            MarketType::Spot => 6000,
+            MarketType::UsdM => 2400,
+            MarketType::CoinM => 2400,
+        }
+    }
+
+    // Path prefix of the Binance REST API endpoints
+    pub fn to_binance_api_path(&self) -> String {
+        match self {
+            MarketType::Spot => "/api/v3".to_string(),
+            MarketType::UsdM => "/fapi/v1".to_string(),
+            MarketType::CoinM => "/dapi/v1".to_string(),
+        }
+    }
+
+    // Directory of the market in the Binance public data archives
+    pub fn to_archive_directory(&self) -> String {
+        match self {
+            MarketType::Spot => "spot".to_string(),
+            MarketType::UsdM => "futures/um".to_string(),
+            MarketType::CoinM => "futures/cm".to_string(),
+        }
+    }
+}
@@ -2,4 +2,5 @@ pub mod criteria;
 pub mod indicators;
 pub mod intervals;
 pub mod klines;
+pub mod markets;
 pub mod objects;
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::Type;
 
 use super::intervals;
+use super::markets::MarketType;
 
 // --- Crypto Symbols --- //
 
@@ -17,6 +18,8 @@ pub struct CryptoSymbol {
     pub volume: f64,
     pub last_updated: DateTime<Utc>,
     pub available: bool,
+    #[serde(default)]
+    pub market: MarketType,
 }
 
 impl CryptoSymbol {
@@ -28,6 +31,7 @@ impl CryptoSymbol {
             volume: -1.0,
             last_updated: Utc::now(),
             available: false,
+            market: MarketType::default(),
         }
     }
 }
@@ -50,6 +54,8 @@ pub struct CryptoList {
     pub interval: intervals::CryptoInterval,
     pub limit_count: i32,
     pub r#type: String,
+    #[serde(default)]
+    pub market: MarketType,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +66,8 @@ pub struct CryptoListComplete {
     pub interval: intervals::CryptoInterval,
     pub limit_count: i32,
     pub r#type: String,
+    #[serde(default)]
+    pub market: MarketType,
     pub crypto_symbols: Vec<i32>,
 }
 
@@ -69,6 +77,8 @@ pub struct CreateCryptoList {
     pub interval: intervals::CryptoInterval,
     pub limit_count: i32,
     pub r#type: String,
+    #[serde(default)]
+    pub market: MarketType,
     pub crypto_symbols: Vec<i32>,
 }
 
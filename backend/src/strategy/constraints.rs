@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+use super::simulation::{self, Trade};
+
+/// Constraint a strategy must satisfy to be feasible
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StrategyConstraint {
+    // Minimum number of trades
+    MinTrades(usize),
+    // Maximum drawdown (0.25 = 25%)
+    MaxDrawdown(f64),
+}
+
+impl StrategyConstraint {
+    // Parse the constraints from the 'constraints' object of the MHObject other parameters
+    // ex: {"constraints": {"min_trades": 30, "max_drawdown": 0.25}}
+    pub fn parse_all(other_parameters: &Option<String>) -> Result<Vec<StrategyConstraint>, String> {
+        let other_parameters: serde_json::Value = match other_parameters {
+            Some(other_parameters) if !other_parameters.trim().is_empty() => {
+                serde_json::from_str(other_parameters)
+                    .map_err(|_| "Invalid other parameters".to_string())?
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        let constraints_json = match other_parameters.get("constraints") {
+            Some(constraints_json) => constraints_json,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut constraints: Vec<StrategyConstraint> = Vec::new();
+        if let Some(value) = constraints_json.get("min_trades") {
+            match json_value_to_f64(value) {
+                Some(min_trades) if min_trades >= 0.0 => {
+                    constraints.push(StrategyConstraint::MinTrades(min_trades as usize))
+                }
+                _ => return Err("Invalid 'min_trades' constraint".to_string()),
+            }
+        }
+        if let Some(value) = constraints_json.get("max_drawdown") {
+            match json_value_to_f64(value) {
+                Some(max_drawdown) if max_drawdown > 0.0 => {
+                    constraints.push(StrategyConstraint::MaxDrawdown(max_drawdown))
+                }
+                _ => return Err("Invalid 'max_drawdown' constraint".to_string()),
+            }
+        }
+
+        Ok(constraints)
+    }
+
+    // Violation of the constraint (0.0 if satisfied), relative to the constraint bound
+    pub fn violation(&self, trades: &[Trade]) -> f64 {
+        match self {
+            StrategyConstraint::MinTrades(min_trades) => {
+                if trades.len() >= *min_trades {
+                    0.0
+                } else {
+                    (*min_trades - trades.len()) as f64 / *min_trades as f64
+                }
+            }
+            StrategyConstraint::MaxDrawdown(max_drawdown) => {
+                ((simulation::max_drawdown(trades) - max_drawdown) / max_drawdown).max(0.0)
+            }
+        }
+    }
+}
+
+// Parameters are sent either as numbers or as strings by the frontend
+pub(super) fn json_value_to_f64(value: &serde_json::Value) -> Option<f64> {
+    match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(string) => string.parse::<f64>().ok(),
+        _ => None,
+    }
+}
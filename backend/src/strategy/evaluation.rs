@@ -1,42 +1,51 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
 use serde_json::Number;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use super::constraints::StrategyConstraint;
+use super::monte_carlo::{analyse_trades, MonteCarloAnalysis, MonteCarloSettings};
+use super::objectives::{perturbations, StrategyObjective};
+use super::simulation::{self, Trade};
 use crate::metaheuristic::{
-    Metaheuristic, MetaheuristicTrait, MultiObjectiveDescent, Variable, VariableDefinition, NSGAII,
+    repair_variables, CacheStats, Checkpointing, Evaluation, FitnessCache, IslandNSGAII,
+    Metaheuristic, MetaheuristicTrait, MultiObjectiveDescent, ProgressReporter, Solution, Variable,
+    VariableConstraint, VariableDefinition, NSGAII,
 };
 use crate::objects::indicators::IndicatorTrait;
 use crate::objects::{
     criteria::Criterion, criteria::CriterionTrait, indicators::Indicator, klines::KlineCollection,
     objects::MHObject,
 };
+use crate::utils;
 
-pub fn backtest(
+/// Outcome of a strategy evaluation, stored as the result of the task
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyResult {
+    pub algorithm: String,
+    pub seed: u64,
+    pub evaluations: usize,
+    pub cache: CacheStats, // evaluations answered without a backtest
+    pub solutions: Vec<Solution>,
+    // Risk of each solution (same order as the solutions), empty if the analysis is disabled
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub monte_carlo: Vec<MonteCarloAnalysis>,
+}
+
+// Number of signals and trades of the strategy given by the variables
+pub fn simulate_strategy(
+    pool: &PgPool,
     vars: &[Variable],
     kline_collections: &Vec<KlineCollection>,
     indicators: &Vec<Indicator>,
     variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
-) -> Vec<f64> {
-    // let x = match vars[0] {
-    //     Variable::Float(v) => v,
-    //     _ => panic!("Expected float"),
-    // };
-    // let _b = match vars[1] {
-    //     Variable::Boolean(v) => v,
-    //     _ => panic!("Expected boolean"),
-    // };
-    // let y = match vars[2] {
-    //     Variable::Integer(v) => v,
-    //     _ => panic!("Expected integer"),
-    // };
-
-    // vec![
-    //     x.powi(2),                // First objective
-    //     (4.0 - (y as f64)),       // Second objective
-    //     (x + (y as f64) / 100.0), // Third objective
-    // ]
-
+) -> (i32, Vec<Trade>) {
     println!("-> Backtesting with variables: {:?}", vars);
 
     let mut sum = 0;
+    let mut trades: Vec<Trade> = Vec::new();
 
     let mut j = 0;
     for (i, indicator) in indicators.iter().enumerate() {
@@ -48,19 +57,16 @@ pub fn backtest(
 
         let mut kline_collection_cloned = kline_collections[0].clone();
 
-        let res = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current()
-                .block_on(kline_collection_cloned.retrieve_extended_klines(&indicator_cloned))
-        });
+        let res = utils::runtime::block_on(
+            kline_collection_cloned.retrieve_extended_klines(pool, &indicator_cloned),
+        );
         // println!("Result: {:?}", res);
 
         // println!("Kline collection cloned:");
         // kline_collection_cloned.display();
 
-        let res = tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current()
-                .block_on(indicator_cloned.retrieve(&kline_collection_cloned))
-        });
+        let res =
+            utils::runtime::block_on(indicator_cloned.retrieve(pool, &kline_collection_cloned));
 
         indicator_cloned.calculate_criteria(&kline_collection_cloned);
 
@@ -74,6 +80,9 @@ pub fn backtest(
             .map(|x| if *x { 1 } else { 0 })
             .sum::<i32>();
 
+        let close_prices: Vec<f64> = kline_collection_cloned.get_close_prices_iter().collect();
+        trades = simulation::simulate_trades(criterion.get_values(), &close_prices);
+
         // println!("Criterion: {:?}", criterion);
 
         break;
@@ -85,6 +94,7 @@ pub fn backtest(
                 Variable::Float(v) => v.to_string(),
                 Variable::Integer(v) => v.to_string(),
                 Variable::Boolean(v) => v.to_string(),
+                Variable::Categorical(v) => v.to_string(),
             };
             println!(
                 "Indicator: {:?}, Variable: {:?}",
@@ -95,17 +105,123 @@ pub fn backtest(
         }
     }
 
+    (sum, trades)
+}
+
+pub fn backtest(
+    pool: &PgPool,
+    vars: &[Variable],
+    kline_collections: &Vec<KlineCollection>,
+    indicators: &Vec<Indicator>,
+    variable_definitions_sep: &Vec<Vec<VariableDefinition>>,
+    constraints: &[StrategyConstraint],
+    objectives: &[StrategyObjective],
+) -> Evaluation {
+    // let x = match vars[0] {
+    //     Variable::Float(v) => v,
+    //     _ => panic!("Expected float"),
+    // };
+    // let _b = match vars[1] {
+    //     Variable::Boolean(v) => v,
+    //     _ => panic!("Expected boolean"),
+    // };
+    // let y = match vars[2] {
+    //     Variable::Integer(v) => v,
+    //     _ => panic!("Expected integer"),
+    // };
+
+    // vec![
+    //     x.powi(2),                // First objective
+    //     (4.0 - (y as f64)),       // Second objective
+    //     (x + (y as f64) / 100.0), // Third objective
+    // ]
+
+    let (sum, trades) = simulate_strategy(
+        pool,
+        vars,
+        kline_collections,
+        indicators,
+        variable_definitions_sep,
+    );
+
     println!("-> Sum: {}", sum);
 
-    vec![sum as f64, 0.0, 0.0]
+    let constraint_violations: Vec<f64> = constraints
+        .iter()
+        .map(|constraint| constraint.violation(&trades))
+        .collect();
+
+    // Returns of the strategy with each parameter moved around (robustness objective only)
+    let perturbation = objectives.iter().find_map(|objective| match objective {
+        StrategyObjective::Robustness(perturbation) => Some(*perturbation),
+        _ => None,
+    });
+    let perturbed_returns: Vec<f64> = match perturbation {
+        Some(perturbation) => {
+            let variable_definitions: Vec<VariableDefinition> =
+                variable_definitions_sep.iter().flatten().cloned().collect();
+            let variable_constraints =
+                get_variable_constraints(indicators, variable_definitions_sep);
+            perturbations(vars, &variable_definitions, perturbation)
+                .into_iter()
+                .map(|mut neighbour| {
+                    repair_variables(&mut neighbour, &variable_definitions, &variable_constraints);
+                    let (_, neighbour_trades) = simulate_strategy(
+                        pool,
+                        &neighbour,
+                        kline_collections,
+                        indicators,
+                        variable_definitions_sep,
+                    );
+                    simulation::total_return(&neighbour_trades)
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    // Default objectives if none were selected
+    let objective_values: Vec<f64> = if objectives.is_empty() {
+        vec![sum as f64, 0.0, 0.0]
+    } else {
+        objectives
+            .iter()
+            .map(|objective| objective.value(sum, &trades, &perturbed_returns))
+            .collect()
+    };
+
+    Evaluation::with_constraint_violations(objective_values, constraint_violations)
+}
+
+// Constraints between the parameters of each indicator (indices in the whole variables vector)
+pub fn get_variable_constraints(
+    indicators: &[Indicator],
+    variable_definitions_sep: &[Vec<VariableDefinition>],
+) -> Vec<VariableConstraint> {
+    let mut variable_constraints: Vec<VariableConstraint> = Vec::new();
+    let mut offset = 0;
+    for (i, indicator) in indicators.iter().enumerate() {
+        variable_constraints.extend(
+            indicator
+                .get_params_constraints()
+                .iter()
+                .map(|constraint| constraint.offset(offset)),
+        );
+        offset += variable_definitions_sep[i].len();
+    }
+    variable_constraints
 }
 
 // Evaluation of the strategy
 pub fn evaluate(
+    pool: &PgPool,
     kline_collections: &Vec<KlineCollection>,
     indicators: &Vec<Indicator>,
     mh_object: &MHObject,
-) -> Result<(), String> {
+    checkpointing: Option<Checkpointing>,
+    progress_reporter: Option<ProgressReporter>,
+    initial_solutions: Option<Vec<Vec<Variable>>>,
+) -> Result<StrategyResult, String> {
     println!("-> Evaluating the strategy");
 
     println!("-> Kline collections:");
@@ -121,6 +237,23 @@ pub fn evaluate(
 
     println!("Parameters: {:?}", algorithm_parameters);
 
+    // Constraints a strategy must satisfy to be feasible
+    let constraints = StrategyConstraint::parse_all(&mh_object.other_parameters)?;
+    println!("-> Constraints: {:?}", constraints);
+
+    // Objectives to minimize (the default objectives of the backtest if none is selected)
+    let objectives = StrategyObjective::parse_all(&mh_object.other_parameters)?;
+    println!("-> Objectives: {:?}", objectives);
+
+    // Monte Carlo analysis of the trades of the final solutions
+    let monte_carlo_settings = MonteCarloSettings::parse(&mh_object.other_parameters)?;
+    println!("-> Monte Carlo analysis: {:?}", monte_carlo_settings);
+    let num_objectives = if objectives.is_empty() {
+        3
+    } else {
+        objectives.len()
+    };
+
     // Variables definitions for each indicator
     let variable_definitions_sep: Vec<Vec<VariableDefinition>> = indicators
         .iter()
@@ -133,25 +266,49 @@ pub fn evaluate(
         .flat_map(|v| v.clone())
         .collect();
 
+    // Constraints between the parameters of each indicator (indices in the whole variables vector)
+    let variable_constraints = get_variable_constraints(indicators, &variable_definitions_sep);
+
     // Create algorithm
-    let algorithm = match algorithm_name.as_str() {
+    let mut algorithm = match algorithm_name.as_str() {
         "NSGA-II" => {
-            let algo = NSGAII::new_from_json(&algorithm_parameters, variable_definitions, 3);
+            let algo =
+                NSGAII::new_from_json(&algorithm_parameters, variable_definitions, num_objectives);
             if algo.is_err() {
                 return Err("Error creating NSGA-II algorithm".to_string());
             }
-            Metaheuristic::NSGAII(algo.unwrap())
+            Metaheuristic::NSGAII(
+                algo.unwrap()
+                    .with_variable_constraints(variable_constraints),
+            )
+        }
+        "Island NSGA-II" => {
+            let algo = IslandNSGAII::new_from_json(
+                &algorithm_parameters,
+                variable_definitions,
+                num_objectives,
+            );
+            if algo.is_err() {
+                return Err("Error creating Island NSGA-II algorithm".to_string());
+            }
+            Metaheuristic::IslandNSGAII(
+                algo.unwrap()
+                    .with_variable_constraints(variable_constraints),
+            )
         }
         "Multi-Objective Simple Descent" => {
             let algo = MultiObjectiveDescent::new_from_json(
                 &algorithm_parameters,
                 variable_definitions,
-                3,
+                num_objectives,
             );
             if algo.is_err() {
                 return Err("Error creating Multi-Objective Simple Descent algorithm".to_string());
             }
-            Metaheuristic::MultiObjectiveDescent(algo.unwrap())
+            Metaheuristic::MultiObjectiveDescent(
+                algo.unwrap()
+                    .with_variable_constraints(variable_constraints),
+            )
         }
         _ => {
             println!("-> Unknown algorithm: {}", algorithm_name);
@@ -159,20 +316,96 @@ pub fn evaluate(
         }
     };
 
+    if let Some(checkpointing) = checkpointing {
+        algorithm = algorithm.with_checkpointing(checkpointing);
+    }
+    if let Some(progress_reporter) = progress_reporter {
+        algorithm = algorithm.with_progress_reporter(progress_reporter);
+    }
+    if let Some(initial_solutions) = initial_solutions {
+        println!("-> Initial solutions: {}", initial_solutions.len());
+        algorithm = algorithm.with_initial_solutions(initial_solutions);
+    }
+
     println!("-> Algorithm: {:?}", algorithm);
+    println!("-> Seed: {}", algorithm.get_seed());
+
+    // Identical variables give the same backtest, so each one is only run once
+    let cache = FitnessCache::new();
+    let evaluations = AtomicUsize::new(0);
+    let evaluate = |vars: &[Variable],
+                    kline_collections: &Vec<KlineCollection>,
+                    indicators: &Vec<Indicator>,
+                    variable_definitions_sep: &Vec<Vec<VariableDefinition>>| {
+        evaluations.fetch_add(1, Ordering::Relaxed);
+        cache.get_or_evaluate(vars, || {
+            backtest(
+                pool,
+                vars,
+                kline_collections,
+                indicators,
+                variable_definitions_sep,
+                &constraints,
+                &objectives,
+            )
+        })
+    };
 
-    let final_solutions = algorithm.run(
-        20,
-        backtest,
+    let mut final_solutions = algorithm.run(
+        evaluate,
         &kline_collections,
         &indicators,
         &variable_definitions_sep,
     );
 
+    // Infeasible strategies are not part of the final front
+    let solutions_count = final_solutions.len();
+    final_solutions.retain(|solution| solution.is_feasible());
+    println!(
+        "-> {} infeasible solution(s) removed",
+        solutions_count - final_solutions.len()
+    );
+
+    let cache_stats = cache.stats();
+    println!(
+        "-> Fitness cache: {} hit(s) for {} evaluation(s) ({:.1}%)",
+        cache_stats.hits,
+        cache_stats.lookups,
+        cache_stats.hit_rate * 100.0
+    );
+
     println!("-> Final solutions:");
-    for solution in final_solutions {
+    for solution in &final_solutions {
         println!("{:?}", solution);
     }
 
-    Ok(())
+    // Resampled from the seed of the run, so that the analysis is reproducible
+    let mut monte_carlo: Vec<MonteCarloAnalysis> = Vec::new();
+    if let Some(settings) = monte_carlo_settings {
+        let mut rng = ChaCha8Rng::seed_from_u64(algorithm.get_seed());
+        for solution in &final_solutions {
+            let (_, trades) = simulate_strategy(
+                pool,
+                &solution.variables,
+                kline_collections,
+                indicators,
+                &variable_definitions_sep,
+            );
+            monte_carlo.push(analyse_trades(&trades, &settings, &mut rng));
+        }
+        println!(
+            "-> Monte Carlo analysis of {} solution(s) ({} simulations per method)",
+            monte_carlo.len(),
+            settings.simulations
+        );
+    }
+
+    Ok(StrategyResult {
+        algorithm: algorithm_name,
+        seed: algorithm.get_seed(),
+        evaluations: evaluations.load(Ordering::Relaxed),
+        cache: cache_stats,
+        solutions: final_solutions,
+        monte_carlo,
+    })
 }
@@ -1,3 +1,13 @@
+mod constraints;
 mod evaluation;
+mod monte_carlo;
+mod objectives;
+mod simulation;
+mod tuning;
 
+pub use constraints::*;
 pub use evaluation::*;
+pub use monte_carlo::*;
+pub use objectives::*;
+pub use simulation::*;
+pub use tuning::*;
@@ -0,0 +1,272 @@
+use rand::prelude::*;
+use serde::Serialize;
+
+use super::constraints::json_value_to_f64;
+use super::simulation::{self, Trade};
+
+const SIMULATIONS_DEFAULT: usize = 1000;
+const SKIP_FRACTION_DEFAULT: f64 = 0.1;
+const CONFIDENCE_DEFAULT: f64 = 0.95;
+
+/// Settings of the Monte Carlo analysis of the trades of a strategy
+#[derive(Debug, Clone)]
+pub struct MonteCarloSettings {
+    pub simulations: usize, // resampled trade lists for each method
+    pub skip_fraction: f64, // probability of skipping each trade (0.1 = 10%)
+    pub confidence: f64,    // level of the confidence intervals (0.95 = 95%)
+}
+
+impl Default for MonteCarloSettings {
+    fn default() -> Self {
+        MonteCarloSettings {
+            simulations: SIMULATIONS_DEFAULT,
+            skip_fraction: SKIP_FRACTION_DEFAULT,
+            confidence: CONFIDENCE_DEFAULT,
+        }
+    }
+}
+
+impl MonteCarloSettings {
+    // Parse the settings from the 'monte_carlo' object of the MHObject other parameters
+    // ex: {"monte_carlo": {"simulations": 1000, "skip_fraction": 0.1, "confidence": 0.95}}
+    // (None if the analysis is disabled with {"monte_carlo": false})
+    pub fn parse(other_parameters: &Option<String>) -> Result<Option<MonteCarloSettings>, String> {
+        let other_parameters: serde_json::Value = match other_parameters {
+            Some(other_parameters) if !other_parameters.trim().is_empty() => {
+                serde_json::from_str(other_parameters)
+                    .map_err(|_| "Invalid other parameters".to_string())?
+            }
+            _ => return Ok(Some(MonteCarloSettings::default())),
+        };
+
+        let settings_json = match other_parameters.get("monte_carlo") {
+            Some(serde_json::Value::Bool(false)) => return Ok(None),
+            Some(serde_json::Value::Object(settings_json)) => settings_json,
+            Some(serde_json::Value::Bool(true)) | None => {
+                return Ok(Some(MonteCarloSettings::default()))
+            }
+            Some(_) => return Err("Invalid 'monte_carlo' settings".to_string()),
+        };
+
+        let mut settings = MonteCarloSettings::default();
+        if let Some(value) = settings_json.get("simulations") {
+            match json_value_to_f64(value) {
+                Some(simulations) if simulations >= 1.0 => {
+                    settings.simulations = simulations as usize
+                }
+                _ => return Err("Invalid 'simulations' for the Monte Carlo analysis".to_string()),
+            }
+        }
+        if let Some(value) = settings_json.get("skip_fraction") {
+            match json_value_to_f64(value) {
+                Some(skip_fraction) if (0.0..1.0).contains(&skip_fraction) => {
+                    settings.skip_fraction = skip_fraction
+                }
+                _ => return Err("Invalid 'skip_fraction' for the Monte Carlo analysis".to_string()),
+            }
+        }
+        if let Some(value) = settings_json.get("confidence") {
+            match json_value_to_f64(value) {
+                Some(confidence) if confidence > 0.0 && confidence < 1.0 => {
+                    settings.confidence = confidence
+                }
+                _ => return Err("Invalid 'confidence' for the Monte Carlo analysis".to_string()),
+            }
+        }
+
+        Ok(Some(settings))
+    }
+}
+
+/// Summary of a distribution, with its confidence interval
+#[derive(Debug, Clone, Serialize)]
+pub struct DistributionSummary {
+    pub mean: f64,
+    pub median: f64,
+    pub lower: f64, // lower bound of the confidence interval
+    pub upper: f64, // upper bound of the confidence interval
+}
+
+/// Returns and drawdowns of the resampled trade lists of one method
+#[derive(Debug, Clone, Serialize)]
+pub struct ResamplingAnalysis {
+    pub returns: DistributionSummary,
+    pub drawdowns: DistributionSummary,
+}
+
+/// Monte Carlo analysis of the trades of a solution
+#[derive(Debug, Clone, Serialize)]
+pub struct MonteCarloAnalysis {
+    pub trades: usize,
+    pub total_return: f64, // of the backtest
+    pub max_drawdown: f64, // of the backtest
+    pub simulations: usize,
+    pub confidence: f64,
+    pub reshuffle: ResamplingAnalysis, // trades in a random order
+    pub bootstrap: ResamplingAnalysis, // trades drawn with replacement
+    pub skip: ResamplingAnalysis,      // trades randomly skipped
+}
+
+/// Monte Carlo analysis of the trades : reshuffled order, bootstrap resampling and skipped trades
+pub fn analyse_trades(
+    trades: &[Trade],
+    settings: &MonteCarloSettings,
+    rng: &mut impl Rng,
+) -> MonteCarloAnalysis {
+    let reshuffle = resample(trades, settings, rng, |trades, rng| {
+        let mut shuffled = trades.to_vec();
+        shuffled.shuffle(rng);
+        shuffled
+    });
+    let bootstrap = resample(trades, settings, rng, |trades, rng| {
+        (0..trades.len())
+            .map(|_| trades[rng.gen_range(0..trades.len())].clone())
+            .collect()
+    });
+    let skip = resample(trades, settings, rng, |trades, rng| {
+        trades
+            .iter()
+            .filter(|_| rng.gen::<f64>() >= settings.skip_fraction)
+            .cloned()
+            .collect()
+    });
+
+    MonteCarloAnalysis {
+        trades: trades.len(),
+        total_return: simulation::total_return(trades),
+        max_drawdown: simulation::max_drawdown(trades),
+        simulations: settings.simulations,
+        confidence: settings.confidence,
+        reshuffle,
+        bootstrap,
+        skip,
+    }
+}
+
+// Returns and drawdowns of the trade lists generated by the method
+fn resample<R: Rng>(
+    trades: &[Trade],
+    settings: &MonteCarloSettings,
+    rng: &mut R,
+    method: impl Fn(&[Trade], &mut R) -> Vec<Trade>,
+) -> ResamplingAnalysis {
+    let mut returns: Vec<f64> = Vec::with_capacity(settings.simulations);
+    let mut drawdowns: Vec<f64> = Vec::with_capacity(settings.simulations);
+    for _ in 0..settings.simulations {
+        let resampled = if trades.is_empty() {
+            Vec::new()
+        } else {
+            method(trades, rng)
+        };
+        returns.push(simulation::total_return(&resampled));
+        drawdowns.push(simulation::max_drawdown(&resampled));
+    }
+
+    ResamplingAnalysis {
+        returns: summarize(returns, settings.confidence),
+        drawdowns: summarize(drawdowns, settings.confidence),
+    }
+}
+
+// Mean, median and percentile confidence interval of the values
+fn summarize(mut values: Vec<f64>, confidence: f64) -> DistributionSummary {
+    if values.is_empty() {
+        return DistributionSummary {
+            mean: 0.0,
+            median: 0.0,
+            lower: 0.0,
+            upper: 0.0,
+        };
+    }
+
+    values.sort_by(|a, b| a.total_cmp(b));
+    let tail = (1.0 - confidence) / 2.0;
+    DistributionSummary {
+        mean: values.iter().sum::<f64>() / values.len() as f64,
+        median: quantile(&values, 0.5),
+        lower: quantile(&values, tail),
+        upper: quantile(&values, 1.0 - tail),
+    }
+}
+
+// Quantile of sorted values (linear interpolation between the closest ranks)
+fn quantile(sorted_values: &[f64], q: f64) -> f64 {
+    let position = q.clamp(0.0, 1.0) * (sorted_values.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    let weight = position - below as f64;
+    sorted_values[below] * (1.0 - weight) + sorted_values[above] * weight
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha8Rng;
+
+    fn trade(entry_price: f64, exit_price: f64) -> Trade {
+        Trade {
+            entry_index: 0,
+            exit_index: 1,
+            entry_price,
+            exit_price,
+        }
+    }
+
+    #[test]
+    fn test_quantile() {
+        let values = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(quantile(&values, 0.5), 3.0);
+        assert_eq!(quantile(&values, 0.0), 1.0);
+        assert_eq!(quantile(&values, 1.0), 5.0);
+        assert_eq!(quantile(&values, 0.125), 1.5);
+    }
+
+    #[test]
+    fn test_analyse_trades() {
+        let trades = vec![
+            trade(100.0, 110.0),
+            trade(100.0, 80.0),
+            trade(100.0, 120.0),
+            trade(100.0, 95.0),
+        ];
+        let settings = MonteCarloSettings {
+            simulations: 200,
+            ..MonteCarloSettings::default()
+        };
+        let analysis = analyse_trades(&trades, &settings, &mut ChaCha8Rng::seed_from_u64(1));
+
+        // Reshuffling keeps the compounded return, only the path (and the drawdown) changes
+        assert!((analysis.reshuffle.returns.lower - analysis.total_return).abs() < 1e-9);
+        assert!((analysis.reshuffle.returns.upper - analysis.total_return).abs() < 1e-9);
+        assert!(analysis.reshuffle.drawdowns.lower <= analysis.reshuffle.drawdowns.upper);
+        assert!(analysis.bootstrap.returns.lower < analysis.bootstrap.returns.upper);
+
+        // No trades, no risk
+        let analysis = analyse_trades(&[], &settings, &mut ChaCha8Rng::seed_from_u64(1));
+        assert_eq!(analysis.skip.returns.mean, 0.0);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(MonteCarloSettings::parse(&None).unwrap().is_some());
+        assert!(
+            MonteCarloSettings::parse(&Some(r#"{"monte_carlo": false}"#.to_string()))
+                .unwrap()
+                .is_none()
+        );
+        let settings = MonteCarloSettings::parse(&Some(
+            r#"{"monte_carlo": {"simulations": "500", "confidence": 0.9}}"#.to_string(),
+        ))
+        .unwrap()
+        .unwrap();
+        assert_eq!(settings.simulations, 500);
+        assert_eq!(settings.confidence, 0.9);
+        assert_eq!(settings.skip_fraction, SKIP_FRACTION_DEFAULT);
+        assert!(MonteCarloSettings::parse(&Some(
+            r#"{"monte_carlo": {"confidence": 2}}"#.to_string()
+        ))
+        .is_err());
+    }
+}
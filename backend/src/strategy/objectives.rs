@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use super::constraints::json_value_to_f64;
+use super::simulation::{self, Trade};
+use crate::metaheuristic::{Variable, VariableDefinition};
+
+const ROBUSTNESS_PERTURBATION_DEFAULT: f64 = 0.05;
+
+/// Objective of the strategy search (all objectives are minimized)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum StrategyObjective {
+    // Number of signals of the strategy
+    Signals,
+    // Compounded return of the trades (negated)
+    Return,
+    // Maximum drawdown of the trades
+    Drawdown,
+    // Mean loss of return when each indicator parameter is moved by a fraction of its range
+    // (0.05 = 5%), low on robust plateaus and high on sharp peaks
+    Robustness(f64),
+}
+
+impl StrategyObjective {
+    // Parse the objectives from the 'objectives' list of the MHObject other parameters
+    // ex: {"objectives": ["return", "drawdown", "robustness"], "robustness_perturbation": 0.1}
+    // (no list : the default objectives of the backtest)
+    pub fn parse_all(other_parameters: &Option<String>) -> Result<Vec<StrategyObjective>, String> {
+        let other_parameters: serde_json::Value = match other_parameters {
+            Some(other_parameters) if !other_parameters.trim().is_empty() => {
+                serde_json::from_str(other_parameters)
+                    .map_err(|_| "Invalid other parameters".to_string())?
+            }
+            _ => return Ok(Vec::new()),
+        };
+
+        let objectives_json = match other_parameters.get("objectives") {
+            Some(serde_json::Value::Array(objectives_json)) => objectives_json,
+            Some(_) => return Err("Invalid 'objectives' list".to_string()),
+            None => return Ok(Vec::new()),
+        };
+
+        let perturbation = match other_parameters.get("robustness_perturbation") {
+            Some(value) => match json_value_to_f64(value) {
+                Some(perturbation) if perturbation > 0.0 && perturbation <= 1.0 => perturbation,
+                _ => return Err("Invalid 'robustness_perturbation'".to_string()),
+            },
+            None => ROBUSTNESS_PERTURBATION_DEFAULT,
+        };
+
+        objectives_json
+            .iter()
+            .map(|objective| match objective.as_str() {
+                Some("signals") => Ok(StrategyObjective::Signals),
+                Some("return") => Ok(StrategyObjective::Return),
+                Some("drawdown") => Ok(StrategyObjective::Drawdown),
+                Some("robustness") => Ok(StrategyObjective::Robustness(perturbation)),
+                _ => Err(format!("Unknown objective: {}", objective)),
+            })
+            .collect()
+    }
+
+    // Value of the objective for the trades of the strategy
+    // (the robustness needs the returns of the perturbed strategies)
+    pub fn value(&self, signals: i32, trades: &[Trade], perturbed_returns: &[f64]) -> f64 {
+        match self {
+            StrategyObjective::Signals => signals as f64,
+            StrategyObjective::Return => -simulation::total_return(trades),
+            StrategyObjective::Drawdown => simulation::max_drawdown(trades),
+            StrategyObjective::Robustness(_) => {
+                robustness(simulation::total_return(trades), perturbed_returns)
+            }
+        }
+    }
+}
+
+// Mean loss of return of the perturbed strategies (gains count as no loss)
+pub fn robustness(base_return: f64, perturbed_returns: &[f64]) -> f64 {
+    if perturbed_returns.is_empty() {
+        return 0.0;
+    }
+    perturbed_returns
+        .iter()
+        .map(|perturbed_return| (base_return - perturbed_return).max(0.0))
+        .sum::<f64>()
+        / perturbed_returns.len() as f64
+}
+
+// Neighbours of the variables : each numeric variable moved down and up by a fraction of its range
+// (at least one unit for integers and one step for stepped floats), within the bounds
+pub fn perturbations(
+    variables: &[Variable],
+    variable_definitions: &[VariableDefinition],
+    fraction: f64,
+) -> Vec<Vec<Variable>> {
+    let mut neighbours: Vec<Vec<Variable>> = Vec::new();
+
+    for (i, (variable, definition)) in variables.iter().zip(variable_definitions).enumerate() {
+        let moved: Vec<Variable> = match (definition, variable) {
+            (VariableDefinition::Float(min, max), Variable::Float(value)) => {
+                let delta = (max - min) * fraction;
+                vec![value - delta, value + delta]
+                    .into_iter()
+                    .map(|moved| Variable::Float(definition.quantize(moved)))
+                    .collect()
+            }
+            (VariableDefinition::SteppedFloat(min, max, step), Variable::Float(value)) => {
+                let delta = ((max - min) * fraction).max(*step);
+                vec![value - delta, value + delta]
+                    .into_iter()
+                    .map(|moved| Variable::Float(definition.quantize(moved)))
+                    .collect()
+            }
+            (VariableDefinition::Integer(min, max), Variable::Integer(value)) => {
+                let delta = (((max - min) as f64 * fraction).round() as i64).max(1);
+                vec![value - delta, value + delta]
+                    .into_iter()
+                    .map(|moved| Variable::Integer(moved.clamp(*min, *max)))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        for moved in moved {
+            // Variables at a bound are not moved beyond it
+            if moved != *variable {
+                let mut neighbour = variables.to_vec();
+                neighbour[i] = moved;
+                neighbours.push(neighbour);
+            }
+        }
+    }
+
+    neighbours
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all() {
+        let objectives = StrategyObjective::parse_all(&Some(
+            r#"{"objectives": ["return", "robustness"], "robustness_perturbation": "0.1"}"#
+                .to_string(),
+        ))
+        .unwrap();
+        assert_eq!(objectives.len(), 2);
+        assert!(matches!(objectives[1], StrategyObjective::Robustness(p) if p == 0.1));
+
+        assert!(StrategyObjective::parse_all(&None).unwrap().is_empty());
+        assert!(
+            StrategyObjective::parse_all(&Some(r#"{"objectives": ["sharpe"]}"#.to_string()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_perturbations() {
+        let definitions = vec![
+            VariableDefinition::Integer(2, 50),
+            VariableDefinition::Float(0.0, 1.0),
+            VariableDefinition::Boolean,
+        ];
+        let variables = vec![
+            Variable::Integer(2),
+            Variable::Float(0.5),
+            Variable::Boolean(true),
+        ];
+
+        let neighbours = perturbations(&variables, &definitions, 0.1);
+        // The integer is at its minimum, so it only moves up
+        assert_eq!(neighbours.len(), 3);
+        assert_eq!(neighbours[0][0], Variable::Integer(7));
+        assert_eq!(neighbours[1][1], Variable::Float(0.4));
+        assert_eq!(neighbours[2][1], Variable::Float(0.6));
+    }
+
+    #[test]
+    fn test_robustness() {
+        assert_eq!(robustness(0.2, &[]), 0.0);
+        // A gain counts as no loss
+        assert!((robustness(0.2, &[0.1, 0.3]) - 0.05).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+/// A closed trade of the simulated strategy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trade {
+    pub entry_index: usize,
+    pub exit_index: usize,
+    pub entry_price: f64,
+    pub exit_price: f64,
+}
+
+impl Trade {
+    // Relative return of the trade (0.01 = +1%)
+    pub fn get_return(&self) -> f64 {
+        (self.exit_price - self.entry_price) / self.entry_price
+    }
+}
+
+// Long-only simulation : a position is held while the signal is true
+// (an open position is closed on the last close price)
+pub fn simulate_trades(signals: &[bool], close_prices: &[f64]) -> Vec<Trade> {
+    let mut trades: Vec<Trade> = Vec::new();
+    let mut entry: Option<(usize, f64)> = None;
+
+    let length = signals.len().min(close_prices.len());
+    for i in 0..length {
+        match (signals[i], entry) {
+            (true, None) => entry = Some((i, close_prices[i])),
+            (false, Some((entry_index, entry_price))) => {
+                trades.push(Trade {
+                    entry_index,
+                    exit_index: i,
+                    entry_price,
+                    exit_price: close_prices[i],
+                });
+                entry = None;
+            }
+            _ => {}
+        }
+    }
+
+    if let Some((entry_index, entry_price)) = entry {
+        if length > 0 && entry_index < length - 1 {
+            trades.push(Trade {
+                entry_index,
+                exit_index: length - 1,
+                entry_price,
+                exit_price: close_prices[length - 1],
+            });
+        }
+    }
+
+    trades
+}
+
+// Compounded return of the trades (0.1 = +10%)
+pub fn total_return(trades: &[Trade]) -> f64 {
+    trades
+        .iter()
+        .fold(1.0, |equity, trade| equity * (1.0 + trade.get_return()))
+        - 1.0
+}
+
+// Maximum drawdown of the compounded equity curve of the trades (0.25 = -25%)
+pub fn max_drawdown(trades: &[Trade]) -> f64 {
+    let mut equity = 1.0;
+    let mut peak = 1.0;
+    let mut max_drawdown: f64 = 0.0;
+
+    for trade in trades {
+        equity *= 1.0 + trade.get_return();
+        if equity > peak {
+            peak = equity;
+        }
+        max_drawdown = max_drawdown.max((peak - equity) / peak);
+    }
+
+    max_drawdown
+}
+
+//// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate_trades() {
+        let signals = vec![false, true, true, false, true, true];
+        let close_prices = vec![100.0, 100.0, 110.0, 120.0, 120.0, 90.0];
+        let trades = simulate_trades(&signals, &close_prices);
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].entry_index, 1);
+        assert_eq!(trades[0].exit_index, 3);
+        assert_eq!(trades[0].get_return(), 0.2);
+        assert_eq!(trades[1].entry_index, 4);
+        assert_eq!(trades[1].exit_index, 5);
+        assert_eq!(trades[1].get_return(), -0.25);
+    }
+
+    #[test]
+    fn test_max_drawdown() {
+        let trades = vec![
+            Trade {
+                entry_index: 0,
+                exit_index: 1,
+                entry_price: 100.0,
+                exit_price: 200.0,
+            },
+            Trade {
+                entry_index: 1,
+                exit_index: 2,
+                entry_price: 200.0,
+                exit_price: 150.0,
+            },
+        ];
+
+        assert_eq!(max_drawdown(&trades), 0.25);
+        assert_eq!(max_drawdown(&[]), 0.0);
+        assert_eq!(total_return(&trades), 0.5);
+        assert_eq!(total_return(&[]), 0.0);
+    }
+}
@@ -0,0 +1,577 @@
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::evaluation::{self, StrategyResult};
+use crate::metaheuristic::{
+    parse_optional, parse_seed, reference_point, solutions_hypervolume, Metaheuristic, Solution,
+    MAX_SEED,
+};
+use crate::objects::{indicators::Indicator, klines::KlineCollection, objects::MHObject};
+
+const CONFIGURATIONS_DEFAULT: usize = 10;
+const REPETITIONS_DEFAULT: usize = 3;
+const LEVELS_DEFAULT: usize = 3;
+const NON_TUNABLE_PARAMETERS: [&str; 2] = ["num_objectives", "seed"];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum TuningMethod {
+    Grid,   // every combination of `levels` values of each parameter
+    Random, // `configurations` random configurations
+    Racing, // random configurations, the worst half being eliminated after each round of runs
+}
+
+/// Range of values of a tuned parameter of the algorithm
+#[derive(Clone, Debug)]
+pub struct TunedParameter {
+    pub name: String,
+    pub integer: bool,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl TunedParameter {
+    fn sample(&self, rng: &mut impl Rng) -> Value {
+        if self.max <= self.min {
+            return self.to_json(self.min);
+        }
+        self.to_json(rng.gen_range(self.min..=self.max))
+    }
+
+    fn levels(&self, count: usize) -> Vec<Value> {
+        let mut levels: Vec<Value> = Vec::new();
+        for i in 0..count.max(1) {
+            let value = if count <= 1 {
+                (self.min + self.max) / 2.0
+            } else {
+                self.min + i as f64 * (self.max - self.min) / (count - 1) as f64
+            };
+            let value = self.to_json(value);
+            if !levels.contains(&value) {
+                levels.push(value);
+            }
+        }
+        levels
+    }
+
+    // Parameters are given as strings, like the ones sent by the interface
+    fn to_json(&self, value: f64) -> Value {
+        if self.integer {
+            Value::String((value.round() as i64).to_string())
+        } else {
+            Value::String(value.to_string())
+        }
+    }
+}
+
+/// Settings of a meta-optimisation task, from the task parameters. Ex :
+/// {"tuning": {"method": "racing", "configurations": 10, "repetitions": 3, "seed": 1,
+///             "parameters": {"population_size": [20, 200], "mutation_rate": [0.01, 0.3]}}}
+/// Without "parameters", every numeric parameter of the algorithm with finite bounds is tuned.
+#[derive(Clone, Debug)]
+pub struct TuningSettings {
+    pub method: TuningMethod,
+    pub configurations: usize, // random and racing methods
+    pub repetitions: usize,    // runs (with different seeds) of each configuration
+    pub levels: usize,         // grid method
+    pub seed: u64,
+    pub parameters: Vec<TunedParameter>,
+}
+
+impl TuningSettings {
+    pub fn from_json(json: &Value, algorithm_name: &str) -> Result<Self, String> {
+        let method = match json
+            .get("method")
+            .and_then(|v| v.as_str())
+            .unwrap_or("racing")
+        {
+            "grid" => TuningMethod::Grid,
+            "random" => TuningMethod::Random,
+            "racing" => TuningMethod::Racing,
+            method => return Err(format!("Unknown tuning method: {}", method)),
+        };
+
+        let info = Metaheuristic::get_all_info()
+            .into_iter()
+            .find(|info| info.name == algorithm_name)
+            .ok_or_else(|| format!("Unknown algorithm: {}", algorithm_name))?;
+        let tunable = info.parameters.iter().filter(|parameter| {
+            (parameter.variable_type == "integer" || parameter.variable_type == "float")
+                && !NON_TUNABLE_PARAMETERS.contains(&parameter.name.as_str())
+        });
+
+        let parameters: Vec<TunedParameter> = match json.get("parameters") {
+            Some(Value::Object(ranges)) => ranges
+                .iter()
+                .map(|(name, range)| {
+                    let parameter = tunable
+                        .clone()
+                        .find(|parameter| &parameter.name == name)
+                        .ok_or_else(|| format!("Parameter {} cannot be tuned", name))?;
+                    let range: Vec<f64> = range
+                        .as_array()
+                        .map(|values| values.iter().filter_map(|v| v.as_f64()).collect())
+                        .unwrap_or_default();
+                    if range.len() != 2 || range[0] > range[1] {
+                        return Err(format!("Invalid range for parameter {}", name));
+                    }
+                    Ok(TunedParameter {
+                        name: name.clone(),
+                        integer: parameter.variable_type == "integer",
+                        min: range[0],
+                        max: range[1],
+                    })
+                })
+                .collect::<Result<_, String>>()?,
+            Some(_) => return Err("Invalid tuning parameters".to_string()),
+            None => tunable
+                .filter_map(|parameter| match parameter.bounds {
+                    Some((min, max)) if min.is_finite() && max.is_finite() => {
+                        Some(TunedParameter {
+                            name: parameter.name.clone(),
+                            integer: parameter.variable_type == "integer",
+                            min,
+                            max,
+                        })
+                    }
+                    _ => None,
+                })
+                .collect(),
+        };
+        if parameters.is_empty() {
+            return Err("No parameter to tune".to_string());
+        }
+
+        let get_usize = |key: &str, default: usize| -> Result<usize, String> {
+            Ok(parse_optional::<usize>(json, key)?
+                .map(|v| v.max(1))
+                .unwrap_or(default))
+        };
+
+        Ok(TuningSettings {
+            method,
+            configurations: get_usize("configurations", CONFIGURATIONS_DEFAULT)?,
+            repetitions: get_usize("repetitions", REPETITIONS_DEFAULT)?,
+            levels: get_usize("levels", LEVELS_DEFAULT)?,
+            seed: parse_seed(json)?,
+            parameters,
+        })
+    }
+
+    /// Configurations (values of the tuned parameters) to evaluate
+    fn generate_configurations(&self) -> Vec<Map<String, Value>> {
+        match self.method {
+            TuningMethod::Grid => {
+                let mut grid = vec![Map::new()];
+                for parameter in &self.parameters {
+                    let levels = parameter.levels(self.levels);
+                    grid = grid
+                        .into_iter()
+                        .flat_map(|configuration| {
+                            levels.iter().map(move |level| {
+                                let mut configuration = configuration.clone();
+                                configuration.insert(parameter.name.clone(), level.clone());
+                                configuration
+                            })
+                        })
+                        .collect();
+                }
+                grid
+            }
+            TuningMethod::Random | TuningMethod::Racing => {
+                let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+                (0..self.configurations)
+                    .map(|_| {
+                        self.parameters
+                            .iter()
+                            .map(|parameter| (parameter.name.clone(), parameter.sample(&mut rng)))
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// One run of a configuration
+#[derive(Clone, Debug, Serialize)]
+pub struct TuningRun {
+    pub seed: u64,
+    pub evaluations: usize,
+    pub hypervolume: f64,
+    #[serde(skip)]
+    solutions: Vec<Solution>,
+}
+
+/// Runs and scores of a configuration
+#[derive(Clone, Debug, Serialize)]
+pub struct ConfigurationResult {
+    pub parameters: Map<String, Value>,
+    pub runs: Vec<TuningRun>,
+    pub mean_hypervolume: f64,
+    pub mean_hypervolume_per_evaluation: f64,
+    pub eliminated_after_round: Option<usize>, // racing method
+}
+
+/// Outcome of a meta-optimisation task, stored as the result of the task
+#[derive(Clone, Debug, Serialize)]
+pub struct TuningResult {
+    pub algorithm: String,
+    pub method: TuningMethod,
+    pub reference_point: Vec<f64>,
+    pub configurations: Vec<ConfigurationResult>, // best first
+}
+
+/// Meta-optimisation : find the parameters of the algorithm of the MHObject giving the best
+/// hypervolume per evaluation on the strategy problem
+pub fn tune(
+    pool: &PgPool,
+    kline_collections: &Vec<KlineCollection>,
+    indicators: &Vec<Indicator>,
+    mh_object: &MHObject,
+    settings: &TuningSettings,
+    should_cancel: &AtomicBool,
+) -> Result<TuningResult, String> {
+    println!("-> Tuning {} ({:?})", mh_object.mh_algorithm_name, settings);
+
+    let (configurations, reference_point) = race(
+        settings,
+        |parameters, seed| {
+            let result = run_configuration(
+                pool,
+                kline_collections,
+                indicators,
+                mh_object,
+                parameters,
+                seed,
+            )?;
+            Ok((result.evaluations, result.solutions))
+        },
+        should_cancel,
+    )?;
+
+    Ok(TuningResult {
+        algorithm: mh_object.mh_algorithm_name.clone(),
+        method: settings.method.clone(),
+        reference_point,
+        configurations,
+    })
+}
+
+// Run every configuration `repetitions` times (eliminating the worst half after each round with the
+// racing method), `run` giving the evaluations and solutions of a run of a configuration with a seed.
+// Returns the configurations (best first) and the common reference point of their scores
+fn race(
+    settings: &TuningSettings,
+    mut run: impl FnMut(&Map<String, Value>, u64) -> Result<(usize, Vec<Solution>), String>,
+    should_cancel: &AtomicBool,
+) -> Result<(Vec<ConfigurationResult>, Vec<f64>), String> {
+    let mut configurations: Vec<ConfigurationResult> = settings
+        .generate_configurations()
+        .into_iter()
+        .map(|parameters| ConfigurationResult {
+            parameters,
+            runs: Vec::new(),
+            mean_hypervolume: 0.0,
+            mean_hypervolume_per_evaluation: 0.0,
+            eliminated_after_round: None,
+        })
+        .collect();
+
+    for round in 0..settings.repetitions {
+        // Every configuration uses the same seed in a round, so that they are compared on the same runs
+        let seed = (settings.seed + round as u64) % (MAX_SEED + 1);
+        for configuration in configurations
+            .iter_mut()
+            .filter(|configuration| configuration.eliminated_after_round.is_none())
+        {
+            if should_cancel.load(Ordering::Relaxed) {
+                return Err("Task was cancelled".to_string());
+            }
+
+            println!(
+                "-> Tuning round {} : {:?}",
+                round + 1,
+                configuration.parameters
+            );
+            let (evaluations, solutions) = run(&configuration.parameters, seed)?;
+            configuration.runs.push(TuningRun {
+                seed,
+                evaluations,
+                hypervolume: 0.0,
+                solutions,
+            });
+        }
+
+        score(&mut configurations);
+
+        // Racing : the worst half of the remaining configurations is eliminated
+        if settings.method == TuningMethod::Racing && round + 1 < settings.repetitions {
+            let mut alive: Vec<usize> = (0..configurations.len())
+                .filter(|&i| configurations[i].eliminated_after_round.is_none())
+                .collect();
+            alive.sort_by(|&a, &b| {
+                configurations[b]
+                    .mean_hypervolume_per_evaluation
+                    .total_cmp(&configurations[a].mean_hypervolume_per_evaluation)
+            });
+            for &i in alive.iter().skip(alive.len().div_ceil(2)) {
+                configurations[i].eliminated_after_round = Some(round + 1);
+            }
+        }
+    }
+
+    // The survivors first (an eliminated configuration is only scored on its first rounds), then
+    // the configurations eliminated the latest
+    let reference_point = score(&mut configurations);
+    configurations.sort_by(|a, b| {
+        let round = |configuration: &ConfigurationResult| {
+            configuration.eliminated_after_round.unwrap_or(usize::MAX)
+        };
+        round(b).cmp(&round(a)).then(
+            b.mean_hypervolume_per_evaluation
+                .total_cmp(&a.mean_hypervolume_per_evaluation),
+        )
+    });
+
+    Ok((configurations, reference_point))
+}
+
+// Run the algorithm of the MHObject with the given parameter values and seed
+fn run_configuration(
+    pool: &PgPool,
+    kline_collections: &Vec<KlineCollection>,
+    indicators: &Vec<Indicator>,
+    mh_object: &MHObject,
+    parameters: &Map<String, Value>,
+    seed: u64,
+) -> Result<StrategyResult, String> {
+    let mut mh_parameters: Map<String, Value> = serde_json::from_str(&mh_object.mh_parameters)
+        .map_err(|_| "Invalid parameters for the algorithm".to_string())?;
+    mh_parameters.extend(parameters.clone());
+    mh_parameters.insert("seed".to_string(), Value::String(seed.to_string()));
+
+    // Only the solutions are scored : no Monte Carlo analysis of their trades
+    let mut other_parameters: Map<String, Value> = match &mh_object.other_parameters {
+        Some(other_parameters) if !other_parameters.trim().is_empty() => {
+            serde_json::from_str(other_parameters)
+                .map_err(|_| "Invalid other parameters".to_string())?
+        }
+        _ => Map::new(),
+    };
+    other_parameters.insert("monte_carlo".to_string(), Value::Bool(false));
+
+    let mh_object = MHObject {
+        mh_parameters: Value::Object(mh_parameters).to_string(),
+        other_parameters: Some(Value::Object(other_parameters).to_string()),
+        ..mh_object.clone()
+    };
+
+    evaluation::evaluate(
+        pool,
+        kline_collections,
+        indicators,
+        &mh_object,
+        None,
+        None,
+        None,
+    )
+}
+
+// Score the runs of all the configurations against a common reference point (returned)
+fn score(configurations: &mut [ConfigurationResult]) -> Vec<f64> {
+    let all_solutions: Vec<Solution> = configurations
+        .iter()
+        .flat_map(|configuration| &configuration.runs)
+        .flat_map(|run| run.solutions.clone())
+        .collect();
+    let num_objectives = all_solutions
+        .first()
+        .map(|solution| solution.objectives.len())
+        .unwrap_or(0);
+    let reference_point = reference_point(&all_solutions, num_objectives);
+
+    for configuration in configurations.iter_mut() {
+        for run in configuration.runs.iter_mut() {
+            run.hypervolume = solutions_hypervolume(&run.solutions, &reference_point);
+        }
+
+        let runs = configuration.runs.len().max(1) as f64;
+        configuration.mean_hypervolume = configuration
+            .runs
+            .iter()
+            .map(|run| run.hypervolume)
+            .sum::<f64>()
+            / runs;
+        configuration.mean_hypervolume_per_evaluation = configuration
+            .runs
+            .iter()
+            .map(|run| run.hypervolume / run.evaluations.max(1) as f64)
+            .sum::<f64>()
+            / runs;
+    }
+
+    reference_point
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metaheuristic::test_utils::solution_with;
+
+    #[test]
+    fn test_grid_configurations() {
+        let settings = TuningSettings::from_json(
+            &serde_json::json!({
+                "method": "grid",
+                "levels": 3,
+                "parameters": { "population_size": [10, 30], "mutation_rate": [0.1, 0.3] }
+            }),
+            "NSGA-II",
+        )
+        .unwrap();
+
+        let configurations = settings.generate_configurations();
+        assert_eq!(configurations.len(), 9);
+        assert_eq!(configurations[0]["population_size"], "10");
+        assert_eq!(configurations[8]["population_size"], "30");
+        assert_eq!(configurations[4]["population_size"], "20");
+        let mutation_rate: f64 = configurations[4]["mutation_rate"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((mutation_rate - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_untunable_parameter() {
+        let settings = TuningSettings::from_json(
+            &serde_json::json!({ "parameters": { "seed": [0, 10] } }),
+            "NSGA-II",
+        );
+        assert!(settings.is_err());
+
+        // Without ranges, only the parameters with finite bounds are tuned
+        let settings = TuningSettings::from_json(&serde_json::json!({}), "NSGA-II").unwrap();
+        let names: Vec<&str> = settings
+            .parameters
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["mutation_rate", "crossover_rate"]);
+    }
+
+    #[test]
+    fn test_racing_eliminates_dominated_configurations() {
+        let settings = TuningSettings::from_json(
+            &serde_json::json!({
+                "method": "racing",
+                "configurations": 4,
+                "repetitions": 3,
+                "seed": 1,
+                "parameters": { "mutation_rate": [0.0, 1.0] }
+            }),
+            "NSGA-II",
+        )
+        .unwrap();
+        let rate = |parameters: &Map<String, Value>| -> f64 {
+            parameters["mutation_rate"]
+                .as_str()
+                .unwrap()
+                .parse()
+                .unwrap()
+        };
+        let rates: Vec<f64> = settings
+            .generate_configurations()
+            .iter()
+            .map(rate)
+            .collect();
+        let worst = rates.iter().cloned().fold(f64::INFINITY, f64::min);
+        let best = rates.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+
+        // The higher the rate, the better (dominating) the single solution of a run
+        let mut runs = 0;
+        let (configurations, _) = race(
+            &settings,
+            |parameters, _| {
+                runs += 1;
+                let r = rate(parameters);
+                Ok((10, vec![solution_with(vec![1.0 - r, 1.0 - r], vec![])]))
+            },
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        // 4 runs in the first round, 2 in the second and 1 in the last
+        assert_eq!(runs, 7);
+        let dominated = configurations
+            .iter()
+            .find(|configuration| rate(&configuration.parameters) == worst)
+            .unwrap();
+        assert_eq!(dominated.eliminated_after_round, Some(1));
+        assert_eq!(dominated.runs.len(), 1);
+
+        assert_eq!(rate(&configurations[0].parameters), best);
+        assert_eq!(configurations[0].eliminated_after_round, None);
+        assert_eq!(configurations[0].runs.len(), 3);
+    }
+
+    #[test]
+    fn test_racing_survivors_ranked_first() {
+        let settings = TuningSettings::from_json(
+            &serde_json::json!({
+                "method": "racing",
+                "configurations": "4",
+                "repetitions": "3",
+                "seed": MAX_SEED.to_string(),
+                "parameters": { "mutation_rate": [0.0, 1.0] }
+            }),
+            "NSGA-II",
+        )
+        .unwrap();
+        assert_eq!((settings.configurations, settings.repetitions), (4, 3));
+
+        // The later rounds are harder : the survivors score less than the configurations
+        // eliminated after the first round
+        let mut seeds = Vec::new();
+        let (configurations, _) = race(
+            &settings,
+            |parameters, seed| {
+                seeds.push(seed);
+                let r: f64 = parameters["mutation_rate"]
+                    .as_str()
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let round = if seed == MAX_SEED {
+                    0.0
+                } else {
+                    seed as f64 + 1.0
+                };
+                let objective = 1.0 - r + 10.0 * round;
+                Ok((10, vec![solution_with(vec![objective, objective], vec![])]))
+            },
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        // The seeds of the rounds wrap around MAX_SEED
+        seeds.dedup();
+        assert_eq!(seeds, vec![MAX_SEED, 0, 1]);
+        let eliminations: Vec<Option<usize>> = configurations
+            .iter()
+            .map(|configuration| configuration.eliminated_after_round)
+            .collect();
+        assert_eq!(eliminations, vec![None, Some(2), Some(1), Some(1)]);
+        assert!(
+            configurations[3].mean_hypervolume_per_evaluation
+                > configurations[0].mean_hypervolume_per_evaluation
+        );
+    }
+}
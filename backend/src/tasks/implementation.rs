@@ -1,22 +1,42 @@
+use sqlx::PgPool;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
 
+use crate::binance::klines::{
+    acquire::{self, KlinesWindow},
+    progress::KlinesProgressReporter,
+    resample,
+};
 use crate::interface::handlers;
-use crate::objects::{indicators, klines::KlineCollection, objects::Task};
+use crate::metaheuristic::{Checkpoint, Checkpointing, ProgressReporter, Variable};
+use crate::objects::{
+    indicators,
+    intervals::CryptoInterval,
+    klines::KlineCollection,
+    objects::{MHObject, Task},
+};
 use crate::strategy;
 use crate::tasks::implementation::indicators::IndicatorTrait;
+use crate::utils::{datetime, runtime};
 
 const FORCE_FETCH_DEFAULT: bool = false;
 const TRAINING_PERCENTAGE_DEFAULT: f64 = 0.8;
+const CHECKPOINT_INTERVAL_DEFAULT: usize = 5;
+const FETCH_CONCURRENCY_DEFAULT: usize = 4;
 
 impl Task {
-    pub async fn execute(&self, should_cancel: Arc<AtomicBool>) -> Result<String, String> {
+    pub async fn execute(
+        &self,
+        pool: &PgPool,
+        should_cancel: Arc<AtomicBool>,
+        progress_reporter: ProgressReporter,
+        klines_reporter: KlinesProgressReporter,
+    ) -> Result<String, String> {
         println!("[TASK {:?}] Starting", self.id);
 
-        let pool = crate::utils::db::get_new_pool().await;
-        let pool_state = rocket::State::from(&pool);
+        let pool_state = rocket::State::from(pool);
 
         // Get MHObject and CryptoList ids
         let mh_object_id = self.mh_object_id.unwrap();
@@ -131,9 +151,61 @@ impl Task {
             Some(value) => value.as_f64().unwrap_or(TRAINING_PERCENTAGE_DEFAULT),
             None => TRAINING_PERCENTAGE_DEFAULT,
         };
+        let checkpoint_interval = match other_parameters.get("checkpoint_interval") {
+            Some(value) => value
+                .as_u64()
+                .map(|v| v as usize)
+                .unwrap_or(CHECKPOINT_INTERVAL_DEFAULT),
+            None => CHECKPOINT_INTERVAL_DEFAULT,
+        };
+        // Number of symbols whose klines are fetched at the same time
+        let fetch_concurrency = match other_parameters.get("fetch_concurrency") {
+            Some(value) => value
+                .as_u64()
+                .map(|v| v as usize)
+                .unwrap_or(FETCH_CONCURRENCY_DEFAULT),
+            None => FETCH_CONCURRENCY_DEFAULT,
+        };
 
-        // If limit_minutes is less than 10*interval, return err
-        if limit_minutes < 10 * interval.to_minutes() {
+        // Finer interval whose klines are downloaded to build the klines of the list (ex: "Int1m"),
+        // so that each symbol is downloaded once for all the intervals
+        let resample_from = match other_parameters.get("resample_from") {
+            Some(value) => {
+                let from = CryptoInterval::parse_from(value.as_str().unwrap_or_default());
+                if !resample::can_resample(&from, interval) {
+                    return Err(format!(
+                        "{} klines cannot be built from {} klines",
+                        interval.to_string(),
+                        from.to_string()
+                    ));
+                }
+                Some(from)
+            }
+            None => None,
+        };
+
+        // Data window pinned by dates (start included, end excluded), so that the task can be run
+        // again on the same klines (otherwise the limit_count last klines of the list)
+        let date_range = match (
+            other_parameters.get("start_date"),
+            other_parameters.get("end_date"),
+        ) {
+            (Some(start), Some(end)) => {
+                let parse = |value: &serde_json::Value| {
+                    datetime::parse_datetime(value.as_str().unwrap_or_default())
+                };
+                Some((parse(start)?, parse(end)?))
+            }
+            (None, None) => None,
+            _ => return Err("start_date and end_date must be given together".to_string()),
+        };
+
+        // If the window is less than 10*interval, return err
+        let window_minutes = match date_range {
+            Some((start, end)) => (end - start).num_minutes(),
+            None => limit_minutes,
+        };
+        if window_minutes < 10 * interval.to_minutes() {
             return Err("limit_minutes must be at least 10 times the interval".to_string());
         }
 
@@ -148,21 +220,98 @@ impl Task {
             self.id, biggest_n_before_indicator
         );
 
-        // Kline Collections
+        // Klines acquisition, several symbols at a time
+        // (with the klines needed before the first one by the indicators)
+        let n_before = biggest_n_before_indicator.n_before_needed() as i64;
+        let acquired_interval = resample_from.as_ref().unwrap_or(interval);
+        let window = match (date_range, &resample_from) {
+            (Some((start, end)), _) => KlinesWindow::Range(
+                // One more kline when resampling, the first one built may be incomplete
+                start
+                    - chrono::Duration::minutes(
+                        (n_before + resample_from.is_some() as i64) * interval.to_minutes(),
+                    ),
+                end,
+            ),
+            (None, Some(from)) => KlinesWindow::Last(
+                (crypto_list.limit_count as i64 + n_before + 1) * interval.to_minutes()
+                    / from.to_minutes(),
+            ),
+            (None, None) => KlinesWindow::Last(crypto_list.limit_count as i64 + n_before),
+        };
+        let acquisitions = acquire::acquire_symbols_klines(
+            pool,
+            &crypto_symbols,
+            acquired_interval,
+            &window,
+            force_fetch,
+            fetch_concurrency,
+            Some(&klines_reporter),
+        )
+        .await;
+        for (crypto_symbol, result) in acquisitions {
+            if let Err(e) = result {
+                println!(
+                    "[TASK {:?}] Error acquiring klines for {:?}: {:?}",
+                    self.id, crypto_symbol.symbol, e
+                );
+            }
+        }
+
+        // Klines of the list built from the finer ones
+        if let Some(from) = &resample_from {
+            for crypto_symbol in &crypto_symbols {
+                let start = match &window {
+                    KlinesWindow::Range(start, _) => Some(start.timestamp_millis()),
+                    KlinesWindow::Last(_) => None,
+                };
+                if let Err(e) =
+                    resample::resample_table(pool, crypto_symbol, from, interval, start).await
+                {
+                    println!(
+                        "[TASK {:?}] Error building klines for {:?}: {:?}",
+                        self.id, crypto_symbol.symbol, e
+                    );
+                }
+            }
+        }
+
+        if should_cancel.load(Ordering::Relaxed) {
+            return Err("Task was cancelled".to_string());
+        }
+
+        // Kline Collections (the klines are already acquired)
         let mut kline_collections: Vec<KlineCollection> = vec![];
         kline_collections.reserve(crypto_symbols.len());
         for crypto_symbol in crypto_symbols {
             let mut kline_collection = KlineCollection::new();
-            match kline_collection
-                .retrieve_klines_simple(
-                    &crypto_symbol,
-                    interval,
-                    limit_minutes,
-                    training_percentage,
-                    force_fetch,
-                )
-                .await
-            {
+            let retrieved = match date_range {
+                Some(range) => {
+                    kline_collection
+                        .retrieve_klines_range(
+                            pool,
+                            &crypto_symbol,
+                            interval,
+                            range,
+                            training_percentage,
+                            false,
+                        )
+                        .await
+                }
+                None => {
+                    kline_collection
+                        .retrieve_klines_simple(
+                            pool,
+                            &crypto_symbol,
+                            interval,
+                            limit_minutes,
+                            training_percentage,
+                            false,
+                        )
+                        .await
+                }
+            };
+            match retrieved {
                 Ok(_) => {}
                 Err(e) => {
                     println!(
@@ -173,7 +322,7 @@ impl Task {
             }
 
             match kline_collection
-                .retrieve_extended_klines(&biggest_n_before_indicator)
+                .retrieve_extended_klines(pool, &biggest_n_before_indicator)
                 .await
             {
                 Ok(_) => {}
@@ -197,6 +346,20 @@ impl Task {
 
         //
 
+        // Meta-optimisation task : tuning of the parameters of the MHObject algorithm
+        if let Some(tuning) = other_parameters.get("tuning") {
+            return self
+                .execute_tuning(
+                    pool_state,
+                    tuning,
+                    &kline_collections,
+                    &indicators,
+                    &mh_object,
+                    &should_cancel,
+                )
+                .await;
+        }
+
         // MHObject evaluation
         //
         println!(
@@ -204,10 +367,70 @@ impl Task {
             self.id, mh_object.mh_algorithm_name
         );
 
-        let result = strategy::evaluate(&kline_collections, &indicators, &mh_object);
-        match result {
-            Ok(_) => {
+        // Pareto front of a previous task to start from (warm start)
+        let initial_solutions = match other_parameters.get("warm_start_task_id") {
+            Some(warm_start_task_id) => Some(
+                self.get_warm_start_solutions(pool_state, warm_start_task_id)
+                    .await?,
+            ),
+            None => None,
+        };
+
+        // Checkpoint of a previous run of this task (if it was interrupted)
+        let resume_from = match handlers::checkpoints::get_checkpoint(pool_state, self.id).await {
+            Ok(Some(state)) => match serde_json::from_str::<Checkpoint>(&state) {
+                Ok(checkpoint) => {
+                    println!(
+                        "[TASK {:?}] Resuming from checkpoint (iteration {})",
+                        self.id, checkpoint.iteration
+                    );
+                    Some(checkpoint)
+                }
+                Err(e) => {
+                    println!("[TASK {:?}] Invalid checkpoint, ignored: {:?}", self.id, e);
+                    None
+                }
+            },
+            Ok(None) => None,
+            Err(e) => {
+                println!("[TASK {:?}] Error retrieving checkpoint: {:?}", self.id, e);
+                None
+            }
+        };
+
+        // Save the state of the run in the database every checkpoint_interval generations
+        let task_id = self.id;
+        let checkpoint_pool = pool.clone();
+        let checkpointing = Checkpointing::new(
+            checkpoint_interval,
+            resume_from,
+            Arc::new(move |checkpoint: &Checkpoint| {
+                let state = serde_json::to_string(checkpoint).unwrap();
+                let res = runtime::block_on(handlers::checkpoints::save_checkpoint(
+                    rocket::State::from(&checkpoint_pool),
+                    task_id,
+                    checkpoint.iteration as i32,
+                    state,
+                ));
+                if let Err(e) = res {
+                    println!("[TASK {:?}] Error saving checkpoint: {:?}", task_id, e);
+                }
+            }),
+        );
+
+        let result = strategy::evaluate(
+            pool,
+            &kline_collections,
+            &indicators,
+            &mh_object,
+            Some(checkpointing),
+            Some(progress_reporter),
+            initial_solutions,
+        );
+        let strategy_result = match result {
+            Ok(strategy_result) => {
                 println!("[TASK {:?}] MHObject evaluated successfully", self.id);
+                strategy_result
             }
             Err(e) => {
                 println!("[TASK {:?}] Error evaluating MHObject: {:?}", self.id, e);
@@ -215,6 +438,28 @@ impl Task {
             }
         };
 
+        // Store the result (with the seed, so that the run can be reproduced)
+        let results = serde_json::to_string(&strategy_result).unwrap();
+        let result_other_parameters = serde_json::json!({ "seed": strategy_result.seed });
+        match handlers::results::create_task_result(
+            pool_state,
+            self.id,
+            results,
+            Some(result_other_parameters.to_string()),
+        )
+        .await
+        {
+            Ok(result_id) => {
+                println!("[TASK {:?}] Result stored (id: {})", self.id, result_id);
+                // The run is over, its checkpoint is not needed anymore
+                let _ = handlers::checkpoints::delete_checkpoint(pool_state, self.id).await;
+            }
+            Err(e) => {
+                println!("[TASK {:?}] Error storing result: {:?}", self.id, e);
+                return Err("Error storing result".to_string());
+            }
+        };
+
         // Dummy task
         // let mut i: i64 = 0;
         // for _ in 0..i32::MAX {
@@ -223,4 +468,130 @@ impl Task {
 
         Ok("Task completed successfully".to_string())
     }
+
+    /// Variables of the Pareto front found by a previous task on the same indicator combination
+    async fn get_warm_start_solutions(
+        &self,
+        pool_state: &rocket::State<sqlx::PgPool>,
+        warm_start_task_id: &serde_json::Value,
+    ) -> Result<Vec<Vec<Variable>>, String> {
+        let warm_start_task_id = match warm_start_task_id {
+            serde_json::Value::Number(number) => number.as_i64().map(|id| id.to_string()),
+            serde_json::Value::String(string) => Some(string.trim().to_string()),
+            _ => None,
+        }
+        .filter(|id| id.parse::<i32>().is_ok())
+        .ok_or_else(|| "Invalid warm start task id".to_string())?;
+
+        let warm_start_task = handlers::tasks::get_tasks(pool_state, Some(warm_start_task_id))
+            .await
+            .map_err(|_| "Warm start task not found".to_string())?
+            .into_inner()
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Warm start task not found".to_string())?;
+
+        // Same indicator combination, so that the variables have the same layout
+        if warm_start_task.indicator_combination_id != self.indicator_combination_id {
+            return Err("Warm start task uses another indicator combination".to_string());
+        }
+
+        let result_id = warm_start_task
+            .result_id
+            .ok_or_else(|| "Warm start task has no result".to_string())?;
+        let result = handlers::results::get_results(pool_state, Some(result_id))
+            .await
+            .into_inner()
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Warm start task has no result".to_string())?;
+
+        let results: serde_json::Value = serde_json::from_str(&result.results)
+            .map_err(|_| "Invalid result of the warm start task".to_string())?;
+        let solutions = results
+            .get("solutions")
+            .and_then(|solutions| solutions.as_array())
+            .ok_or_else(|| "The warm start task has no solutions".to_string())?;
+
+        // First front only (objectives are not needed, and may not be parsable if infinite)
+        let front: Vec<Vec<Variable>> = solutions
+            .iter()
+            .filter(|solution| solution.get("rank").and_then(|rank| rank.as_u64()) == Some(0))
+            .filter_map(|solution| serde_json::from_value(solution.get("variables")?.clone()).ok())
+            .collect();
+
+        println!(
+            "[TASK {:?}] Warm start from task {:?} ({} solutions)",
+            self.id,
+            warm_start_task.id,
+            front.len()
+        );
+
+        Ok(front)
+    }
+
+    async fn execute_tuning(
+        &self,
+        pool_state: &rocket::State<sqlx::PgPool>,
+        tuning: &serde_json::Value,
+        kline_collections: &Vec<KlineCollection>,
+        indicators: &Vec<indicators::Indicator>,
+        mh_object: &MHObject,
+        should_cancel: &AtomicBool,
+    ) -> Result<String, String> {
+        println!(
+            "[TASK {:?}] Tuning MHObject (mh algorithm name : {})",
+            self.id, mh_object.mh_algorithm_name
+        );
+
+        let settings = strategy::TuningSettings::from_json(tuning, &mh_object.mh_algorithm_name)
+            .map_err(|e| {
+                println!("[TASK {:?}] Invalid tuning settings: {:?}", self.id, e);
+                "Invalid tuning settings".to_string()
+            })?;
+
+        let tuning_result = match strategy::tune(
+            pool_state,
+            kline_collections,
+            indicators,
+            mh_object,
+            &settings,
+            should_cancel,
+        ) {
+            Ok(tuning_result) => tuning_result,
+            Err(e) => {
+                println!("[TASK {:?}] Error tuning MHObject: {:?}", self.id, e);
+                return Err("Error tuning MHObject".to_string());
+            }
+        };
+
+        if let Some(best) = tuning_result.configurations.first() {
+            println!(
+                "[TASK {:?}] Best parameters: {:?} (hypervolume per evaluation: {})",
+                self.id, best.parameters, best.mean_hypervolume_per_evaluation
+            );
+        }
+
+        let results = serde_json::to_string(&tuning_result).unwrap();
+        let result_other_parameters =
+            serde_json::json!({ "type": "tuning", "seed": settings.seed });
+        match handlers::results::create_task_result(
+            pool_state,
+            self.id,
+            results,
+            Some(result_other_parameters.to_string()),
+        )
+        .await
+        {
+            Ok(result_id) => {
+                println!("[TASK {:?}] Result stored (id: {})", self.id, result_id);
+            }
+            Err(e) => {
+                println!("[TASK {:?}] Error storing result: {:?}", self.id, e);
+                return Err("Error storing result".to_string());
+            }
+        };
+
+        Ok("Tuning completed successfully".to_string())
+    }
 }
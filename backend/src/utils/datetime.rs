@@ -7,3 +7,37 @@ pub fn convert_primitive_to_chrono(primitive: PrimitiveDateTime) -> DateTime<Utc
         .expect("Failed to parse datetime");
     Utc.from_utc_datetime(&naive_datetime).into()
 }
+
+// Parse a date (2022-01-01) or a date time (2022-01-01 12:00:00, RFC 3339), in UTC by default
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    if let Ok(naive_datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(Utc.from_utc_datetime(&naive_datetime));
+    }
+    match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap())),
+        Err(_) => Err(format!("Invalid date: {}", value)),
+    }
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_datetime() {
+        let expected = Utc.with_ymd_and_hms(2022, 6, 1, 0, 0, 0).unwrap();
+        assert_eq!(parse_datetime("2022-06-01").unwrap(), expected);
+        assert_eq!(parse_datetime("2022-06-01 00:00:00").unwrap(), expected);
+        assert_eq!(
+            parse_datetime("2022-06-01T02:00:00+02:00").unwrap(),
+            expected
+        );
+        assert!(parse_datetime("June 2022").is_err());
+    }
+}
@@ -1,8 +1,201 @@
+use serde::Serialize;
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::env;
+use std::str::FromStr;
+use std::time::Duration;
 
-pub async fn get_new_pool() -> PgPool {
-    PgPool::connect(&env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
+// Migrations of backend/migrations, embedded in the binary
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+// Demo data (see DATABASE_SEED)
+const DEMO_SEED: &str = include_str!("../../seeds/demo.sql");
+
+/// Settings of the connection pool shared by the whole application
+/// (DATABASE_MAX_CONNECTIONS, DATABASE_MIN_CONNECTIONS, DATABASE_ACQUIRE_TIMEOUT and
+/// DATABASE_IDLE_TIMEOUT, in seconds)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        PoolSettings {
+            max_connections: 10,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+impl PoolSettings {
+    pub fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    // Settings from the given variables (the default ones for the missing variables)
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let default = PoolSettings::default();
+        let settings = PoolSettings {
+            max_connections: parse_setting(&lookup, "DATABASE_MAX_CONNECTIONS")?
+                .unwrap_or(default.max_connections),
+            min_connections: parse_setting(&lookup, "DATABASE_MIN_CONNECTIONS")?
+                .unwrap_or(default.min_connections),
+            acquire_timeout: parse_setting(&lookup, "DATABASE_ACQUIRE_TIMEOUT")?
+                .map(Duration::from_secs)
+                .unwrap_or(default.acquire_timeout),
+            idle_timeout: parse_setting(&lookup, "DATABASE_IDLE_TIMEOUT")?
+                .map(Duration::from_secs)
+                .unwrap_or(default.idle_timeout),
+        };
+
+        if settings.max_connections == 0 || settings.min_connections > settings.max_connections {
+            return Err(format!(
+                "Invalid pool size: {} to {} connections",
+                settings.min_connections, settings.max_connections
+            ));
+        }
+        Ok(settings)
+    }
+}
+
+fn parse_setting<T: FromStr>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    lookup(name)
+        .map(|value| {
+            value
+                .parse::<T>()
+                .map_err(|_| format!("Invalid value for {}: {}", name, value))
+        })
+        .transpose()
+}
+
+/// Pool of the application, created once at startup and shared by the API, the task manager,
+/// the klines listeners and the data access functions
+pub async fn create_pool(settings: &PoolSettings) -> Result<PgPool, String> {
+    let database_url = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL must be set")?;
+    PgPoolOptions::new()
+        .max_connections(settings.max_connections)
+        .min_connections(settings.min_connections)
+        .acquire_timeout(settings.acquire_timeout)
+        .idle_timeout(settings.idle_timeout)
+        .connect(&database_url)
+        .await
+        .map_err(|e| format!("Failed to create pool: {}", e))
+}
+
+/// Connections of the pool
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolMetrics {
+    pub size: u32,
+    pub idle: usize,
+    pub in_use: usize,
+    pub max_connections: u32,
+    pub min_connections: u32,
+}
+
+impl PoolMetrics {
+    pub fn new(pool: &PgPool) -> Self {
+        let size = pool.size();
+        let idle = pool.num_idle();
+        PoolMetrics {
+            size,
+            idle,
+            in_use: (size as usize).saturating_sub(idle),
+            max_connections: pool.options().get_max_connections(),
+            min_connections: pool.options().get_min_connections(),
+        }
+    }
+}
+
+/// Apply the migrations not applied yet (the applied ones are recorded in _sqlx_migrations)
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Insert the demo data when the database is empty (no crypto symbol)
+/// Returns whether the data was inserted
+pub async fn seed_demo_data(pool: &PgPool) -> Result<bool, sqlx::Error> {
+    let empty: bool = sqlx::query_scalar("SELECT NOT EXISTS (SELECT 1 FROM crypto_symbol)")
+        .fetch_one(pool)
+        .await?;
+    if !empty {
+        return Ok(false);
+    }
+
+    let mut transaction = pool.begin().await?;
+    sqlx::raw_sql(DEMO_SEED).execute(&mut *transaction).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+// Schema up to date, with the demo data when DATABASE_SEED=true
+pub async fn prepare_database(pool: &PgPool) -> Result<(), String> {
+    run_migrations(pool)
         .await
-        .expect("Failed to create pool.")
+        .map_err(|e| format!("Failed to apply the migrations: {}", e))?;
+    println!("Database migrations applied");
+
+    if env::var("DATABASE_SEED").as_deref() == Ok("true") {
+        match seed_demo_data(pool).await {
+            Ok(true) => println!("Demo data inserted"),
+            Ok(false) => println!("Database not empty, demo data skipped"),
+            Err(e) => return Err(format!("Failed to insert the demo data: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+//// Tests
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations() {
+        // Versions in order, without gaps (a new migration takes the next version)
+        let versions: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect();
+        assert_eq!(versions, (1..=versions.len() as i64).collect::<Vec<i64>>());
+        assert!(versions.len() >= 2);
+    }
+
+    #[test]
+    fn test_pool_settings() {
+        let settings = PoolSettings::from_lookup(|_| None).unwrap();
+        assert_eq!(settings, PoolSettings::default());
+
+        let settings = PoolSettings::from_lookup(|name| match name {
+            "DATABASE_MAX_CONNECTIONS" => Some("20".to_string()),
+            "DATABASE_ACQUIRE_TIMEOUT" => Some("5".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(settings.max_connections, 20);
+        assert_eq!(settings.acquire_timeout, Duration::from_secs(5));
+        assert_eq!(settings.idle_timeout, Duration::from_secs(600));
+
+        assert!(PoolSettings::from_lookup(|name| match name {
+            "DATABASE_MAX_CONNECTIONS" => Some("ten".to_string()),
+            _ => None,
+        })
+        .is_err());
+        assert!(PoolSettings::from_lookup(|name| match name {
+            "DATABASE_MIN_CONNECTIONS" => Some("20".to_string()),
+            _ => None,
+        })
+        .is_err());
+    }
 }
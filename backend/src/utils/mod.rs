@@ -1,4 +1,4 @@
 pub mod datetime;
 pub mod db;
-pub mod loading;
+pub mod runtime;
 pub mod time;
@@ -0,0 +1,8 @@
+use std::future::Future;
+
+// Run a future (a database access) to completion from the synchronous code of a task, on the runtime
+// of the current thread. The thread of a task is inside the block_on of the runtime, hence
+// block_in_place (a no-op on the threads spawned by the algorithms, which only enter the runtime)
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(future))
+}
@@ -1,5 +1,4 @@
--- Connect to the database
-\c mydb;
+-- Schema of the first databases (database/init.sql before the migrations), with some of its data
 
 -- Create crypto_symbol Table
 CREATE TABLE IF NOT EXISTS crypto_symbol (
@@ -84,10 +83,6 @@ CREATE TABLE IF NOT EXISTS indicator_in_combination (
     FOREIGN KEY (indicator_combination_id) REFERENCES indicator_combination(id)
 );
 
-
----------------------------------------------------------------
-
-
 -- Insert data into crypto_symbol Table
 INSERT INTO crypto_symbol (symbol, name, volume, last_updated)
 VALUES ('BTC', 'Bitcoin', 1.0, '2021-01-01 00:00:00'),
@@ -102,57 +97,3 @@ VALUES ('Top 2', 'Top', 'Int1h', 20);
 INSERT INTO crypto_list_x_crypto_symbol (crypto_list_id, crypto_symbol_id)
 VALUES (1, 1),
        (1, 2);
-
--- Show crypto symbol for the crypto list 'Top 2'
-SELECT cs.symbol, cs.name
-FROM crypto_symbol cs
-JOIN crypto_list_x_crypto_symbol clxcs
-ON cs.id = clxcs.crypto_symbol_id
-JOIN crypto_list cl
-ON cl.id = clxcs.crypto_list_id
-WHERE cl.name = 'Top 2';
-
--- Insert data into mh_object Table
-INSERT INTO mh_object (mh_algorithm_name, mh_parameters, other_parameters)
-VALUES ('mh_1', '{"param1": "value1"}', '{"other_param1": "other_value1"}'),
-       ('mh_2', '{"param2": "value2"}', '{"other_param2": "other_value2"}');
-
--- Insert data into result Table
-INSERT INTO result (results, other_parameters)
-VALUES ('{"result1": "result_value1"}', '{"other_result1": "other_result_value1"}'),
-       ('{"result2": "result_value2"}', '{"other_result2": "other_result_value2"}');
-
--- Insert data into task Table
-INSERT INTO task (state, other_parameters, mh_object_id, crypto_list_id, result_id)
-VALUES ('PENDING', '{"task_param1": "task_value1"}', 1, 1, NULL),
-       ('COMPLETED', '{"task_param2": "task_value2"}', 2, 1, 2);
-
--- Show tasks with mh_object and result information
-SELECT t.id, t.state, t.other_parameters, mh.mh_parameters, r.results
-FROM task t
-LEFT JOIN mh_object mh
-ON t.mh_object_id = mh.id
-LEFT JOIN result r
-ON t.result_id = r.id;
-
--- Update task state to 'COMPLETED' and set result_id to 1
-UPDATE task
-SET state = 'FAILED',
-    result_id = 1
-WHERE id = 1;
-
--- Show all tasks for the crypto list 'Top 2'
-SELECT t.id, t.state, t.other_parameters, mh.mh_parameters, r.results
-FROM task t
-LEFT JOIN mh_object mh
-ON t.mh_object_id = mh.id
-LEFT JOIN result r
-ON t.result_id = r.id
-WHERE t.crypto_list_id = 1;
-
--- Show all crypto symbols
-SELECT *
-FROM crypto_symbol;
-
--- Show tables
-\dt
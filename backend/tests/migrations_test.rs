@@ -0,0 +1,209 @@
+// Migrations of a database created by the first version of database/init.sql
+// (skipped unless TEST_DATABASE_URL is set, ex: postgres://postgres@localhost:5432/postgres ;
+// the migration_test_* databases are dropped and created again)
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, PgPool};
+use std::str::FromStr;
+
+use backend::utils::db::run_migrations;
+
+const BASELINE_SCHEMA: &str = include_str!("fixtures/baseline_schema.sql");
+
+async fn baseline_database(url: &str, name: &str) -> PgPool {
+    let options = PgConnectOptions::from_str(url).unwrap();
+    let mut connection = options.connect().await.unwrap();
+    for statement in [
+        format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"),
+        format!("CREATE DATABASE {name}"),
+    ] {
+        sqlx::raw_sql(&statement)
+            .execute(&mut connection)
+            .await
+            .unwrap();
+    }
+
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect_with(options.database(name))
+        .await
+        .unwrap();
+    sqlx::raw_sql(BASELINE_SCHEMA).execute(&pool).await.unwrap();
+    pool
+}
+
+async fn insert_symbol(pool: &PgPool, symbol: &str, market: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO crypto_symbol (symbol, name, volume, last_updated, market)
+        VALUES ($1, '', 1.0, '2024-01-01 00:00:00', $2::market_type)
+        "#,
+    )
+    .bind(symbol)
+    .bind(market)
+    .execute(pool)
+    .await
+    .map(|_| ())
+}
+
+// Per-symbol klines table as created before the kline table, with `count` klines `gap` ms apart
+async fn legacy_klines_table(pool: &PgPool, table_name: &str, count: i64, gap: i64) {
+    sqlx::raw_sql(&format!(
+        r#"
+        CREATE TABLE {table_name} (
+            open_time BIGINT PRIMARY KEY,
+            open FLOAT NOT NULL,
+            high FLOAT NOT NULL,
+            low FLOAT NOT NULL,
+            close FLOAT NOT NULL,
+            volume FLOAT NOT NULL,
+            close_time BIGINT NOT NULL,
+            quote_asset_volume FLOAT NOT NULL,
+            number_of_trades BIGINT NOT NULL,
+            taker_buy_base_asset_volume FLOAT NOT NULL,
+            taker_buy_quote_asset_volume FLOAT NOT NULL
+        );
+        INSERT INTO {table_name}
+        SELECT i * {gap}, 1.0, 2.0, 0.5, i, 10.0, (i + 1) * {gap} - 1, 100.0, 5, 4.0, 40.0
+        FROM generate_series(0, {count} - 1) AS i;
+        "#
+    ))
+    .execute(pool)
+    .await
+    .unwrap();
+}
+
+async fn kline_count(pool: &PgPool, market: &str, symbol: &str, interval: &str) -> i64 {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM kline
+        WHERE market = $1::market_type AND symbol = $2 AND interval = $3::crypto_interval
+        "#,
+    )
+    .bind(market)
+    .bind(symbol)
+    .bind(interval)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_migrations_from_baseline() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        println!("TEST_DATABASE_URL not set, test skipped");
+        return;
+    };
+    let pool = baseline_database(&url, "migration_test_markets").await;
+
+    run_migrations(&pool).await.unwrap();
+
+    // The existing symbols are USDⓈ-M futures, the same symbol may be added to another market
+    let market: String =
+        sqlx::query_scalar("SELECT market::TEXT FROM crypto_symbol WHERE symbol = 'BTC'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(market, "UsdM");
+    insert_symbol(&pool, "BTC", "Spot").await.unwrap();
+    assert!(insert_symbol(&pool, "BTC", "UsdM").await.is_err());
+
+    // The lists get a market too
+    let market: String = sqlx::query_scalar("SELECT market::TEXT FROM crypto_list WHERE id = 1")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(market, "UsdM");
+
+    // Nothing left to apply
+    run_migrations(&pool).await.unwrap();
+    pool.close().await;
+}
+
+#[tokio::test]
+async fn test_migrations_move_legacy_klines() {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        println!("TEST_DATABASE_URL not set, test skipped");
+        return;
+    };
+    let pool = baseline_database(&url, "migration_test_klines").await;
+
+    const MINUTE: i64 = 60_000;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    legacy_klines_table(&pool, "klines_btcusdt_int1h", 5, HOUR).await;
+    legacy_klines_table(&pool, "klines_coinm_btcusd_perp_int1d", 3, DAY).await;
+    legacy_klines_table(&pool, "klines_spot_ethusdt_int1m", 4, MINUTE).await;
+    // Int1m and Int1M had the same table name
+    legacy_klines_table(&pool, "klines_ethusdt_int1m", 3, 30 * DAY).await;
+
+    // Indicator columns, with a value missing for the first klines
+    sqlx::raw_sql(
+        r#"
+        ALTER TABLE klines_btcusdt_int1h ADD COLUMN i_ema_20 FLOAT;
+        ALTER TABLE klines_btcusdt_int1h ADD COLUMN i_rsi_14 FLOAT;
+        UPDATE klines_btcusdt_int1h SET i_ema_20 = close * 2 WHERE open_time >= 2 * 3600000;
+        UPDATE klines_btcusdt_int1h SET i_rsi_14 = 50.0;
+        "#,
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    run_migrations(&pool).await.unwrap();
+
+    assert_eq!(kline_count(&pool, "UsdM", "BTCUSDT", "Int1h").await, 5);
+    assert_eq!(kline_count(&pool, "CoinM", "BTCUSD_PERP", "Int1d").await, 3);
+    assert_eq!(kline_count(&pool, "Spot", "ETHUSDT", "Int1m").await, 4);
+    assert_eq!(kline_count(&pool, "UsdM", "ETHUSDT", "Int1M").await, 3);
+    assert_eq!(kline_count(&pool, "UsdM", "ETHUSDT", "Int1m").await, 0);
+
+    let (open_time, close, close_time): (i64, f64, i64) = sqlx::query_as(
+        r#"
+        SELECT open_time, close, close_time FROM kline
+        WHERE market = 'CoinM' AND symbol = 'BTCUSD_PERP' AND interval = 'Int1d'
+        ORDER BY open_time DESC LIMIT 1
+        "#,
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((open_time, close, close_time), (2 * DAY, 2.0, 3 * DAY - 1));
+
+    let values: Vec<(String, i64, f64)> = sqlx::query_as(
+        r#"
+        SELECT name, open_time, value FROM indicator_value
+        WHERE market = 'UsdM' AND symbol = 'BTCUSDT' AND interval = 'Int1h'
+        ORDER BY name, open_time
+        "#,
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    let ema: Vec<(i64, f64)> = values
+        .iter()
+        .filter(|(name, _, _)| name == "i_ema_20")
+        .map(|(_, open_time, value)| (*open_time, *value))
+        .collect();
+    assert_eq!(ema, vec![(2 * HOUR, 4.0), (3 * HOUR, 6.0), (4 * HOUR, 8.0)]);
+    assert_eq!(
+        values
+            .iter()
+            .filter(|(name, _, value)| name == "i_rsi_14" && *value == 50.0)
+            .count(),
+        5
+    );
+    assert_eq!(values.len(), 8);
+
+    // The per-symbol tables are dropped
+    let remaining: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM information_schema.tables
+        WHERE table_schema = 'public' AND table_name LIKE 'klines\_%'
+        "#,
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining, 0);
+    pool.close().await;
+}